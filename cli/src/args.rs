@@ -0,0 +1,770 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use img2pic_core::{
+    parse_palette, AlphaMode, BorderMode, BuiltinPalette, ChannelCombine, ColorSpace, DenoiseFilter, DitherMode,
+    EnergyChannels, EnergyNorm, EnergyProjection, EnhanceMode, GradientNorm, GradientOperator, JpegDeblock, PaletteSort,
+    PeriodMethod, PipelineParams, QuantizeMethod, SampleMode,
+};
+
+/// 从 AI 生成的"伪像素风"图像中检测网格并还原为真正的像素画
+///
+/// 参数与 Python 版 `energ` 保持一致。
+#[derive(Debug, Parser)]
+#[command(name = "img2pic", version, about)]
+pub struct Args {
+    /// 输入图像路径；也可以是目录或 glob 模式（如 "sprites/*.png"），此时进入批量模式
+    #[arg(long = "in", value_name = "PATH")]
+    pub input: PathBuf,
+
+    /// 输出路径，默认 out/<name>_energy_grid.png；批量模式下为输出目录，默认 out
+    #[arg(long = "out", value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// 批量模式的汇总文件（.json 或 .csv），默认 <输出目录>/summary.json
+    #[arg(long, value_name = "PATH")]
+    pub summary: Option<PathBuf>,
+
+    /// 批量模式的并行线程数（0=自动）
+    #[arg(long, default_value_t = 0)]
+    pub jobs: usize,
+
+    // energy generation
+    /// 计算梯度前的高斯模糊 sigma
+    #[arg(long, default_value_t = 1.0)]
+    pub sigma: f64,
+
+    /// 梯度能量通道：luma=灰度，rgb / oklab / lab=逐通道彩色梯度
+    #[arg(long, value_enum, default_value_t = EnergyChannelsArg::Luma)]
+    pub energy_channels: EnergyChannelsArg,
+
+    /// 彩色梯度的通道合并方式
+    #[arg(long, value_enum, default_value_t = ChannelCombineArg::Max)]
+    pub energy_combine: ChannelCombineArg,
+
+    /// 计算能量前透明度对颜色的处理
+    #[arg(long, value_enum, default_value_t = AlphaModeArg::Ignore)]
+    pub alpha_mode: AlphaModeArg,
+
+    /// alpha 梯度能量的叠加权重（0=不使用）
+    #[arg(long, default_value_t = 0.0)]
+    pub alpha_weight: f32,
+
+    /// JPEG 8x8 块效应预处理（auto=检测到块效应时去块）
+    #[arg(long, value_enum, default_value_t = JpegDeblockArg::Off)]
+    pub jpeg_deblock: JpegDeblockArg,
+
+    // denoise
+    /// 保边去噪滤波器
+    #[arg(long, value_enum, default_value_t = DenoiseArg::None)]
+    pub denoise: DenoiseArg,
+
+    /// 去噪窗口半径（中值滤波：1=3x3，2=5x5）
+    #[arg(long, default_value_t = 2)]
+    pub denoise_radius: usize,
+
+    /// 双边滤波空间标准差（像素）
+    #[arg(long, default_value_t = 2.0)]
+    pub denoise_sigma_space: f32,
+
+    /// 双边滤波值域标准差（0~1）
+    #[arg(long, default_value_t = 0.1)]
+    pub denoise_sigma_range: f32,
+
+    /// 引导滤波正则项
+    #[arg(long, default_value_t = 0.01)]
+    pub denoise_eps: f32,
+
+    /// 去噪结果用于：能量计算 / 采样 / 两者
+    #[arg(long, value_enum, default_value_t = DenoiseStageArg::Energy)]
+    pub denoise_stage: DenoiseStageArg,
+
+    /// 梯度算子
+    #[arg(long, value_enum, default_value_t = GradientOperatorArg::Sobel)]
+    pub gradient_operator: GradientOperatorArg,
+
+    /// 梯度幅值范数
+    #[arg(long, value_enum, default_value_t = GradientNormArg::L1)]
+    pub gradient_norm: GradientNormArg,
+
+    /// 卷积、梯度与网格投影的边界处理（可平铺纹理用 wrap）；省略时卷积与梯度使用 reflect101，网格投影平滑只在图像内取平均
+    #[arg(long, value_enum)]
+    pub border_mode: Option<BorderModeArg>,
+
+    // energy enhancement
+    /// 启用能量增强
+    #[arg(long)]
+    pub enhance_energy: bool,
+
+    /// 水平边缘增强系数（1.0=不增强）
+    #[arg(long, default_value_t = 1.0)]
+    pub enhance_horizontal: f32,
+
+    /// 垂直边缘增强系数（1.0=不增强）
+    #[arg(long, default_value_t = 1.0)]
+    pub enhance_vertical: f32,
+
+    /// 启用方向性增强（水平 / 垂直分别设置）
+    #[arg(long)]
+    pub enhance_directional: bool,
+
+    /// 方向增强方式（structure-tensor 抑制斜向 / 弯曲边缘）
+    #[arg(long, value_enum, default_value_t = EnhanceModeArg::Sobel)]
+    pub enhance_mode: EnhanceModeArg,
+
+    /// 结构张量积分尺度
+    #[arg(long, default_value_t = 1.5)]
+    pub structure_sigma: f64,
+
+    /// 方向增强前的离群值裁剪分位数（1.0=不裁剪）
+    #[arg(long, default_value_t = 0.999)]
+    pub enhance_clip: f64,
+
+    // energy normalization
+    /// 能量图归一化方式
+    #[arg(long, value_enum, default_value_t = EnergyNormArg::Percentile)]
+    pub energy_norm: EnergyNormArg,
+
+    /// percentile 归一化的分位数
+    #[arg(long, default_value_t = 0.99)]
+    pub energy_quantile: f64,
+
+    /// CLAHE 每轴分块数
+    #[arg(long, default_value_t = 8)]
+    pub clahe_tiles: usize,
+
+    /// CLAHE 直方图裁剪倍数
+    #[arg(long, default_value_t = 2.0)]
+    pub clahe_clip: f32,
+
+    /// 网格检测直接使用浮点能量图（不量化为 8 位）
+    #[arg(long)]
+    pub float_energy: bool,
+
+    /// 网格投影能量图：split=列 / 行分别使用 |gx| / |gy|，combined=共用合并能量图
+    #[arg(long, value_enum, default_value_t = EnergyProjectionArg::Split)]
+    pub energy_projection: EnergyProjectionArg,
+
+    // grid detection
+    /// 像素大小（像素），0=自动检测
+    #[arg(long, default_value_t = 0)]
+    pub pixel_size: usize,
+
+    /// y 轴像素大小（像素），0=与 --pixel-size 相同；仅在手动指定像素大小时生效
+    #[arg(long, default_value_t = 0)]
+    pub pixel_size_y: usize,
+
+    /// 预期网格间距（像素），仅为兼容 energ 保留；网格检测始终使用检测到（或 --pixel-size 指定）的像素大小
+    #[arg(long, default_value_t = 8)]
+    pub gap_size: usize,
+
+    /// 两轴使用同一像素大小，不分别检测（正方形像素）
+    #[arg(long)]
+    pub square_pixels: bool,
+
+    /// 亚像素网格：按拟合的非整数周期生成等距网格线（适合非整数倍放大的图像）
+    #[arg(long)]
+    pub subpixel_grid: bool,
+
+    /// 间距容差（±像素）
+    #[arg(long, default_value_t = 2)]
+    pub gap_tolerance: usize,
+
+    /// 最小能量阈值（0~1，相对于最大值）
+    #[arg(long, default_value_t = 0.15)]
+    pub min_energy: f32,
+
+    /// 1D 投影平滑窗口
+    #[arg(long, default_value_t = 3)]
+    pub smooth: usize,
+
+    /// 峰值检测滑动窗口大小（0=根据间距自动）
+    #[arg(long, default_value_t = 0)]
+    pub window_size: usize,
+
+    /// 插值阈值：间距大于 typical * 该值时插入插值线
+    #[arg(long, default_value_t = 1.5)]
+    pub interp_threshold: f32,
+
+    /// 自动检测时的最小像素周期
+    #[arg(long, default_value_t = 4)]
+    pub min_s: usize,
+
+    /// 自动检测时的最大像素周期（0=根据图像尺寸自动确定）
+    #[arg(long, default_value_t = 24)]
+    pub max_s: usize,
+
+    /// 根据图像尺寸自动确定搜索范围（2 到短边的 1/4，粗到细搜索），忽略 --min-s / --max-s
+    #[arg(long)]
+    pub auto_range: bool,
+
+    /// 像素周期检测方法（spectral 适合较大的 --max-s 或大面积平坦区域）
+    #[arg(long, value_enum, default_value_t = PeriodMethodArg::Autocorr)]
+    pub period_method: PeriodMethodArg,
+
+    // visualization
+    /// 检测到的网格线颜色
+    #[arg(long, value_enum, default_value_t = LineColor::Red)]
+    pub line_color: LineColor,
+
+    /// 网格线宽度（像素）
+    #[arg(long, default_value_t = 1)]
+    pub line_width: usize,
+
+    /// 额外保存纯能量图（用于调试）
+    #[arg(long)]
+    pub save_energy: bool,
+
+    // pixel art sampling
+    /// 生成像素画
+    #[arg(long)]
+    pub sample: bool,
+
+    /// 采样模式
+    #[arg(long, value_enum, default_value_t = SampleModeArg::Center)]
+    pub sample_mode: SampleModeArg,
+
+    /// weighted 模式的权重比例（0.1-0.9）
+    #[arg(long, default_value_t = 0.6)]
+    pub sample_weight_ratio: f32,
+
+    /// 像素画放大倍数（0=使用像素大小，1=原生分辨率，>1=自定义）
+    #[arg(long, default_value_t = 0)]
+    pub upscale: usize,
+
+    /// 输出原生分辨率像素画（每个单元格 1 像素）
+    #[arg(long)]
+    pub native_res: bool,
+
+    // color quantization
+    /// 对像素画进行颜色量化
+    #[arg(long)]
+    pub quantize: bool,
+
+    /// 量化模式：smart=相似度合并，force=中位切分（同 median-cut），或 octree / kmeans
+    #[arg(long, value_enum, default_value_t = QuantizeModeArg::Smart)]
+    pub quantize_mode: QuantizeModeArg,
+
+    /// 目标颜色数（0=自动）
+    #[arg(long, default_value_t = 0)]
+    pub colors: usize,
+
+    /// smart 模式的颜色相似度阈值（0.0-1.0）
+    #[arg(long, default_value_t = 0.8)]
+    pub similarity_threshold: f32,
+
+    // fixed palette
+    /// 映射到固定调色板：内置名称（pico8 / nes / gameboy / cga / db16 / db32 / endesga32）
+    /// 或调色板文件路径（.gpl / .hex / .pal / Paint.NET .txt）
+    #[arg(long, value_name = "NAME|PATH")]
+    pub palette: Option<String>,
+
+    /// 调色板最近色匹配的色彩空间
+    #[arg(long, value_enum, default_value_t = ColorSpaceArg::Rgb)]
+    pub palette_space: ColorSpaceArg,
+
+    // dithering
+    /// 减色时的抖动模式（需配合 --quantize 或 --palette）
+    #[arg(long, value_enum, default_value_t = DitherModeArg::None)]
+    pub dither: DitherModeArg,
+
+    /// 抖动强度（0.0-1.0）
+    #[arg(long, default_value_t = 1.0)]
+    pub dither_strength: f32,
+
+    /// 完全透明的单元格也进行抖动（默认跳过）
+    #[arg(long)]
+    pub dither_transparent: bool,
+
+    // palette export
+    /// 导出像素画实际使用的调色板，可多选（如 gpl,hex,ase,png），写到 <out_stem>_palette.<ext>
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub export_palette: Vec<PaletteExportArg>,
+
+    /// 导出调色板的排序方式
+    #[arg(long, value_enum, default_value_t = PaletteSortArg::Frequency)]
+    pub palette_sort: PaletteSortArg,
+}
+
+/// 能量通道（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EnergyChannelsArg {
+    Luma,
+    Rgb,
+    Oklab,
+    Lab,
+}
+
+impl From<EnergyChannelsArg> for EnergyChannels {
+    fn from(channels: EnergyChannelsArg) -> Self {
+        match channels {
+            EnergyChannelsArg::Luma => EnergyChannels::Luma,
+            EnergyChannelsArg::Rgb => EnergyChannels::Rgb,
+            EnergyChannelsArg::Oklab => EnergyChannels::Oklab,
+            EnergyChannelsArg::Lab => EnergyChannels::Lab,
+        }
+    }
+}
+
+/// 通道合并方式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChannelCombineArg {
+    Max,
+    Sum,
+    DiZenzo,
+}
+
+impl From<ChannelCombineArg> for ChannelCombine {
+    fn from(combine: ChannelCombineArg) -> Self {
+        match combine {
+            ChannelCombineArg::Max => ChannelCombine::Max,
+            ChannelCombineArg::Sum => ChannelCombine::Sum,
+            ChannelCombineArg::DiZenzo => ChannelCombine::DiZenzo,
+        }
+    }
+}
+
+/// 透明度处理方式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AlphaModeArg {
+    Ignore,
+    Premultiply,
+    Mask,
+}
+
+impl From<AlphaModeArg> for AlphaMode {
+    fn from(mode: AlphaModeArg) -> Self {
+        match mode {
+            AlphaModeArg::Ignore => AlphaMode::Ignore,
+            AlphaModeArg::Premultiply => AlphaMode::Premultiply,
+            AlphaModeArg::Mask => AlphaMode::Mask,
+        }
+    }
+}
+
+/// 去噪滤波器（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DenoiseArg {
+    None,
+    Bilateral,
+    Median,
+    Guided,
+    Kuwahara,
+}
+
+impl From<DenoiseArg> for DenoiseFilter {
+    fn from(filter: DenoiseArg) -> Self {
+        match filter {
+            DenoiseArg::None => DenoiseFilter::None,
+            DenoiseArg::Bilateral => DenoiseFilter::Bilateral,
+            DenoiseArg::Median => DenoiseFilter::Median,
+            DenoiseArg::Guided => DenoiseFilter::Guided,
+            DenoiseArg::Kuwahara => DenoiseFilter::Kuwahara,
+        }
+    }
+}
+
+/// 去噪结果的使用阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DenoiseStageArg {
+    Energy,
+    Sample,
+    Both,
+}
+
+/// JPEG 去块方式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JpegDeblockArg {
+    Off,
+    On,
+    Auto,
+}
+
+impl From<JpegDeblockArg> for JpegDeblock {
+    fn from(mode: JpegDeblockArg) -> Self {
+        match mode {
+            JpegDeblockArg::Off => JpegDeblock::Off,
+            JpegDeblockArg::On => JpegDeblock::On,
+            JpegDeblockArg::Auto => JpegDeblock::Auto,
+        }
+    }
+}
+
+/// 边界处理方式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BorderModeArg {
+    Reflect101,
+    Reflect,
+    Replicate,
+    Constant,
+    Wrap,
+}
+
+impl From<BorderModeArg> for BorderMode {
+    fn from(mode: BorderModeArg) -> Self {
+        match mode {
+            BorderModeArg::Reflect101 => BorderMode::Reflect101,
+            BorderModeArg::Reflect => BorderMode::Reflect,
+            BorderModeArg::Replicate => BorderMode::Replicate,
+            BorderModeArg::Constant => BorderMode::Constant,
+            BorderModeArg::Wrap => BorderMode::Wrap,
+        }
+    }
+}
+
+/// 像素周期检测方法（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PeriodMethodArg {
+    Autocorr,
+    Spectral,
+}
+
+impl From<PeriodMethodArg> for PeriodMethod {
+    fn from(method: PeriodMethodArg) -> Self {
+        match method {
+            PeriodMethodArg::Autocorr => PeriodMethod::Autocorr,
+            PeriodMethodArg::Spectral => PeriodMethod::Spectral,
+        }
+    }
+}
+
+/// 网格投影能量图（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EnergyProjectionArg {
+    Split,
+    Combined,
+}
+
+impl From<EnergyProjectionArg> for EnergyProjection {
+    fn from(mode: EnergyProjectionArg) -> Self {
+        match mode {
+            EnergyProjectionArg::Split => EnergyProjection::Split,
+            EnergyProjectionArg::Combined => EnergyProjection::Combined,
+        }
+    }
+}
+
+/// 方向增强方式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EnhanceModeArg {
+    Sobel,
+    StructureTensor,
+}
+
+impl From<EnhanceModeArg> for EnhanceMode {
+    fn from(mode: EnhanceModeArg) -> Self {
+        match mode {
+            EnhanceModeArg::Sobel => EnhanceMode::Sobel,
+            EnhanceModeArg::StructureTensor => EnhanceMode::StructureTensor,
+        }
+    }
+}
+
+/// 能量图归一化方式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EnergyNormArg {
+    Percentile,
+    MinMax,
+    Log,
+    HistEq,
+    Clahe,
+}
+
+impl From<EnergyNormArg> for EnergyNorm {
+    fn from(norm: EnergyNormArg) -> Self {
+        match norm {
+            EnergyNormArg::Percentile => EnergyNorm::Percentile,
+            EnergyNormArg::MinMax => EnergyNorm::MinMax,
+            EnergyNormArg::Log => EnergyNorm::Log,
+            EnergyNormArg::HistEq => EnergyNorm::HistEq,
+            EnergyNormArg::Clahe => EnergyNorm::Clahe,
+        }
+    }
+}
+
+/// 梯度算子（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GradientOperatorArg {
+    Sobel,
+    Scharr,
+    Prewitt,
+    Roberts,
+    Laplacian,
+    Dog,
+    ForwardDiff,
+}
+
+impl From<GradientOperatorArg> for GradientOperator {
+    fn from(op: GradientOperatorArg) -> Self {
+        match op {
+            GradientOperatorArg::Sobel => GradientOperator::Sobel,
+            GradientOperatorArg::Scharr => GradientOperator::Scharr,
+            GradientOperatorArg::Prewitt => GradientOperator::Prewitt,
+            GradientOperatorArg::Roberts => GradientOperator::Roberts,
+            GradientOperatorArg::Laplacian => GradientOperator::Laplacian,
+            GradientOperatorArg::Dog => GradientOperator::Dog,
+            GradientOperatorArg::ForwardDiff => GradientOperator::ForwardDiff,
+        }
+    }
+}
+
+/// 梯度范数（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GradientNormArg {
+    L1,
+    L2,
+    Max,
+}
+
+impl From<GradientNormArg> for GradientNorm {
+    fn from(norm: GradientNormArg) -> Self {
+        match norm {
+            GradientNormArg::L1 => GradientNorm::L1,
+            GradientNormArg::L2 => GradientNorm::L2,
+            GradientNormArg::Max => GradientNorm::Max,
+        }
+    }
+}
+
+/// 网格线颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LineColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Cyan,
+    Magenta,
+}
+
+impl LineColor {
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            LineColor::Red => [255, 0, 0],
+            LineColor::Green => [0, 255, 0],
+            LineColor::Blue => [0, 0, 255],
+            LineColor::Yellow => [255, 255, 0],
+            LineColor::Cyan => [0, 255, 255],
+            LineColor::Magenta => [255, 0, 255],
+        }
+    }
+}
+
+/// 采样模式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SampleModeArg {
+    Center,
+    Average,
+    Weighted,
+    Direct,
+}
+
+impl From<SampleModeArg> for SampleMode {
+    fn from(mode: SampleModeArg) -> Self {
+        match mode {
+            SampleModeArg::Center => SampleMode::Center,
+            SampleModeArg::Average => SampleMode::Average,
+            SampleModeArg::Weighted => SampleMode::Weighted,
+            SampleModeArg::Direct => SampleMode::Direct,
+        }
+    }
+}
+
+/// 量化模式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QuantizeModeArg {
+    Smart,
+    #[value(alias = "force")]
+    MedianCut,
+    Octree,
+    Kmeans,
+}
+
+impl From<QuantizeModeArg> for QuantizeMethod {
+    fn from(mode: QuantizeModeArg) -> Self {
+        match mode {
+            QuantizeModeArg::Smart => QuantizeMethod::Smart,
+            QuantizeModeArg::MedianCut => QuantizeMethod::MedianCut,
+            QuantizeModeArg::Octree => QuantizeMethod::Octree,
+            QuantizeModeArg::Kmeans => QuantizeMethod::KMeans,
+        }
+    }
+}
+
+/// 色彩空间（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorSpaceArg {
+    Rgb,
+    Lab,
+    Oklab,
+}
+
+impl From<ColorSpaceArg> for ColorSpace {
+    fn from(space: ColorSpaceArg) -> Self {
+        match space {
+            ColorSpaceArg::Rgb => ColorSpace::Rgb,
+            ColorSpaceArg::Lab => ColorSpace::Lab,
+            ColorSpaceArg::Oklab => ColorSpace::Oklab,
+        }
+    }
+}
+
+/// 抖动模式（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DitherModeArg {
+    None,
+    Bayer2,
+    Bayer4,
+    Bayer8,
+    FloydSteinberg,
+    Atkinson,
+    Sierra,
+    BlueNoise,
+}
+
+impl From<DitherModeArg> for DitherMode {
+    fn from(mode: DitherModeArg) -> Self {
+        match mode {
+            DitherModeArg::None => DitherMode::None,
+            DitherModeArg::Bayer2 => DitherMode::Bayer2,
+            DitherModeArg::Bayer4 => DitherMode::Bayer4,
+            DitherModeArg::Bayer8 => DitherMode::Bayer8,
+            DitherModeArg::FloydSteinberg => DitherMode::FloydSteinberg,
+            DitherModeArg::Atkinson => DitherMode::Atkinson,
+            DitherModeArg::Sierra => DitherMode::Sierra,
+            DitherModeArg::BlueNoise => DitherMode::BlueNoise,
+        }
+    }
+}
+
+/// 调色板导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PaletteExportArg {
+    Gpl,
+    Hex,
+    Ase,
+    Png,
+}
+
+impl PaletteExportArg {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PaletteExportArg::Gpl => "gpl",
+            PaletteExportArg::Hex => "hex",
+            PaletteExportArg::Ase => "ase",
+            PaletteExportArg::Png => "png",
+        }
+    }
+}
+
+/// 调色板排序（命令行取值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PaletteSortArg {
+    Frequency,
+    Hue,
+}
+
+impl From<PaletteSortArg> for PaletteSort {
+    fn from(sort: PaletteSortArg) -> Self {
+        match sort {
+            PaletteSortArg::Frequency => PaletteSort::Frequency,
+            PaletteSortArg::Hue => PaletteSort::Hue,
+        }
+    }
+}
+
+impl Args {
+    /// 转为核心库的流程参数（会读取调色板文件）
+    pub fn pipeline_params(&self) -> Result<PipelineParams, Box<dyn Error>> {
+        let (palette, custom_palette) = match self.palette.as_deref() {
+            None => (None, Vec::new()),
+            Some(name) => match BuiltinPalette::from_name(name) {
+                Some(builtin) => (Some(builtin), Vec::new()),
+                None => {
+                    let text = fs::read_to_string(name)
+                        .map_err(|e| format!("failed to read palette {}: {}", name, e))?;
+                    (None, parse_palette(&text).map_err(|e| format!("palette {}: {}", name, e))?)
+                }
+            },
+        };
+
+        Ok(PipelineParams {
+            sigma: self.sigma,
+            energy_channels: self.energy_channels.into(),
+            energy_combine: self.energy_combine.into(),
+            alpha_mode: self.alpha_mode.into(),
+            alpha_weight: self.alpha_weight,
+            jpeg_deblock: self.jpeg_deblock.into(),
+            denoise: self.denoise.into(),
+            denoise_radius: self.denoise_radius,
+            denoise_sigma_space: self.denoise_sigma_space,
+            denoise_sigma_range: self.denoise_sigma_range,
+            denoise_eps: self.denoise_eps,
+            denoise_energy: self.denoise_stage != DenoiseStageArg::Sample,
+            denoise_sample: self.denoise_stage != DenoiseStageArg::Energy,
+            gradient_operator: self.gradient_operator.into(),
+            gradient_norm: self.gradient_norm.into(),
+            border_mode: self.border_mode.map(Into::into),
+            enhance_energy: self.enhance_energy,
+            enhance_mode: self.enhance_mode.into(),
+            structure_sigma: self.structure_sigma,
+            enhance_directional: self.enhance_directional,
+            enhance_horizontal: self.enhance_horizontal,
+            enhance_vertical: self.enhance_vertical,
+            enhance_clip_quantile: self.enhance_clip,
+            energy_norm: self.energy_norm.into(),
+            energy_quantile: self.energy_quantile,
+            clahe_tiles: self.clahe_tiles,
+            clahe_clip: self.clahe_clip,
+            float_energy: self.float_energy,
+            energy_projection: self.energy_projection.into(),
+            gap_tolerance: self.gap_tolerance,
+            interp_threshold: self.interp_threshold,
+            min_energy: self.min_energy,
+            smooth: self.smooth,
+            window_size: self.window_size,
+            pixel_size: self.pixel_size,
+            pixel_size_y: self.pixel_size_y,
+            square_pixels: self.square_pixels,
+            subpixel_grid: self.subpixel_grid,
+            min_s: self.min_s,
+            max_s: self.max_s,
+            auto_range: self.auto_range,
+            period_method: self.period_method.into(),
+            sample: self.sample,
+            sample_mode: self.sample_mode.into(),
+            sample_weight_ratio: self.sample_weight_ratio,
+            upscale: self.upscale,
+            native_res: self.native_res,
+            quantize: self.quantize,
+            quantize_method: self.quantize_mode.into(),
+            colors: self.colors,
+            similarity_threshold: self.similarity_threshold,
+            palette,
+            custom_palette,
+            palette_space: self.palette_space.into(),
+            dither: self.dither.into(),
+            dither_strength: self.dither_strength,
+            dither_skip_transparent: !self.dither_transparent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gap_size_does_not_override_detection() {
+        let args = Args::try_parse_from(["img2pic", "--in", "a.png", "--gap-size", "12"]).unwrap();
+        assert_eq!(args.pipeline_params().unwrap().pixel_size, 0);
+
+        let argv = ["img2pic", "--in", "a.png", "--pixel-size", "6", "--pixel-size-y", "12"];
+        let args = Args::try_parse_from(argv).unwrap();
+        let params = args.pipeline_params().unwrap();
+        assert_eq!((params.pixel_size, params.pixel_size_y), (6, 12));
+        assert_eq!(args.gap_size, 8);
+    }
+}
@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use img2pic_core::PipelineParams;
+use rayon::prelude::*;
+
+use crate::args::Args;
+use crate::output;
+use crate::process::{process_file, FileReport};
+
+/// 批量模式识别的图像扩展名
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "bmp", "gif"];
+
+/// 输入是否需要按批量处理（目录或 glob 模式）
+pub fn is_batch_input(input: &Path) -> bool {
+    input.is_dir() || is_glob(input)
+}
+
+fn is_glob(input: &Path) -> bool {
+    input.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// 展开目录（不递归）或 glob 模式，返回排序后的文件列表
+pub fn collect_inputs(input: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = if input.is_dir() {
+        fs::read_dir(input)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && has_image_extension(p))
+            .collect()
+    } else {
+        let pattern = input.to_string_lossy();
+        glob::glob(&pattern)
+            .map_err(|e| format!("invalid glob pattern {}: {}", pattern, e))?
+            .filter_map(|p| p.ok())
+            .filter(|p| p.is_file())
+            .collect()
+    };
+    files.sort();
+    Ok(files)
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// 并行处理所有输入，单个文件失败不影响其他文件
+pub fn run_batch(args: &Args, params: &PipelineParams, inputs: &[PathBuf]) -> Vec<FileReport> {
+    let out_dir = args.output.clone().unwrap_or_else(|| PathBuf::from("out"));
+
+    inputs
+        .par_iter()
+        .map(|input| {
+            let output_path = output::output_in_dir(input, &out_dir);
+            let report = match process_file(args, params, input, &output_path, false) {
+                Ok(report) => report,
+                Err(e) => FileReport::failed(input, e.as_ref()),
+            };
+            match &report.error {
+                None => println!(
+                    "ok    {} (pixel size {}, {}x{})",
+                    input.display(),
+                    report.pixel_size,
+                    report.output_width,
+                    report.output_height
+                ),
+                Some(e) => println!("error {}: {}", input.display(), e),
+            }
+            report
+        })
+        .collect()
+}
+
+/// 按扩展名写出汇总（.csv 为 CSV，其余为 JSON）
+pub fn write_summary(path: &Path, reports: &[FileReport]) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let is_csv = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    let mut file = fs::File::create(path)?;
+    if is_csv {
+        file.write_all(summary_csv(reports).as_bytes())?;
+    } else {
+        serde_json::to_writer_pretty(&mut file, reports)?;
+        writeln!(file)?;
+    }
+    Ok(())
+}
+
+fn summary_csv(reports: &[FileReport]) -> String {
+    let mut out = String::from(
+        "input,output,pixel_art_output,pixel_size,x_lines,y_lines,all_x_lines,all_y_lines,output_width,output_height,colors,warnings,error\n",
+    );
+    let path_field = |p: &Option<PathBuf>| p.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+    for r in reports {
+        let fields = [
+            r.input.display().to_string(),
+            path_field(&r.output),
+            path_field(&r.pixel_art_output),
+            r.pixel_size.to_string(),
+            r.x_lines.to_string(),
+            r.y_lines.to_string(),
+            r.all_x_lines.to_string(),
+            r.all_y_lines.to_string(),
+            r.output_width.to_string(),
+            r.output_height.to_string(),
+            r.colors.to_string(),
+            r.warnings.join("; "),
+            r.error.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_escape(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("a.png"), "a.png");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_summary_csv_rows() {
+        let ok = FileReport { input: "a.png".into(), pixel_size: 8, output_width: 4, output_height: 2, ..FileReport::default() };
+        let bad = FileReport { input: "b.png".into(), error: Some("bad, file".into()), ..FileReport::default() };
+        let csv = summary_csv(&[ok, bad]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "a.png,,,8,0,0,0,0,4,2,0,,");
+        assert!(lines[2].ends_with(",\"bad, file\""));
+    }
+
+    #[test]
+    fn test_has_image_extension() {
+        assert!(has_image_extension(Path::new("a/b.PNG")));
+        assert!(has_image_extension(Path::new("c.webp")));
+        assert!(!has_image_extension(Path::new("notes.txt")));
+        assert!(!has_image_extension(Path::new("noext")));
+    }
+}
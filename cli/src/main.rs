@@ -0,0 +1,64 @@
+//! img2pic 命令行工具，替代 Python 版 `energ` 脚本
+
+mod args;
+mod batch;
+mod output;
+mod process;
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use args::Args;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let result = if batch::is_batch_input(&args.input) {
+        run_batch(&args)
+    } else {
+        run_single(&args)
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_single(args: &Args) -> Result<bool, Box<dyn Error>> {
+    let output_path = output::resolve_output_path(&args.input, args.output.as_deref());
+    let params = args.pipeline_params()?;
+    process::process_file(args, &params, &args.input, &output_path, true)?;
+    Ok(true)
+}
+
+/// 批量模式：返回 false 表示至少有一个文件处理失败
+fn run_batch(args: &Args) -> Result<bool, Box<dyn Error>> {
+    let params = args.pipeline_params()?;
+    let inputs = batch::collect_inputs(&args.input)?;
+    if inputs.is_empty() {
+        return Err(format!("no images found in {}", args.input.display()).into());
+    }
+
+    if args.jobs > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.jobs).build_global()?;
+    }
+
+    println!("Processing {} images...", inputs.len());
+    let reports = batch::run_batch(args, &params, &inputs);
+
+    let summary_path = args.summary.clone().unwrap_or_else(|| {
+        args.output.clone().unwrap_or_else(|| PathBuf::from("out")).join("summary.json")
+    });
+    batch::write_summary(&summary_path, &reports)?;
+
+    let failed = reports.iter().filter(|r| r.error.is_some()).count();
+    println!("Processed {} images, {} failed", reports.len(), failed);
+    println!("Saved summary: {}", summary_path.display());
+    Ok(failed == 0)
+}
@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use img2pic_core::{decode_image, export_ase, export_gpl, export_hex, swatch_strip, RgbaImage};
+
+use crate::args::PaletteExportArg;
+
+/// 插值线颜色（蓝）
+const INTERPOLATED_COLOR: [u8; 3] = [0, 0, 255];
+/// 单元格中心点颜色（绿）
+const CENTER_COLOR: [u8; 3] = [0, 255, 0];
+
+/// 默认输出路径：out/<name>_energy_grid.png
+pub fn resolve_output_path(input: &Path, output: Option<&Path>) -> PathBuf {
+    match output {
+        Some(p) => p.to_path_buf(),
+        None => output_in_dir(input, Path::new("out")),
+    }
+}
+
+/// 指定目录下的默认输出路径：<dir>/<name>_energy_grid.png
+pub fn output_in_dir(input: &Path, dir: &Path) -> PathBuf {
+    dir.join(format!("{}_energy_grid.png", file_stem(input)))
+}
+
+/// 与主输出同目录的派生文件：<out_stem><suffix>.png
+pub fn sibling_path(output: &Path, suffix: &str) -> PathBuf {
+    output.with_file_name(format!("{}{}.png", file_stem(output), suffix))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// 读取图像（PNG / JPEG / WebP / BMP / GIF）并转为 RGBA
+pub fn load_rgba(path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let image = decode_image(&bytes).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    Ok(image)
+}
+
+/// 保存 PNG（自动创建父目录）
+pub fn save_png(
+    path: &Path,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    color: image::ExtendedColorType,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    image::save_buffer(path, data, width as u32, height as u32, color)
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// 调色板色卡条中每个色块的边长
+const SWATCH_SIZE: usize = 16;
+
+/// 导出调色板文件：<out_stem>_palette.<ext>，返回写出的路径
+pub fn export_palette(
+    output: &Path,
+    colors: &[[u8; 3]],
+    format: PaletteExportArg,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = output.with_file_name(format!("{}_palette.{}", file_stem(output), format.extension()));
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    match format {
+        PaletteExportArg::Gpl => fs::write(&path, export_gpl(colors, &file_stem(output)))?,
+        PaletteExportArg::Hex => fs::write(&path, export_hex(colors))?,
+        PaletteExportArg::Ase => fs::write(&path, export_ase(colors))?,
+        PaletteExportArg::Png => {
+            let strip = swatch_strip(colors, SWATCH_SIZE)?;
+            save_png(&path, &strip.data, strip.width, strip.height, image::ExtendedColorType::Rgba8)?;
+        }
+    }
+    Ok(path)
+}
+
+/// 在能量图上绘制网格线：
+/// 检测到的线用 line_color，插值/补全的线用蓝色，单元格中心用 1px 绿点
+#[allow(clippy::too_many_arguments)]
+pub fn draw_grid_overlay(
+    energy_u8: &[u8],
+    width: usize,
+    height: usize,
+    x_lines: &[usize],
+    y_lines: &[usize],
+    all_x_lines: &[usize],
+    all_y_lines: &[usize],
+    line_color: [u8; 3],
+    line_width: usize,
+) -> Vec<u8> {
+    let mut rgb: Vec<u8> = energy_u8.iter().flat_map(|&v| [v, v, v]).collect();
+    let mut put = |x: usize, y: usize, c: [u8; 3]| {
+        if x < width && y < height {
+            let i = (y * width + x) * 3;
+            rgb[i..i + 3].copy_from_slice(&c);
+        }
+    };
+
+    let line_width = line_width.max(1);
+    let band = |pos: usize| {
+        let start = pos.saturating_sub((line_width - 1) / 2);
+        start..start + line_width
+    };
+
+    // 先画插值线，再画检测线，使检测线在重叠处优先显示
+    for (lines, color) in [(all_x_lines, INTERPOLATED_COLOR), (x_lines, line_color)] {
+        for &pos in lines {
+            for x in band(pos) {
+                for y in 0..height {
+                    put(x, y, color);
+                }
+            }
+        }
+    }
+    for (lines, color) in [(all_y_lines, INTERPOLATED_COLOR), (y_lines, line_color)] {
+        for &pos in lines {
+            for y in band(pos) {
+                for x in 0..width {
+                    put(x, y, color);
+                }
+            }
+        }
+    }
+
+    for xs in all_x_lines.windows(2) {
+        let cx = (xs[0] + xs[1]) / 2;
+        for ys in all_y_lines.windows(2) {
+            put(cx, (ys[0] + ys[1]) / 2, CENTER_COLOR);
+        }
+    }
+
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_paths() {
+        let out = resolve_output_path(Path::new("imgs/cat.png"), None);
+        assert_eq!(out, Path::new("out/cat_energy_grid.png"));
+        assert_eq!(sibling_path(&out, "_pixel_art"), Path::new("out/cat_energy_grid_pixel_art.png"));
+    }
+
+    #[test]
+    fn test_draw_grid_overlay() {
+        let (w, h) = (5, 5);
+        let vis = draw_grid_overlay(&[0u8; 25], w, h, &[0], &[], &[0, 4], &[0, 4], [255, 0, 0], 1);
+        let px = |x: usize, y: usize| &vis[(y * w + x) * 3..(y * w + x) * 3 + 3];
+        assert_eq!(px(0, 2), &[255, 0, 0]); // 检测线
+        assert_eq!(px(4, 2), &[0, 0, 255]); // 插值线
+        assert_eq!(px(2, 2), &[0, 255, 0]); // 中心点
+        assert_eq!(px(1, 1), &[0, 0, 0]);
+    }
+}
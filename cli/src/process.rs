@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use image::ExtendedColorType;
+use img2pic_core::{
+    auto_search_range, extract_palette, DitherMode, Pipeline, PipelineParams, QuantizeMethod, SampleMode,
+};
+use serde::Serialize;
+
+use crate::args::Args;
+use crate::output;
+
+/// 仅在 verbose 时输出（批量模式下关闭逐步日志）
+macro_rules! info {
+    ($verbose:expr, $($arg:tt)*) => {
+        if $verbose {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 单个文件的处理报告（批量模式汇总用）
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub pixel_art_output: Option<PathBuf>,
+    pub pixel_size: usize,
+    pub x_lines: usize,
+    pub y_lines: usize,
+    pub all_x_lines: usize,
+    pub all_y_lines: usize,
+    /// 像素画输出尺寸（未采样时为 0）
+    pub output_width: usize,
+    pub output_height: usize,
+    /// 量化后的颜色数（未量化时为 0）
+    pub colors: usize,
+    /// 检测结果的提示信息
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    /// 处理失败时的报告
+    pub fn failed(input: &Path, err: &dyn Error) -> Self {
+        Self { input: input.to_path_buf(), error: Some(err.to_string()), ..Self::default() }
+    }
+}
+
+/// 处理单个文件：读取 → Pipeline::run → 写出网格可视化 / 能量图 / 像素画
+pub fn process_file(
+    args: &Args,
+    params: &PipelineParams,
+    input: &Path,
+    output_path: &Path,
+    verbose: bool,
+) -> Result<FileReport, Box<dyn Error>> {
+    let image = output::load_rgba(input)?;
+    info!(verbose, "Image: {}", input.display());
+    info!(verbose, "Size: {}x{}", image.width, image.height);
+
+    let result = Pipeline::run(&image, params)?;
+    let (w, h) = (result.width, result.height);
+
+    let mut report = FileReport {
+        input: input.to_path_buf(),
+        pixel_size: result.detected_pixel_size,
+        x_lines: result.x_lines.len(),
+        y_lines: result.y_lines.len(),
+        all_x_lines: result.all_x_lines.len(),
+        all_y_lines: result.all_y_lines.len(),
+        warnings: result.warnings.clone(),
+        ..FileReport::default()
+    };
+
+    if params.sample_mode != SampleMode::Direct {
+        if params.enhance_energy {
+            if params.enhance_directional {
+                info!(
+                    verbose,
+                    "Applied directional enhancement: H={}x, V={}x",
+                    params.enhance_horizontal,
+                    params.enhance_vertical
+                );
+            } else {
+                info!(verbose, "Applied general energy enhancement: 1.5x");
+            }
+        }
+
+        if args.save_energy {
+            let energy_path = output::sibling_path(output_path, "_pure_energy");
+            output::save_png(&energy_path, &result.energy_u8, w, h, ExtendedColorType::L8)?;
+            info!(verbose, "Saved pure energy map: {}", energy_path.display());
+            if !result.energy_x_u8.is_empty() {
+                for (suffix, map) in [("_energy_x", &result.energy_x_u8), ("_energy_y", &result.energy_y_u8)] {
+                    let path = output::sibling_path(output_path, suffix);
+                    output::save_png(&path, map, w, h, ExtendedColorType::L8)?;
+                    info!(verbose, "Saved axis energy map: {}", path.display());
+                }
+            }
+        }
+
+        if params.pixel_size == 0 {
+            if params.auto_range {
+                let (min_s, max_s) = auto_search_range(image.width, image.height);
+                info!(verbose, "Auto search range: {}..={}", min_s, max_s);
+            }
+            info!(verbose, "Auto-detected pixel size: {}", result.detected_pixel_size);
+            if result.detected_pixel_size_x != result.detected_pixel_size_y {
+                info!(
+                    verbose,
+                    "Per-axis pixel size: X={}, Y={}",
+                    result.detected_pixel_size_x,
+                    result.detected_pixel_size_y
+                );
+            }
+            if params.subpixel_grid {
+                info!(
+                    verbose,
+                    "Sub-pixel period: X={:.3}, Y={:.3}",
+                    result.detected_period_x,
+                    result.detected_period_y
+                );
+            }
+            if let Some(report) = &result.pixel_size_report {
+                let candidates: Vec<String> =
+                    report.candidates.iter().map(|c| format!("{} ({:.2})", c.size, c.score)).collect();
+                info!(verbose, "Detection confidence: {:.2}", report.confidence);
+                info!(verbose, "Pixel size candidates: {}", candidates.join(", "));
+            }
+        }
+        info!(verbose, "Detected X lines: {}", result.x_lines.len());
+        info!(verbose, "Detected Y lines: {}", result.y_lines.len());
+        for warning in &result.warnings {
+            eprintln!("Warning: {}: {}", input.display(), warning);
+        }
+
+        let vis = output::draw_grid_overlay(
+            &result.energy_u8,
+            w,
+            h,
+            &result.x_lines,
+            &result.y_lines,
+            &result.all_x_lines,
+            &result.all_y_lines,
+            args.line_color.rgb(),
+            args.line_width,
+        );
+        output::save_png(output_path, &vis, w, h, ExtendedColorType::Rgb8)?;
+        report.output = Some(output_path.to_path_buf());
+        info!(verbose, "Saved energy map: {}", output_path.display());
+        info!(verbose, "Detected grid lines: {} × {}", result.x_lines.len(), result.y_lines.len());
+        info!(
+            verbose,
+            "All grid lines (with interpolation): {} × {}",
+            result.all_x_lines.len(),
+            result.all_y_lines.len()
+        );
+        info!(
+            verbose,
+            "Grid cell centers: {}",
+            result.all_x_lines.len().saturating_sub(1) * result.all_y_lines.len().saturating_sub(1)
+        );
+    }
+
+    if let Some(art) = &result.pixel_art {
+        let pixel_path = output::sibling_path(output_path, "_pixel_art");
+        output::save_png(&pixel_path, &art.rgba, art.width, art.height, ExtendedColorType::Rgba8)?;
+        report.pixel_art_output = Some(pixel_path.clone());
+        report.output_width = art.width;
+        report.output_height = art.height;
+        report.colors = result.palette.len();
+        info!(verbose, "Saved pixel art: {}", pixel_path.display());
+
+        let factor = result.upscale_factor.max(1);
+        if factor == 1 {
+            info!(
+                verbose,
+                "Pixel art size: {}x{} ({}×{} pixels) - Native resolution",
+                art.width,
+                art.height,
+                art.width,
+                art.height
+            );
+        } else {
+            info!(
+                verbose,
+                "Pixel art size: {}x{} ({}×{} pixels) - {}x upscale",
+                art.width,
+                art.height,
+                art.width / factor,
+                art.height / factor,
+                factor
+            );
+        }
+        info!(verbose, "Sampling mode: {}", format!("{:?}", params.sample_mode).to_lowercase());
+        if params.sample_mode == SampleMode::Weighted {
+            info!(verbose, "Weight ratio: {}", params.sample_weight_ratio);
+        }
+        info!(verbose, "Upscale factor: {}x", factor);
+        if params.quantize {
+            if params.quantize_method == QuantizeMethod::Smart {
+                info!(
+                    verbose,
+                    "Applied smart quantization (similarity threshold: {})",
+                    params.similarity_threshold
+                );
+            } else {
+                info!(verbose, "Applied {:?} quantization", params.quantize_method);
+            }
+        }
+        if let Some(name) = &args.palette {
+            info!(verbose, "Mapped to palette: {} ({:?} matching)", name, params.palette_space);
+        }
+        if params.quantize || params.palette_colors().is_some() {
+            if params.dither != DitherMode::None {
+                info!(verbose, "Dithering: {:?} (strength {})", params.dither, params.dither_strength);
+            }
+            info!(verbose, "Final colors: {}", result.palette.len());
+        }
+    }
+
+    if let Some(cells) = result.cells.as_ref().filter(|_| !args.export_palette.is_empty()) {
+        let entries = extract_palette(
+            &cells.rgba,
+            cells.width,
+            cells.height,
+            params.quantize_params().alpha_threshold,
+            args.palette_sort.into(),
+        )?;
+        let colors: Vec<[u8; 3]> = entries.iter().map(|e| e.color).collect();
+        info!(verbose, "Palette: {} colors", colors.len());
+        for e in entries.iter().take(16) {
+            info!(
+                verbose,
+                "  #{:02X}{:02X}{:02X}  {:6} cells  {:5.1}%",
+                e.color[0],
+                e.color[1],
+                e.color[2],
+                e.count,
+                e.coverage
+            );
+        }
+        if !colors.is_empty() {
+            for &format in &args.export_palette {
+                let path = output::export_palette(output_path, &colors, format)?;
+                info!(verbose, "Saved palette: {}", path.display());
+            }
+        }
+    }
+
+    Ok(report)
+}
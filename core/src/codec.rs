@@ -0,0 +1,99 @@
+use std::io::Cursor;
+
+use image::{ExtendedColorType, ImageEncoder, ImageFormat};
+use image::codecs::png::PngEncoder;
+
+use crate::error::{check_len, Img2PicError, Result};
+use crate::pipeline::{Pipeline, PipelineParams, PipelineResult};
+use crate::types::{PixelArt, RgbaImage};
+
+/// 解码图像文件字节（PNG / JPEG / WebP / BMP / GIF）为 RGBA
+/// 格式根据文件头自动识别，GIF 只取第一帧
+pub fn decode_image(bytes: &[u8]) -> Result<RgbaImage> {
+    let format = image::guess_format(bytes).map_err(|e| Img2PicError::Decode(e.to_string()))?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Bmp | ImageFormat::Gif
+    ) {
+        return Err(Img2PicError::Decode(format!("unsupported image format: {:?}", format)));
+    }
+    let img = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| Img2PicError::Decode(e.to_string()))?
+        .to_rgba8();
+    let (width, height) = img.dimensions();
+    Ok(RgbaImage::new(width as usize, height as usize, img.into_raw()))
+}
+
+/// 将 RGBA 数据编码为 PNG（保留透明通道）
+pub fn encode_png(rgba: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    check_len("encode_png rgba", rgba.len(), width * height * 4)?;
+    let mut out = Vec::new();
+    PngEncoder::new(Cursor::new(&mut out))
+        .write_image(rgba, width as u32, height as u32, ExtendedColorType::Rgba8)
+        .map_err(|e| Img2PicError::Encode(e.to_string()))?;
+    Ok(out)
+}
+
+impl RgbaImage {
+    /// 编码为 PNG
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        encode_png(&self.data, self.width, self.height)
+    }
+}
+
+impl PixelArt {
+    /// 将像素画（out_rgba）编码为 PNG
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        encode_png(&self.rgba, self.width, self.height)
+    }
+}
+
+impl PipelineResult {
+    /// 像素画 PNG（未采样时为 None）
+    pub fn pixel_art_png(&self) -> Result<Option<Vec<u8>>> {
+        self.pixel_art.as_ref().map(PixelArt::to_png).transpose()
+    }
+}
+
+impl Pipeline {
+    /// 直接从图像文件字节运行完整流程
+    pub fn run_bytes(bytes: &[u8], params: &PipelineParams) -> Result<PipelineResult> {
+        let image = decode_image(bytes)?;
+        Self::run(&image, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_roundtrip_keeps_alpha() {
+        let rgba = vec![255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, 10, 20, 30, 40];
+        let png = encode_png(&rgba, 2, 2).unwrap();
+        let img = decode_image(&png).unwrap();
+        assert_eq!((img.width, img.height), (2, 2));
+        assert_eq!(img.data, rgba);
+    }
+
+    #[test]
+    fn test_decode_garbage() {
+        assert!(matches!(decode_image(b"not an image"), Err(Img2PicError::Decode(_))));
+    }
+
+    #[test]
+    fn test_run_bytes_pixel_art_png() {
+        let (w, h, cell) = (32, 32, 8);
+        let mut data = vec![0u8; w * h * 4];
+        for (i, px) in data.chunks_exact_mut(4).enumerate() {
+            let v = if ((i % w) / cell + (i / w) / cell).is_multiple_of(2) { 230 } else { 20 };
+            px.copy_from_slice(&[v, v, v, 200]);
+        }
+        let png = encode_png(&data, w, h).unwrap();
+        let params = PipelineParams { native_res: true, ..Default::default() };
+        let res = Pipeline::run_bytes(&png, &params).unwrap();
+        let art = decode_image(&res.pixel_art_png().unwrap().unwrap()).unwrap();
+        assert_eq!((art.width, art.height), (4, 4));
+        assert!(art.data.chunks_exact(4).all(|px| px[3] == 200));
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+/// 颜色距离计算所用的色彩空间
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    /// sRGB 原始值（0-255）
+    #[default]
+    Rgb,
+    /// CIELAB（D65）
+    Lab,
+    /// OKLab
+    Oklab,
+}
+
+impl ColorSpace {
+    /// 将 sRGB 颜色（0-255，可为小数）转换到该色彩空间
+    pub fn convert(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorSpace::Rgb => rgb,
+            ColorSpace::Lab => rgb_to_lab(rgb),
+            ColorSpace::Oklab => rgb_to_oklab(rgb),
+        }
+    }
+}
+
+/// sRGB 分量（0-255）转线性光（0-1）
+pub fn srgb_to_linear(v: f32) -> f32 {
+    let c = (v / 255.0).clamp(0.0, 1.0);
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_rgb(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(srgb_to_linear)
+}
+
+/// sRGB（0-255）转 CIELAB，D65 白点；L 范围 0-100
+pub fn rgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = linear_rgb(rgb);
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+
+    fn f(t: f32) -> f32 {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    }
+
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// sRGB（0-255）转 OKLab；L 范围 0-1
+pub fn rgb_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = linear_rgb(rgb);
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// 平方欧氏距离
+pub(crate) fn dist2(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lab_white_black() {
+        let w = rgb_to_lab([255.0, 255.0, 255.0]);
+        assert!((w[0] - 100.0).abs() < 0.1 && w[1].abs() < 0.1 && w[2].abs() < 0.1);
+        let k = rgb_to_lab([0.0, 0.0, 0.0]);
+        assert!(k[0].abs() < 0.1);
+    }
+
+    #[test]
+    fn test_oklab_white_red() {
+        let w = rgb_to_oklab([255.0, 255.0, 255.0]);
+        assert!((w[0] - 1.0).abs() < 1e-3 && w[1].abs() < 1e-3 && w[2].abs() < 1e-3);
+        // 参考值：sRGB 红 = (0.628, 0.225, 0.126)
+        let r = rgb_to_oklab([255.0, 0.0, 0.0]);
+        assert!((r[0] - 0.628).abs() < 2e-3 && (r[1] - 0.225).abs() < 2e-3 && (r[2] - 0.126).abs() < 2e-3);
+    }
+}
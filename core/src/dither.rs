@@ -0,0 +1,384 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::ColorSpace;
+use crate::error::{check_len, invalid, Result};
+use crate::palette::PaletteMatcher;
+
+/// 抖动模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DitherMode {
+    /// 不抖动，直接取最近色
+    #[default]
+    None,
+    /// Bayer 有序抖动 2x2
+    Bayer2,
+    /// Bayer 有序抖动 4x4
+    Bayer4,
+    /// Bayer 有序抖动 8x8
+    Bayer8,
+    /// Floyd–Steinberg 误差扩散
+    FloydSteinberg,
+    /// Atkinson 误差扩散（只扩散 3/4 误差，对比度更高）
+    Atkinson,
+    /// Sierra（三行）误差扩散
+    Sierra,
+    /// 蓝噪声阈值抖动
+    BlueNoise,
+}
+
+/// 抖动参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DitherParams {
+    pub mode: DitherMode,
+    /// 抖动强度 0..1（0 等价于不抖动）
+    pub strength: f32,
+    /// 跳过透明单元格：alpha 低于阈值的像素不映射，也不参与误差扩散
+    pub skip_transparent: bool,
+    /// 透明判定阈值：alpha 低于该值视为透明；默认 1，即只跳过完全透明（alpha = 0）的单元格
+    pub alpha_threshold: u8,
+}
+
+impl Default for DitherParams {
+    fn default() -> Self {
+        Self {
+            mode: DitherMode::None,
+            strength: 1.0,
+            skip_transparent: true,
+            alpha_threshold: 1,
+        }
+    }
+}
+
+/// 误差扩散核：(dx, dy, 权重)
+type DiffusionKernel = &'static [(i32, i32, f32)];
+
+const FLOYD_STEINBERG: DiffusionKernel = &[
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+const ATKINSON: DiffusionKernel = &[
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+const SIERRA: DiffusionKernel = &[
+    (1, 0, 5.0 / 32.0),
+    (2, 0, 3.0 / 32.0),
+    (-2, 1, 2.0 / 32.0),
+    (-1, 1, 4.0 / 32.0),
+    (0, 1, 5.0 / 32.0),
+    (1, 1, 4.0 / 32.0),
+    (2, 1, 2.0 / 32.0),
+    (-1, 2, 2.0 / 32.0),
+    (0, 2, 3.0 / 32.0),
+    (1, 2, 2.0 / 32.0),
+];
+
+/// 递归构造 n x n Bayer 矩阵，归一化到 [-0.5, 0.5)
+fn bayer_matrix(n: usize) -> Vec<f32> {
+    let mut m = vec![0u32];
+    let mut size = 1;
+    while size < n {
+        let mut next = vec![0u32; size * size * 4];
+        for y in 0..size {
+            for x in 0..size {
+                let v = m[y * size + x] * 4;
+                let s2 = size * 2;
+                next[y * s2 + x] = v;
+                next[y * s2 + x + size] = v + 2;
+                next[(y + size) * s2 + x] = v + 3;
+                next[(y + size) * s2 + x + size] = v + 1;
+            }
+        }
+        m = next;
+        size *= 2;
+    }
+    let count = (n * n) as f32;
+    m.into_iter().map(|v| (v as f32 + 0.5) / count - 0.5).collect()
+}
+
+/// 蓝噪声阈值图边长
+const BLUE_NOISE_SIZE: usize = 32;
+
+/// 用 void-and-cluster 生成 32x32 蓝噪声阈值图（确定性，首次使用时计算），归一化到 [-0.5, 0.5)
+fn blue_noise() -> &'static [f32] {
+    static TILE: OnceLock<Vec<f32>> = OnceLock::new();
+    TILE.get_or_init(|| {
+        const N: usize = BLUE_NOISE_SIZE;
+        const SIGMA: f32 = 1.5;
+
+        // 环面距离下的高斯核
+        let gauss: Vec<f32> = (0..N * N)
+            .map(|i| {
+                let (dx, dy) = (i % N, i / N);
+                let dx = dx.min(N - dx) as f32;
+                let dy = dy.min(N - dy) as f32;
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect();
+        let splat = |energy: &mut [f32], p: usize, sign: f32| {
+            let (px, py) = (p % N, p / N);
+            for (i, e) in energy.iter_mut().enumerate() {
+                let dx = (i % N + N - px) % N;
+                let dy = (i / N + N - py) % N;
+                *e += sign * gauss[dy * N + dx];
+            }
+        };
+        let argmax = |energy: &[f32], pattern: &[bool], want: bool| {
+            (0..N * N)
+                .filter(|&i| pattern[i] == want)
+                .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+        let argmin = |energy: &[f32], pattern: &[bool], want: bool| {
+            (0..N * N)
+                .filter(|&i| pattern[i] == want)
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+
+        // 初始图案：约 10% 的点（固定种子 LCG）
+        let mut pattern = vec![false; N * N];
+        let mut seed = 0x2545_f491_u32;
+        let mut ones = 0;
+        while ones < N * N / 10 {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let p = (seed >> 8) as usize % (N * N);
+            if !pattern[p] {
+                pattern[p] = true;
+                ones += 1;
+            }
+        }
+        let mut energy = vec![0.0f32; N * N];
+        for p in (0..N * N).filter(|&p| pattern[p]) {
+            splat(&mut energy, p, 1.0);
+        }
+
+        // 迭代：移走最密集的点，放入最大的空洞，直到稳定
+        loop {
+            let cluster = argmax(&energy, &pattern, true);
+            pattern[cluster] = false;
+            splat(&mut energy, cluster, -1.0);
+            let void = argmin(&energy, &pattern, false);
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut rank = vec![0usize; N * N];
+        // 阶段 1：依次移走最密集的点，排名递减
+        {
+            let (mut pat, mut en) = (pattern.clone(), energy.clone());
+            for r in (0..ones).rev() {
+                let cluster = argmax(&en, &pat, true);
+                pat[cluster] = false;
+                splat(&mut en, cluster, -1.0);
+                rank[cluster] = r;
+            }
+        }
+        // 阶段 2：依次填充最大的空洞，排名递增
+        for r in ones..N * N {
+            let void = argmin(&energy, &pattern, false);
+            pattern[void] = true;
+            splat(&mut energy, void, 1.0);
+            rank[void] = r;
+        }
+
+        let count = (N * N) as f32;
+        rank.into_iter().map(|r| (r as f32 + 0.5) / count - 0.5).collect()
+    })
+}
+
+/// 带抖动地将像素映射到调色板（在原生分辨率的单元格网格上调用）
+/// DitherMode::None 时等价于逐像素最近色
+pub fn dither_to_palette(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    palette: &[[u8; 3]],
+    space: ColorSpace,
+    params: &DitherParams,
+) -> Result<Vec<u8>> {
+    check_len("dither_to_palette rgba", rgba.len(), width * height * 4)?;
+    if !(0.0..=1.0).contains(&params.strength) {
+        return Err(invalid(format!("dither strength must be in [0, 1], got {}", params.strength)));
+    }
+    let matcher = PaletteMatcher::new(palette, space)?;
+
+    let skip = |i: usize| params.skip_transparent && rgba[i * 4 + 3] < params.alpha_threshold;
+    let mut out = rgba.to_vec();
+
+    let ordered: Option<(&[f32], usize)> = match params.mode {
+        DitherMode::Bayer2 => Some((bayer_2(), 2)),
+        DitherMode::Bayer4 => Some((bayer_4(), 4)),
+        DitherMode::Bayer8 => Some((bayer_8(), 8)),
+        DitherMode::BlueNoise => Some((blue_noise(), BLUE_NOISE_SIZE)),
+        _ => None,
+    };
+    let kernel: Option<DiffusionKernel> = match params.mode {
+        DitherMode::FloydSteinberg => Some(FLOYD_STEINBERG),
+        DitherMode::Atkinson => Some(ATKINSON),
+        DitherMode::Sierra => Some(SIERRA),
+        _ => None,
+    };
+
+    if let Some(kernel) = kernel {
+        let mut buf: Vec<[f32; 3]> = rgba
+            .chunks_exact(4)
+            .map(|px| [px[0] as f32, px[1] as f32, px[2] as f32])
+            .collect();
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                if skip(i) {
+                    continue;
+                }
+                let old = buf[i].map(|v| v.clamp(0.0, 255.0));
+                let new = matcher.nearest(old);
+                out[i * 4..i * 4 + 3].copy_from_slice(&new);
+
+                let err = [0, 1, 2].map(|k| (old[k] - new[k] as f32) * params.strength);
+                for &(dx, dy, w) in kernel {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if nx < 0 || nx >= width as i32 || ny >= height as i32 {
+                        continue;
+                    }
+                    let j = ny as usize * width + nx as usize;
+                    if skip(j) {
+                        continue;
+                    }
+                    for (v, e) in buf[j].iter_mut().zip(err) {
+                        *v += e * w;
+                    }
+                }
+            }
+        }
+        return Ok(out);
+    }
+
+    // 有序抖动的扰动幅度：调色板越小，相邻颜色间距越大
+    let spread = params.strength * 255.0 / (palette.len() as f32).cbrt().max(1.0);
+    for (i, px) in out.chunks_exact_mut(4).enumerate() {
+        if skip(i) {
+            continue;
+        }
+        let offset = match ordered {
+            Some((map, n)) => map[(i / width % n) * n + (i % width) % n] * spread,
+            None => 0.0,
+        };
+        let c = [px[0], px[1], px[2]].map(|v| v as f32 + offset);
+        px[..3].copy_from_slice(&matcher.nearest(c));
+    }
+    Ok(out)
+}
+
+fn bayer_2() -> &'static [f32] {
+    static M: OnceLock<Vec<f32>> = OnceLock::new();
+    M.get_or_init(|| bayer_matrix(2))
+}
+
+fn bayer_4() -> &'static [f32] {
+    static M: OnceLock<Vec<f32>> = OnceLock::new();
+    M.get_or_init(|| bayer_matrix(4))
+}
+
+fn bayer_8() -> &'static [f32] {
+    static M: OnceLock<Vec<f32>> = OnceLock::new();
+    M.get_or_init(|| bayer_matrix(8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BW: [[u8; 3]; 2] = [[0, 0, 0], [255, 255, 255]];
+
+    /// 16x1 中灰 + 末尾一个透明像素
+    fn gray_row() -> Vec<u8> {
+        let mut rgba: Vec<u8> = (0..15).flat_map(|_| [128, 128, 128, 255]).collect();
+        rgba.extend([128, 128, 128, 0]);
+        rgba
+    }
+
+    #[test]
+    fn test_bayer_matrix() {
+        let m = bayer_matrix(2);
+        // 0 2 / 3 1
+        let expect = [0.0, 2.0, 3.0, 1.0].map(|v: f32| (v + 0.5) / 4.0 - 0.5);
+        assert_eq!(m, expect);
+        let m8 = bayer_matrix(8);
+        let mut sorted = m8.clone();
+        sorted.sort_by(f32::total_cmp);
+        sorted.dedup();
+        assert_eq!(sorted.len(), 64);
+    }
+
+    #[test]
+    fn test_blue_noise_is_permutation() {
+        let tile = blue_noise();
+        assert_eq!(tile.len(), BLUE_NOISE_SIZE * BLUE_NOISE_SIZE);
+        let mut ranks: Vec<usize> = tile
+            .iter()
+            .map(|v| ((v + 0.5) * tile.len() as f32 - 0.5).round() as usize)
+            .collect();
+        ranks.sort_unstable();
+        assert!(ranks.iter().enumerate().all(|(i, &r)| i == r));
+    }
+
+    #[test]
+    fn test_dither_mid_gray_mixes_colors() {
+        for mode in [
+            DitherMode::Bayer2,
+            DitherMode::Bayer4,
+            DitherMode::Bayer8,
+            DitherMode::FloydSteinberg,
+            DitherMode::Atkinson,
+            DitherMode::Sierra,
+            DitherMode::BlueNoise,
+        ] {
+            let params = DitherParams { mode, ..Default::default() };
+            let out = dither_to_palette(&gray_row(), 16, 1, &BW, ColorSpace::Rgb, &params).unwrap();
+            let whites = out[..60].chunks_exact(4).filter(|px| px[0] == 255).count();
+            assert!(whites > 0 && whites < 15, "{:?}: {}", mode, whites);
+            // 透明像素保持不变
+            assert_eq!(out[60..], [128, 128, 128, 0], "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_dither_semi_transparent_cells() {
+        // alpha 64 的半透明单元格默认仍参与抖动，只有 alpha 0 的单元格被跳过
+        let mut row = gray_row();
+        for px in row[..60].chunks_exact_mut(4) {
+            px[3] = 64;
+        }
+        let params = DitherParams { mode: DitherMode::FloydSteinberg, ..Default::default() };
+        let out = dither_to_palette(&row, 16, 1, &BW, ColorSpace::Rgb, &params).unwrap();
+        let whites = out[..60].chunks_exact(4).filter(|px| px[0] == 255).count();
+        assert!(whites > 0 && whites < 15, "{}", whites);
+        assert!(out[..60].chunks_exact(4).all(|px| px[3] == 64 && (px[..3] == [0; 3] || px[..3] == [255; 3])));
+        assert_eq!(out[60..], [128, 128, 128, 0]);
+    }
+
+    #[test]
+    fn test_dither_zero_strength_is_nearest() {
+        let params = DitherParams { mode: DitherMode::FloydSteinberg, strength: 0.0, ..Default::default() };
+        let out = dither_to_palette(&gray_row(), 16, 1, &BW, ColorSpace::Rgb, &params).unwrap();
+        assert!(out[..60].chunks_exact(4).all(|px| px[..3] == [255, 255, 255]));
+        let params = DitherParams { strength: 2.0, ..Default::default() };
+        assert!(dither_to_palette(&gray_row(), 16, 1, &BW, ColorSpace::Rgb, &params).is_err());
+    }
+}
@@ -0,0 +1,907 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::ColorSpace;
+use crate::error::{check_len, invalid, Result};
+use crate::filters::{gaussian_kernel_1d, convolve_separable, gradient, sobel, BorderMode, GradientParams};
+use crate::types::{EnergyMap, GrayImage};
+
+/// 计算梯度能量所用的通道
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyChannels {
+    /// Rec.601 灰度（单通道）
+    #[default]
+    Luma,
+    /// sRGB 三通道
+    Rgb,
+    /// OKLab 三通道
+    Oklab,
+    /// CIELAB 三通道
+    Lab,
+}
+
+impl EnergyChannels {
+    /// 对应的色彩空间；Luma 为 None
+    pub fn color_space(self) -> Option<ColorSpace> {
+        match self {
+            EnergyChannels::Luma => None,
+            EnergyChannels::Rgb => Some(ColorSpace::Rgb),
+            EnergyChannels::Oklab => Some(ColorSpace::Oklab),
+            EnergyChannels::Lab => Some(ColorSpace::Lab),
+        }
+    }
+}
+
+/// 多通道梯度的合并方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelCombine {
+    /// 各通道 |gx|+|gy| 取最大
+    #[default]
+    Max,
+    /// 各通道 |gx|+|gy| 求和
+    Sum,
+    /// Di Zenzo 结构张量的最大特征值开方
+    DiZenzo,
+}
+
+fn check_quantile(q: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&q) {
+        return Err(invalid(format!("quantile must be in [0, 1], got {}", q)));
+    }
+    Ok(())
+}
+
+/// 分位数 q 在长度 n 的有序序列中的下标（向下取整）
+fn quantile_index(q: f64, n: usize) -> usize {
+    ((q * (n - 1) as f64).floor() as usize).min(n - 1)
+}
+
+/// 近似分位数计算（采样避免全排序）；NaN 被忽略
+pub fn quantile_approx(x: &[f32], q: f64) -> Result<f32> {
+    check_quantile(q)?;
+
+    let n = x.len();
+    let sample_max = 200_000_usize;
+    let step = (n / sample_max).max(1);
+
+    let sample: Vec<f32> = x.iter().step_by(step).copied().collect();
+    quantile_exact(&sample, q)
+}
+
+/// 精确分位数（select_nth_unstable，线性时间）；NaN 被忽略，全为 NaN 或为空时返回 0
+pub fn quantile_exact(x: &[f32], q: f64) -> Result<f32> {
+    Ok(quantiles(x, &[q])?[0])
+}
+
+/// 一次计算多个精确分位数，结果与 qs 顺序一致
+pub fn quantiles(x: &[f32], qs: &[f64]) -> Result<Vec<f32>> {
+    for &q in qs {
+        check_quantile(q)?;
+    }
+    let mut v: Vec<f32> = x.iter().copied().filter(|v| !v.is_nan()).collect();
+    if v.is_empty() {
+        return Ok(vec![0.0; qs.len()]);
+    }
+
+    // 按下标升序依次选择，每次只在上一次选中位置之后的部分上划分
+    let n = v.len();
+    let mut order: Vec<usize> = (0..qs.len()).collect();
+    order.sort_by_key(|&i| quantile_index(qs[i], n));
+    let mut out = vec![0.0f32; qs.len()];
+    let mut lo = 0;
+    for i in order {
+        let idx = quantile_index(qs[i], n);
+        if idx >= lo {
+            v[lo..].select_nth_unstable_by(idx - lo, f32::total_cmp);
+            lo = idx + 1;
+        }
+        out[i] = v[idx];
+    }
+    Ok(out)
+}
+
+/// 8 位数据的分位数（直方图，无需排序），结果与 qs 顺序一致
+pub fn quantiles_u8(x: &[u8], qs: &[f64]) -> Result<Vec<u8>> {
+    for &q in qs {
+        check_quantile(q)?;
+    }
+    if x.is_empty() {
+        return Ok(vec![0; qs.len()]);
+    }
+
+    let mut hist = [0usize; 256];
+    for &v in x {
+        hist[v as usize] += 1;
+    }
+    Ok(qs
+        .iter()
+        .map(|&q| {
+            let idx = quantile_index(q, x.len());
+            let mut acc = 0;
+            hist.iter()
+                .position(|&c| {
+                    acc += c;
+                    acc > idx
+                })
+                .unwrap_or(255) as u8
+        })
+        .collect())
+}
+
+/// RGBA 转 0-1 范围的灰度图
+/// 使用标准亮度系数: 0.299*R + 0.587*G + 0.114*B
+pub fn rgba_to_gray01(rgba: &[u8], width: usize, height: usize) -> Result<GrayImage> {
+    let pixel_count = width * height;
+
+    // 验证输入数组长度
+    check_len("rgba_to_gray01 rgba", rgba.len(), pixel_count * 4)?;
+
+    let mut gray = vec![0.0f32; pixel_count];
+
+    for (pixel, px) in gray.iter_mut().zip(rgba.chunks_exact(4)) {
+        let r = px[0] as f32 / 255.0;
+        let g = px[1] as f32 / 255.0;
+        let b = px[2] as f32 / 255.0;
+        // 标准亮度系数 (Rec. 601)
+        *pixel = 0.299 * r + 0.587 * g + 0.114 * b;
+    }
+
+    Ok(GrayImage::new(width, height, gray))
+}
+
+/// 计算梯度能量图
+/// 先用高斯模糊（可选），再用 Sobel 算子计算梯度
+pub fn grad_energy(gray: &GrayImage, sigma: f64) -> Result<EnergyMap> {
+    grad_energy_with(gray, sigma, GradientParams::default())
+}
+
+/// 使用指定梯度算子与范数计算梯度能量图
+pub fn grad_energy_with(gray: &GrayImage, sigma: f64, grad: GradientParams) -> Result<EnergyMap> {
+    let (width, height) = (gray.width, gray.height);
+    let g = if sigma > 0.0 {
+        let k = gaussian_kernel_1d(sigma)?;
+        convolve_separable(&gray.data, width, height, &k, grad.border)?
+    } else {
+        gray.data.clone()
+    };
+
+    let (gx, gy) = gradient(&g, width, height, grad.operator, grad.border)?;
+
+    let energy = gx.iter().zip(&gy).map(|(&x, &y)| grad.magnitude(x, y)).collect();
+
+    Ok(EnergyMap::new(width, height, energy))
+}
+
+/// JPEG 8x8 块效应的预处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JpegDeblock {
+    /// 不处理
+    #[default]
+    Off,
+    /// 总是去块
+    On,
+    /// 块效应强度超过 JPEG_BLOCKINESS_THRESHOLD 时去块
+    Auto,
+}
+
+/// JPEG DCT 块大小
+pub const JPEG_BLOCK: usize = 8;
+
+/// auto 模式下判定存在块效应的阈值（见 jpeg_blockiness）
+pub const JPEG_BLOCKINESS_THRESHOLD: f32 = 1.25;
+
+/// 去块时视为块效应的最大台阶（0-255）；更大的台阶当作真实边缘保留
+const DEBLOCK_MAX_STEP: i32 = 20;
+/// 去块时台阶两侧允许的最大起伏（0-255）
+const DEBLOCK_MAX_SIDE: i32 = 8;
+
+/// JPEG 块效应强度：8 像素块边界两侧的平均亮度差 / 块内部相邻像素的平均亮度差
+/// 明显大于 1 说明存在块效应（或与块网格对齐的 8px 网格）；图像小于两个块时返回 0
+pub fn jpeg_blockiness(rgba: &[u8], width: usize, height: usize) -> Result<f32> {
+    let gray = rgba_to_gray01(rgba, width, height)?;
+    let (mut boundary, mut boundary_n, mut interior, mut interior_n) = (0.0f64, 0usize, 0.0f64, 0usize);
+    let mut add = |pos: usize, d: f32| {
+        if pos.is_multiple_of(JPEG_BLOCK) {
+            boundary += d as f64;
+            boundary_n += 1;
+        } else {
+            interior += d as f64;
+            interior_n += 1;
+        }
+    };
+    for y in 0..height {
+        for x in 1..width {
+            let i = y * width + x;
+            add(x, (gray.data[i] - gray.data[i - 1]).abs());
+        }
+    }
+    for y in 1..height {
+        for x in 0..width {
+            let i = y * width + x;
+            add(y, (gray.data[i] - gray.data[i - width]).abs());
+        }
+    }
+    if boundary_n == 0 || interior_n == 0 {
+        return Ok(0.0);
+    }
+    let boundary = boundary / boundary_n as f64;
+    let interior = interior / interior_n as f64;
+    Ok((boundary / (interior + 1e-4)) as f32)
+}
+
+/// 平滑 8x8 块边界处的小台阶（类似 H.264 环路滤波），大台阶视为真实边缘保留
+/// 先处理竖直边界再处理水平边界，alpha 通道不变
+pub fn deblock_jpeg(rgba: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    check_len("deblock_jpeg rgba", rgba.len(), width * height * 4)?;
+    let mut out = rgba.to_vec();
+
+    // 对 p1 p0 | q0 q1 四个像素的一个通道做滤波
+    let filter = |px: &mut [u8], idx: [usize; 4]| {
+        let [p1, p0, q0, q1] = idx.map(|i| px[i] as i32);
+        let step = q0 - p0;
+        if step.abs() >= DEBLOCK_MAX_STEP || (p1 - p0).abs() > DEBLOCK_MAX_SIDE || (q1 - q0).abs() > DEBLOCK_MAX_SIDE {
+            return;
+        }
+        // 把台阶摊成线性过渡
+        let vals = [p1 + step / 6, p0 + step / 3, q0 - step / 3, q1 - step / 6];
+        for (i, v) in idx.into_iter().zip(vals) {
+            px[i] = v.clamp(0, 255) as u8;
+        }
+    };
+
+    for y in 0..height {
+        for x in (JPEG_BLOCK..width.saturating_sub(1)).step_by(JPEG_BLOCK) {
+            for c in 0..3 {
+                let at = |xx: usize| (y * width + xx) * 4 + c;
+                filter(&mut out, [at(x - 2), at(x - 1), at(x), at(x + 1)]);
+            }
+        }
+    }
+    for y in (JPEG_BLOCK..height.saturating_sub(1)).step_by(JPEG_BLOCK) {
+        for x in 0..width {
+            for c in 0..3 {
+                let at = |yy: usize| (yy * width + x) * 4 + c;
+                filter(&mut out, [at(y - 2), at(y - 1), at(y), at(y + 1)]);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// 网格是否恰好为 8px 且与 JPEG 块网格对齐（至少 80% 的网格线落在块边界两侧）
+/// 这种情况下检测结果可能来自 JPEG 块效应而非像素画本身
+pub fn is_jpeg_aligned_grid(pixel_size: usize, x_lines: &[usize], y_lines: &[usize]) -> bool {
+    if pixel_size != JPEG_BLOCK || x_lines.is_empty() || y_lines.is_empty() {
+        return false;
+    }
+    let aligned = |lines: &[usize]| {
+        let n = lines.iter().filter(|&&p| (p + 1) % JPEG_BLOCK <= 1).count();
+        n * 5 >= lines.len() * 4
+    };
+    aligned(x_lines) && aligned(y_lines)
+}
+
+/// 计算能量前透明度对颜色分量的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaMode {
+    /// 忽略透明度，直接使用 RGB
+    #[default]
+    Ignore,
+    /// 颜色预乘 alpha（相当于合成到黑色背景上）
+    Premultiply,
+    /// alpha < 128 的像素颜色置为黑色，其余保持不变
+    Mask,
+}
+
+/// 按 AlphaMode 处理 RGBA 的颜色分量，alpha 分量保持不变
+/// 透明像素中残留的 RGB 数据不再产生伪边缘
+pub fn apply_alpha(rgba: &[u8], mode: AlphaMode) -> Cow<'_, [u8]> {
+    if mode == AlphaMode::Ignore {
+        return Cow::Borrowed(rgba);
+    }
+    let mut out = rgba.to_vec();
+    for px in out.chunks_exact_mut(4) {
+        let a = px[3];
+        match mode {
+            AlphaMode::Premultiply => {
+                for c in &mut px[..3] {
+                    *c = ((*c as u32 * a as u32 + 127) / 255) as u8;
+                }
+            }
+            AlphaMode::Mask if a < 128 => px[..3].fill(0),
+            _ => {}
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// alpha 通道的梯度能量，透明精灵的轮廓处能量最强
+pub fn alpha_grad_energy(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    sigma: f64,
+    grad: GradientParams,
+) -> Result<EnergyMap> {
+    check_len("alpha_grad_energy rgba", rgba.len(), width * height * 4)?;
+    let alpha = rgba.chunks_exact(4).map(|px| px[3] as f32 / 255.0).collect();
+    grad_energy_with(&GrayImage::new(width, height, alpha), sigma, grad)
+}
+
+/// RGBA 转为指定色彩空间的三个通道平面，各通道大致缩放到 0-1 量级
+pub fn rgba_to_channels(rgba: &[u8], width: usize, height: usize, space: ColorSpace) -> Result<[GrayImage; 3]> {
+    let pixel_count = width * height;
+    check_len("rgba_to_channels rgba", rgba.len(), pixel_count * 4)?;
+
+    let scale = match space {
+        ColorSpace::Rgb => 1.0 / 255.0,
+        ColorSpace::Lab => 1.0 / 100.0,
+        ColorSpace::Oklab => 1.0,
+    };
+    let mut planes = [vec![0.0f32; pixel_count], vec![0.0f32; pixel_count], vec![0.0f32; pixel_count]];
+    for (i, px) in rgba.chunks_exact(4).enumerate() {
+        let c = space.convert([px[0] as f32, px[1] as f32, px[2] as f32]);
+        for (plane, v) in planes.iter_mut().zip(c) {
+            plane[i] = v * scale;
+        }
+    }
+    Ok(planes.map(|data| GrayImage::new(width, height, data)))
+}
+
+/// 逐通道计算梯度并合并的彩色能量图
+/// 相邻单元格亮度相同、色相不同时灰度能量为 0，而彩色能量仍能检出边界
+/// Di Zenzo 合并直接使用梯度分量，不受 grad.norm 影响
+pub fn color_grad_energy(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    sigma: f64,
+    space: ColorSpace,
+    combine: ChannelCombine,
+    grad: GradientParams,
+) -> Result<EnergyMap> {
+    let planes = rgba_to_channels(rgba, width, height, space)?;
+    let k = if sigma > 0.0 { Some(gaussian_kernel_1d(sigma)?) } else { None };
+
+    let pixel_count = width * height;
+    let mut energy = vec![0.0f32; pixel_count];
+    // Di Zenzo 结构张量分量 (gxx, gyy, gxy)
+    let mut tensor = (combine == ChannelCombine::DiZenzo)
+        .then(|| [vec![0.0f32; pixel_count], vec![0.0f32; pixel_count], vec![0.0f32; pixel_count]]);
+
+    for plane in &planes {
+        let g = match &k {
+            Some(k) => convolve_separable(&plane.data, width, height, k, grad.border)?,
+            None => plane.data.clone(),
+        };
+        let (gx, gy) = gradient(&g, width, height, grad.operator, grad.border)?;
+
+        if let Some([gxx, gyy, gxy]) = tensor.as_mut() {
+            for i in 0..pixel_count {
+                let (x, y) = grad.axis.select(gx[i], gy[i]);
+                gxx[i] += x * x;
+                gyy[i] += y * y;
+                gxy[i] += x * y;
+            }
+        } else {
+            let sum = combine == ChannelCombine::Sum;
+            for (e, (&x, &y)) in energy.iter_mut().zip(gx.iter().zip(&gy)) {
+                let m = grad.magnitude(x, y);
+                *e = if sum { *e + m } else { e.max(m) };
+            }
+        }
+    }
+
+    if let Some([gxx, gyy, gxy]) = tensor {
+        for (i, e) in energy.iter_mut().enumerate() {
+            let (a, b, c) = (gxx[i], gyy[i], gxy[i]);
+            let lambda = 0.5 * (a + b + ((a - b) * (a - b) + 4.0 * c * c).sqrt());
+            *e = lambda.max(0.0).sqrt();
+        }
+    }
+
+    Ok(EnergyMap::new(width, height, energy))
+}
+
+/// 方向性能量增强方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnhanceMode {
+    /// 在能量图上再做 Sobel，按系数叠加 |gy| / |gx|
+    #[default]
+    Sobel,
+    /// 结构张量：保留水平 / 垂直边缘，抑制斜向与弯曲边缘
+    StructureTensor,
+}
+
+/// 网格投影所用的能量图
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyProjection {
+    /// 列投影只用 |gx|、行投影只用 |gy|，互不干扰
+    #[default]
+    Split,
+    /// 两个方向共用同一张合并能量图
+    Combined,
+}
+
+/// 方向性能量增强
+/// 增强/削弱水平或垂直边缘
+pub fn enhance_energy_directional(
+    energy: &EnergyMap,
+    horizontal_factor: f32,
+    vertical_factor: f32,
+) -> Result<EnergyMap> {
+    enhance_energy_directional_with(energy, horizontal_factor, vertical_factor, 0.999, BorderMode::Reflect101)
+}
+
+/// 方向性能量增强，增强后裁剪到 clip_quantile 分位数（1.0 表示不裁剪）；border 控制 Sobel 的越界取值
+pub fn enhance_energy_directional_with(
+    energy: &EnergyMap,
+    horizontal_factor: f32,
+    vertical_factor: f32,
+    clip_quantile: f64,
+    border: BorderMode,
+) -> Result<EnergyMap> {
+    if !horizontal_factor.is_finite() || !vertical_factor.is_finite() {
+        return Err(invalid("enhancement factors must be finite"));
+    }
+    if !(0.0..=1.0).contains(&clip_quantile) {
+        return Err(invalid(format!("clip quantile must be in [0, 1], got {}", clip_quantile)));
+    }
+    if (horizontal_factor - 1.0).abs() < 0.001 && (vertical_factor - 1.0).abs() < 0.001 {
+        return Ok(energy.clone());
+    }
+
+    let (gx, gy) = sobel(&energy.data, energy.width, energy.height, border)?;
+
+    let mut out = vec![0.0f32; energy.data.len()];
+
+    for (i, o) in out.iter_mut().enumerate() {
+        let mut v = energy.data[i];
+        if horizontal_factor > 1.0 {
+            v += gy[i].abs() * (horizontal_factor - 1.0);
+        }
+        if vertical_factor > 1.0 {
+            v += gx[i].abs() * (vertical_factor - 1.0);
+        }
+        *o = v;
+    }
+
+    clip_to_quantile(&mut out, clip_quantile)?;
+    Ok(EnergyMap::new(energy.width, energy.height, out))
+}
+
+/// 裁剪到分位数（默认 p99.9）；1.0 表示不裁剪
+fn clip_to_quantile(out: &mut [f32], clip_quantile: f64) -> Result<()> {
+    if clip_quantile < 1.0 {
+        let clip_v = quantile_exact(out, clip_quantile)?;
+        for v in out.iter_mut() {
+            *v = v.min(clip_v);
+        }
+    }
+    Ok(())
+}
+
+/// 结构张量方向性能量
+///
+/// 在能量图梯度的结构张量 J（以 rho 为积分尺度做高斯平滑）上，
+/// 轴对齐程度 w = 1 - 2|Jxy| / (Jxx + Jyy)：水平 / 垂直边缘及网格交点为 1，45° 斜边为 0。
+/// 输出 E * w * (horizontal_factor * Jyy + vertical_factor * Jxx) / (Jxx + Jyy)，
+/// 只保留携带网格信息的轴对齐边缘，可直接用于 x / y 投影。
+pub fn structure_tensor_energy(
+    energy: &EnergyMap,
+    horizontal_factor: f32,
+    vertical_factor: f32,
+    rho: f64,
+    clip_quantile: f64,
+    border: BorderMode,
+) -> Result<EnergyMap> {
+    if !horizontal_factor.is_finite() || !vertical_factor.is_finite() {
+        return Err(invalid("enhancement factors must be finite"));
+    }
+    if !(0.0..=1.0).contains(&clip_quantile) {
+        return Err(invalid(format!("clip quantile must be in [0, 1], got {}", clip_quantile)));
+    }
+    let (w, h) = (energy.width, energy.height);
+    let (gx, gy) = sobel(&energy.data, w, h, border)?;
+
+    let mut jxx: Vec<f32> = gx.iter().map(|v| v * v).collect();
+    let mut jyy: Vec<f32> = gy.iter().map(|v| v * v).collect();
+    let mut jxy: Vec<f32> = gx.iter().zip(&gy).map(|(a, b)| a * b).collect();
+    if rho > 0.0 {
+        let k = gaussian_kernel_1d(rho)?;
+        jxx = convolve_separable(&jxx, w, h, &k, border)?;
+        jyy = convolve_separable(&jyy, w, h, &k, border)?;
+        jxy = convolve_separable(&jxy, w, h, &k, border)?;
+    }
+
+    let mut out: Vec<f32> = (0..energy.data.len())
+        .map(|i| {
+            let trace = jxx[i] + jyy[i];
+            if trace <= 1e-12 {
+                return 0.0;
+            }
+            let axis = (1.0 - 2.0 * jxy[i].abs() / trace).max(0.0);
+            let dir = (horizontal_factor * jyy[i] + vertical_factor * jxx[i]) / trace;
+            energy.data[i] * axis * dir
+        })
+        .collect();
+
+    clip_to_quantile(&mut out, clip_quantile)?;
+    Ok(EnergyMap::new(w, h, out))
+}
+
+/// 将能量图转换为 8 位灰度图（热力图）
+pub fn to_heatmap_u8(energy: &[f32]) -> Result<Vec<u8>> {
+    let p99 = quantile_exact(energy, 0.99)?;
+    let denom = p99 + 1e-6;
+
+    Ok(energy
+        .iter()
+        .map(|&v| ((v / denom).clamp(0.0, 1.0) * 255.0) as u8)
+        .collect())
+}
+
+/// 能量图归一化方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyNorm {
+    /// 除以 quantile 分位数后裁剪（默认 p99）
+    #[default]
+    Percentile,
+    /// 线性拉伸到 [min, max]
+    MinMax,
+    /// ln(1 + v / mean) 压缩强边缘、提升弱边缘
+    Log,
+    /// 全局直方图均衡
+    HistEq,
+    /// 限制对比度的自适应直方图均衡（CLAHE）
+    Clahe,
+}
+
+/// 能量图归一化参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NormalizeParams {
+    pub method: EnergyNorm,
+    /// percentile 模式的分位数
+    pub quantile: f64,
+    /// CLAHE 每个方向的分块数
+    pub clahe_tiles: usize,
+    /// CLAHE 对比度限制（相对于均匀分布的倍数）
+    pub clahe_clip: f32,
+}
+
+impl Default for NormalizeParams {
+    fn default() -> Self {
+        Self { method: EnergyNorm::Percentile, quantile: 0.99, clahe_tiles: 8, clahe_clip: 2.0 }
+    }
+}
+
+/// 均衡化使用的直方图级数
+const HIST_BINS: usize = 256;
+
+/// 将能量图归一化到 [0, 1]
+pub fn normalize_energy(energy: &EnergyMap, params: &NormalizeParams) -> Result<Vec<f32>> {
+    check_len("normalize_energy energy", energy.data.len(), energy.width * energy.height)?;
+    let data = &energy.data;
+    let max = data.iter().copied().filter(|v| v.is_finite()).fold(0.0f32, f32::max);
+
+    Ok(match params.method {
+        EnergyNorm::Percentile => {
+            let denom = quantile_exact(data, params.quantile)? + 1e-6;
+            data.iter().map(|&v| (v / denom).clamp(0.0, 1.0)).collect()
+        }
+        EnergyNorm::MinMax => {
+            let min = data.iter().copied().filter(|v| v.is_finite()).fold(f32::INFINITY, f32::min).min(max);
+            let range = (max - min).max(1e-6);
+            data.iter().map(|&v| ((v - min) / range).clamp(0.0, 1.0)).collect()
+        }
+        EnergyNorm::Log => {
+            let mean = data.iter().sum::<f32>() / data.len().max(1) as f32 + 1e-6;
+            let denom = (max / mean).ln_1p().max(1e-6);
+            data.iter().map(|&v| ((v.max(0.0) / mean).ln_1p() / denom).clamp(0.0, 1.0)).collect()
+        }
+        EnergyNorm::HistEq => {
+            let bins: Vec<usize> = data.iter().map(|&v| hist_bin(v, max)).collect();
+            let mut hist = [0u32; HIST_BINS];
+            for &b in &bins {
+                hist[b] += 1;
+            }
+            let cdf = equalize_cdf(&hist);
+            bins.iter().map(|&b| cdf[b]).collect()
+        }
+        EnergyNorm::Clahe => clahe(energy, max, params.clahe_tiles, params.clahe_clip)?,
+    })
+}
+
+/// 按指定方式归一化后转为 8 位热力图
+pub fn to_heatmap_u8_with(energy: &EnergyMap, params: &NormalizeParams) -> Result<Vec<u8>> {
+    Ok(normalize_energy(energy, params)?.iter().map(|&v| (v * 255.0) as u8).collect())
+}
+
+fn hist_bin(v: f32, max: f32) -> usize {
+    if max <= 0.0 || !v.is_finite() {
+        return 0;
+    }
+    ((v / max).clamp(0.0, 1.0) * (HIST_BINS - 1) as f32).round() as usize
+}
+
+/// 直方图 → 归一化累积分布（最小非零 CDF 映射到 0）
+fn equalize_cdf(hist: &[u32; HIST_BINS]) -> [f32; HIST_BINS] {
+    let total: u32 = hist.iter().sum();
+    let mut cdf = [0.0f32; HIST_BINS];
+    let mut acc = 0u32;
+    let first = hist.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    let denom = (total - first).max(1) as f32;
+    for (c, &h) in cdf.iter_mut().zip(hist) {
+        acc += h;
+        *c = (acc.saturating_sub(first)) as f32 / denom;
+    }
+    cdf
+}
+
+/// CLAHE：分块直方图按 clip 倍数截断并均匀回填，块间双线性插值
+fn clahe(energy: &EnergyMap, max: f32, tiles: usize, clip: f32) -> Result<Vec<f32>> {
+    if tiles == 0 || !clip.is_finite() || clip < 1.0 {
+        return Err(invalid(format!("CLAHE needs tiles >= 1 and clip >= 1, got tiles={} clip={}", tiles, clip)));
+    }
+    let (width, height) = (energy.width, energy.height);
+    let (tx, ty) = (tiles.min(width.max(1)), tiles.min(height.max(1)));
+    let bins: Vec<usize> = energy.data.iter().map(|&v| hist_bin(v, max)).collect();
+
+    // 每个分块的映射表
+    let mut luts = vec![[0.0f32; HIST_BINS]; tx * ty];
+    for j in 0..ty {
+        let (y0, y1) = (j * height / ty, (j + 1) * height / ty);
+        for i in 0..tx {
+            let (x0, x1) = (i * width / tx, (i + 1) * width / tx);
+            let mut hist = [0u32; HIST_BINS];
+            for y in y0..y1 {
+                for &b in &bins[y * width + x0..y * width + x1] {
+                    hist[b] += 1;
+                }
+            }
+            let n = ((x1 - x0) * (y1 - y0)) as f32;
+            let limit = ((clip * n / HIST_BINS as f32).ceil() as u32).max(1);
+            let mut excess = 0u32;
+            for h in hist.iter_mut() {
+                excess += h.saturating_sub(limit);
+                *h = (*h).min(limit);
+            }
+            let (add, rest) = (excess / HIST_BINS as u32, excess as usize % HIST_BINS);
+            for (k, h) in hist.iter_mut().enumerate() {
+                *h += add + u32::from(k < rest);
+            }
+            // 截断后不再使用"最小非零 CDF"平移，直接按比例映射
+            let mut acc = 0u32;
+            for (l, &h) in luts[j * tx + i].iter_mut().zip(&hist) {
+                acc += h;
+                *l = acc as f32 / n.max(1.0);
+            }
+        }
+    }
+
+    // 以分块中心为节点做双线性插值
+    let coord = |p: usize, len: usize, t: usize| -> (usize, usize, f32) {
+        let f = ((p as f32 + 0.5) * t as f32 / len as f32 - 0.5).clamp(0.0, (t - 1) as f32);
+        let a = f.floor() as usize;
+        (a, (a + 1).min(t - 1), f - a as f32)
+    };
+    let mut out = vec![0.0f32; bins.len()];
+    for y in 0..height {
+        let (j0, j1, fy) = coord(y, height, ty);
+        for x in 0..width {
+            let (i0, i1, fx) = coord(x, width, tx);
+            let b = bins[y * width + x];
+            let top = luts[j0 * tx + i0][b] * (1.0 - fx) + luts[j0 * tx + i1][b] * fx;
+            let bottom = luts[j1 * tx + i0][b] * (1.0 - fx) + luts[j1 * tx + i1][b] * fx;
+            out[y * width + x] = (top * (1.0 - fy) + bottom * fy).clamp(0.0, 1.0);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{GradientNorm, GradientOperator};
+
+    #[test]
+    fn test_quantile_approx() {
+        let x = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let q50 = quantile_approx(&x, 0.5).unwrap();
+        assert!((q50 - 3.0).abs() < 0.1);
+
+        let q0 = quantile_approx(&x, 0.0).unwrap();
+        assert_eq!(q0, 1.0);
+    }
+
+    #[test]
+    fn test_quantiles_exact_nan_and_u8() {
+        let x = [5.0, f32::NAN, 1.0, 4.0, 2.0, 3.0, f32::NAN];
+        assert_eq!(quantiles(&x, &[1.0, 0.0, 0.5, 0.75]).unwrap(), vec![5.0, 1.0, 3.0, 4.0]);
+        assert_eq!(quantile_exact(&[f32::NAN], 0.5).unwrap(), 0.0);
+        assert!(quantiles(&x, &[1.5]).is_err());
+
+        let bytes: Vec<u8> = (0..=255u8).rev().collect();
+        let floats: Vec<f32> = bytes.iter().map(|&b| b as f32).collect();
+        let qs = [0.0, 0.25, 0.5, 0.99, 1.0];
+        let expect: Vec<u8> = quantiles(&floats, &qs).unwrap().iter().map(|&v| v as u8).collect();
+        assert_eq!(quantiles_u8(&bytes, &qs).unwrap(), expect);
+    }
+
+    #[test]
+    fn test_structure_tensor_suppresses_diagonals() {
+        // x=8 处竖线 + 斜线 x = y + 16
+        let (w, h) = (48, 48);
+        let mut data = vec![0.0f32; w * h];
+        for y in 0..h {
+            data[y * w + 8] = 1.0;
+            if y + 16 < w {
+                data[y * w + y + 16] = 1.0;
+            }
+        }
+        let energy = EnergyMap::new(w, h, data);
+        let out = structure_tensor_energy(&energy, 1.0, 1.0, 1.5, 1.0, BorderMode::Reflect101).unwrap();
+        let vertical = out.data[24 * w + 8];
+        let diagonal = out.data[20 * w + 36];
+        assert!(vertical > 0.8, "vertical {}", vertical);
+        assert!(diagonal < 0.2, "diagonal {}", diagonal);
+    }
+
+    #[test]
+    fn test_enhance_directional_wrap_border() {
+        // 只有最后一列有能量：wrap 时第 0 列与最后一列相邻，出现竖直边缘响应；reflect101 时没有
+        let (w, h) = (8, 4);
+        let data = (0..w * h).map(|i| if i % w == w - 1 { 1.0 } else { 0.0 }).collect();
+        let energy = EnergyMap::new(w, h, data);
+        let wrap = enhance_energy_directional_with(&energy, 1.0, 2.0, 1.0, BorderMode::Wrap).unwrap();
+        let reflect = enhance_energy_directional_with(&energy, 1.0, 2.0, 1.0, BorderMode::Reflect101).unwrap();
+        assert!(wrap.data[w] > 0.0);
+        assert_eq!(reflect.data[w], 0.0);
+    }
+
+    #[test]
+    fn test_rgba_to_gray01() {
+        // 纯白 RGBA
+        let rgba = vec![255u8, 255, 255, 255];
+        let gray = rgba_to_gray01(&rgba, 1, 1).unwrap();
+        assert_eq!(gray.data.len(), 1);
+        assert!((gray.data[0] - 1.0).abs() < 0.01);
+
+        // 纯黑 RGBA
+        let rgba = vec![0u8, 0, 0, 255];
+        let gray = rgba_to_gray01(&rgba, 1, 1).unwrap();
+        assert_eq!(gray.data.len(), 1);
+        assert!((gray.data[0] - 0.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_to_heatmap_u8_range() {
+        let energy: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let heat = to_heatmap_u8(&energy).unwrap();
+        assert_eq!(heat[0], 0);
+        assert_eq!(heat[99], 255);
+    }
+
+    #[test]
+    fn test_color_energy_equal_luma_edge() {
+        // 左红右绿，调整到亮度相同：灰度能量为 0，彩色能量非 0
+        let (w, h) = (6, 4);
+        let g = ((0.299 * 200.0) / 0.587) as u8;
+        let rgba: Vec<u8> = (0..w * h)
+            .flat_map(|i| if i % w < 3 { [200, 0, 0, 255] } else { [0, g, 0, 255] })
+            .collect();
+        let gray = rgba_to_gray01(&rgba, w, h).unwrap();
+        let luma = grad_energy(&gray, 0.0).unwrap();
+        assert!(luma.data.iter().all(|&v| v < 0.01));
+
+        for space in [ColorSpace::Rgb, ColorSpace::Oklab, ColorSpace::Lab] {
+            for combine in [ChannelCombine::Max, ChannelCombine::Sum, ChannelCombine::DiZenzo] {
+                let e = color_grad_energy(&rgba, w, h, 0.0, space, combine, GradientParams::default()).unwrap();
+                assert!(e.data[w + 2] > 0.1, "{:?} {:?}", space, combine);
+                assert_eq!(e.data[w], 0.0, "{:?} {:?}", space, combine);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grad_energy_with_forward_diff() {
+        // 前向差分 + max 范数：硬边缘只在左侧一列产生能量 1
+        let (w, h) = (6, 3);
+        let gray = GrayImage::new(w, h, (0..w * h).map(|i| if i % w >= 3 { 1.0 } else { 0.0 }).collect());
+        let grad =
+            GradientParams { operator: GradientOperator::ForwardDiff, norm: GradientNorm::Max, ..Default::default() };
+        let e = grad_energy_with(&gray, 0.0, grad).unwrap();
+        assert_eq!(&e.data[w..2 * w], &[0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_alpha_energy_transparent_sprite() {
+        // 左半透明（残留随机 RGB），右半不透明白色
+        let (w, h) = (6, 4);
+        let rgba: Vec<u8> = (0..w * h)
+            .flat_map(|i| if i % w < 3 { [(i * 37 % 256) as u8, (i * 91 % 256) as u8, 7, 0] } else { [255, 255, 255, 255] })
+            .collect();
+
+        let masked = apply_alpha(&rgba, AlphaMode::Mask);
+        let premul = apply_alpha(&rgba, AlphaMode::Premultiply);
+        assert_eq!(masked, premul);
+        assert!(masked.chunks_exact(4).take(3).all(|px| px == [0, 0, 0, 0]));
+        assert!(matches!(apply_alpha(&rgba, AlphaMode::Ignore), Cow::Borrowed(_)));
+
+        let e = alpha_grad_energy(&rgba, w, h, 0.0, GradientParams::default()).unwrap();
+        assert!(e.data[w + 2] > 0.0 && e.data[w + 3] > 0.0);
+        assert_eq!(e.data[w], 0.0);
+    }
+
+    #[test]
+    fn test_jpeg_deblock_blocky_gradient() {
+        // 水平渐变按 8px 块量化：块边界处出现小台阶
+        let (w, h) = (32, 16);
+        let rgba: Vec<u8> = (0..w * h)
+            .flat_map(|i| {
+                let v = (100 + (i % w) / JPEG_BLOCK * 12) as u8;
+                [v, v, v, 255]
+            })
+            .collect();
+        let before = jpeg_blockiness(&rgba, w, h).unwrap();
+        assert!(before > JPEG_BLOCKINESS_THRESHOLD);
+
+        let out = deblock_jpeg(&rgba, w, h).unwrap();
+        assert!(jpeg_blockiness(&out, w, h).unwrap() < before);
+        // 真实的大台阶不被平滑
+        let mut edge = rgba.clone();
+        for px in edge.chunks_exact_mut(4).enumerate().filter(|(i, _)| i % w >= 16).map(|(_, px)| px) {
+            px[..3].fill(250);
+        }
+        let out = deblock_jpeg(&edge, w, h).unwrap();
+        assert_eq!(&out[15 * 4..17 * 4], &edge[15 * 4..17 * 4]);
+
+        assert!(is_jpeg_aligned_grid(8, &[0, 8, 15, 24], &[7, 16]));
+        assert!(!is_jpeg_aligned_grid(8, &[3, 11, 19], &[0, 8]));
+        assert!(!is_jpeg_aligned_grid(6, &[0, 6], &[0, 6]));
+    }
+
+    #[test]
+    fn test_normalize_energy_methods() {
+        // 少量强边缘 + 大量弱纹理
+        let (w, h) = (16, 16);
+        let data: Vec<f32> = (0..w * h).map(|i| if i % w == 8 { 4.0 } else { 0.01 * (i % 7) as f32 }).collect();
+        let energy = EnergyMap::new(w, h, data.clone());
+
+        let default = NormalizeParams::default();
+        assert_eq!(to_heatmap_u8_with(&energy, &default).unwrap(), to_heatmap_u8(&data).unwrap());
+
+        for method in [EnergyNorm::Percentile, EnergyNorm::MinMax, EnergyNorm::Log, EnergyNorm::HistEq, EnergyNorm::Clahe] {
+            let out = normalize_energy(&energy, &NormalizeParams { method, clahe_tiles: 2, ..default }).unwrap();
+            assert!(out.iter().all(|v| (0.0..=1.0).contains(v)), "{:?}", method);
+            // 强边缘始终最亮；全局方法保持单调
+            assert!((out[8] - 1.0).abs() < 1e-3, "{:?} {}", method, out[8]);
+            assert!(method == EnergyNorm::Clahe || out[2] >= out[1], "{:?}", method);
+        }
+
+        // log / histeq 比 min-max 更能拉开弱纹理
+        let minmax = normalize_energy(&energy, &NormalizeParams { method: EnergyNorm::MinMax, ..default }).unwrap();
+        let log = normalize_energy(&energy, &NormalizeParams { method: EnergyNorm::Log, ..default }).unwrap();
+        let histeq = normalize_energy(&energy, &NormalizeParams { method: EnergyNorm::HistEq, ..default }).unwrap();
+        assert!(log[6] > minmax[6] && histeq[6] > minmax[6]);
+    }
+
+    #[test]
+    fn test_rgba_to_gray01_bad_length() {
+        let err = rgba_to_gray01(&[0u8; 7], 1, 2).unwrap_err();
+        assert_eq!(
+            err,
+            crate::Img2PicError::DimensionMismatch { what: "rgba_to_gray01 rgba", expected: 8, actual: 7 }
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+
+/// img2pic 统一错误类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum Img2PicError {
+    /// 缓冲区长度与宽高不匹配
+    DimensionMismatch {
+        what: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// 网格为空（少于两条线或尺寸为 0，无法构成单元格）
+    EmptyGrid,
+    /// 典型间距为 0，无法插值 / 扩展网格线
+    ZeroTypicalGap,
+    /// 参数非法
+    InvalidParams(String),
+    /// 输入解码失败（JSON / 图像等）
+    Decode(String),
+    /// 输出编码失败（PNG 等）
+    Encode(String),
+}
+
+impl fmt::Display for Img2PicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Img2PicError::DimensionMismatch { what, expected, actual } => {
+                write!(f, "{}: expected length {}, got {}", what, expected, actual)
+            }
+            Img2PicError::EmptyGrid => write!(f, "grid is empty: at least two lines per axis are required"),
+            Img2PicError::ZeroTypicalGap => write!(f, "typical gap is 0, cannot interpolate grid lines"),
+            Img2PicError::InvalidParams(msg) => write!(f, "invalid params: {}", msg),
+            Img2PicError::Decode(msg) => write!(f, "decode failed: {}", msg),
+            Img2PicError::Encode(msg) => write!(f, "encode failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Img2PicError {}
+
+pub type Result<T> = std::result::Result<T, Img2PicError>;
+
+/// 校验缓冲区长度是否等于期望值
+pub(crate) fn check_len(what: &'static str, actual: usize, expected: usize) -> Result<()> {
+    if actual != expected {
+        return Err(Img2PicError::DimensionMismatch { what, expected, actual });
+    }
+    Ok(())
+}
+
+/// 构造参数错误
+pub(crate) fn invalid(msg: impl Into<String>) -> Img2PicError {
+    Img2PicError::InvalidParams(msg.into())
+}
@@ -0,0 +1,739 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{check_len, invalid, Result};
+
+/// 卷积 / 梯度的边界处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderMode {
+    /// gfedcb|abcdefgh|gfedcba（不重复边缘像素）
+    #[default]
+    Reflect101,
+    /// hgfedcba|abcdefgh|hgfedcba（重复边缘像素）
+    Reflect,
+    /// aaaaaaa|abcdefgh|hhhhhhh
+    Replicate,
+    /// 000000|abcdefgh|000000
+    Constant,
+    /// cdefgh|abcdefgh|abcdefg，适用于可平铺纹理
+    Wrap,
+}
+
+impl BorderMode {
+    /// 将坐标映射回 [0, len)；Constant 模式越界或 len 为 0 时返回 None
+    /// 任意偏移量（包括超出一个周期）和 1 像素长度都能正确处理
+    pub fn resolve(self, i: i32, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let n = len as i32;
+        if (0..n).contains(&i) {
+            return Some(i as usize);
+        }
+        Some(match self {
+            BorderMode::Reflect101 => reflect101(i, len),
+            BorderMode::Reflect => {
+                let m = i.rem_euclid(2 * n);
+                (if m < n { m } else { 2 * n - 1 - m }) as usize
+            }
+            BorderMode::Replicate => i.clamp(0, n - 1) as usize,
+            BorderMode::Constant => return None,
+            BorderMode::Wrap => i.rem_euclid(n) as usize,
+        })
+    }
+
+    /// 按边界模式读取一维数组；Constant 越界时为 0
+    pub fn sample(self, x: &[f32], i: i32) -> f32 {
+        self.resolve(i, x.len()).map_or(0.0, |j| x[j])
+    }
+}
+
+/// 边界反射处理 (reflect101 模式)，周期为 2*(limit-1)，limit 为 1 时恒为 0
+fn reflect101(x: i32, limit: usize) -> usize {
+    if limit <= 1 {
+        return 0;
+    }
+    let period = 2 * (limit as i32 - 1);
+    let m = x.rem_euclid(period);
+    (if m < limit as i32 { m } else { period - m }) as usize
+}
+
+/// 生成 1D 高斯核
+pub fn gaussian_kernel_1d(sigma: f64) -> Result<Vec<f32>> {
+    if !sigma.is_finite() {
+        return Err(invalid(format!("sigma must be finite, got {}", sigma)));
+    }
+    if sigma <= 0.0 {
+        return Ok(vec![1.0]);
+    }
+    let radius = (3.0 * sigma).ceil().max(1.0) as i32;
+    let size = (radius * 2 + 1) as usize;
+    let mut k = vec![0.0f32; size];
+    let s2 = sigma * sigma;
+    let mut sum = 0.0f32;
+
+    for i in -radius..=radius {
+        let v = (-(i * i) as f64 / (2.0 * s2)).exp() as f32;
+        k[(i + radius) as usize] = v;
+        sum += v;
+    }
+
+    for v in k.iter_mut() {
+        *v /= sum;
+    }
+
+    Ok(k)
+}
+
+/// 可分离卷积 (先水平后垂直)
+pub fn convolve_separable(
+    src: &[f32],
+    width: usize,
+    height: usize,
+    k: &[f32],
+    border: BorderMode,
+) -> Result<Vec<f32>> {
+    convolve_xy(src, width, height, k, k, border)
+}
+
+/// 水平、垂直方向使用不同核的可分离卷积
+fn convolve_xy(
+    src: &[f32],
+    width: usize,
+    height: usize,
+    kx: &[f32],
+    ky: &[f32],
+    border: BorderMode,
+) -> Result<Vec<f32>> {
+    check_len("convolve_separable src", src.len(), width * height)?;
+    for k in [kx, ky] {
+        if k.len().is_multiple_of(2) {
+            return Err(invalid(format!("kernel length must be odd, got {}", k.len())));
+        }
+    }
+
+    let mut tmp = vec![0.0f32; src.len()];
+    let mut dst = vec![0.0f32; src.len()];
+
+    // 水平卷积
+    let radius_i = ((kx.len() - 1) / 2) as i32;
+    for y in 0..height {
+        let row = y * width;
+        for x in 0..width {
+            let mut acc = 0.0f32;
+            for t in -radius_i..=radius_i {
+                if let Some(xx) = border.resolve(x as i32 + t, width) {
+                    unsafe {
+                        acc += src.get_unchecked(row + xx) * kx.get_unchecked((t + radius_i) as usize);
+                    }
+                }
+            }
+            tmp[row + x] = acc;
+        }
+    }
+
+    // 垂直卷积
+    let radius_i = ((ky.len() - 1) / 2) as i32;
+    for y in 0..height {
+        for x in 0..width {
+            let mut acc = 0.0f32;
+            for t in -radius_i..=radius_i {
+                if let Some(yy) = border.resolve(y as i32 + t, height) {
+                    unsafe {
+                        acc += tmp.get_unchecked(yy * width + x) * ky.get_unchecked((t + radius_i) as usize);
+                    }
+                }
+            }
+            dst[y * width + x] = acc;
+        }
+    }
+
+    Ok(dst)
+}
+
+/// 梯度算子
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradientOperator {
+    /// 3x3 Sobel，平滑权重 [1, 2, 1]
+    #[default]
+    Sobel,
+    /// 3x3 Scharr，平滑权重 [3, 10, 3]，旋转对称性更好
+    Scharr,
+    /// 3x3 Prewitt，平滑权重 [1, 1, 1]
+    Prewitt,
+    /// 2x2 Roberts 交叉算子（两个对角方向）
+    Roberts,
+    /// 二阶导数 [1, -2, 1]，分量为 (dxx, dyy)
+    Laplacian,
+    /// 高斯差分（σ=1 与 σ=1.6），分量为两个方向上的一维 DoG
+    Dog,
+    /// 前向差分 a[x+1] - a[x]，硬边缘只在一侧产生响应
+    ForwardDiff,
+}
+
+/// 梯度幅值的范数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradientNorm {
+    /// |gx| + |gy|
+    #[default]
+    L1,
+    /// sqrt(gx² + gy²)
+    L2,
+    /// max(|gx|, |gy|)
+    Max,
+}
+
+impl GradientNorm {
+    /// 由两个方向分量计算幅值
+    pub fn magnitude(self, gx: f32, gy: f32) -> f32 {
+        match self {
+            GradientNorm::L1 => gx.abs() + gy.abs(),
+            GradientNorm::L2 => (gx * gx + gy * gy).sqrt(),
+            GradientNorm::Max => gx.abs().max(gy.abs()),
+        }
+    }
+}
+
+/// 参与能量计算的梯度分量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradientAxis {
+    /// gx 与 gy
+    #[default]
+    Both,
+    /// 仅 gx（垂直边缘，用于列投影）
+    X,
+    /// 仅 gy（水平边缘，用于行投影）
+    Y,
+}
+
+impl GradientAxis {
+    /// 将未选中的分量置零
+    pub fn select(self, gx: f32, gy: f32) -> (f32, f32) {
+        match self {
+            GradientAxis::Both => (gx, gy),
+            GradientAxis::X => (gx, 0.0),
+            GradientAxis::Y => (0.0, gy),
+        }
+    }
+}
+
+/// 梯度能量计算参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GradientParams {
+    pub operator: GradientOperator,
+    pub norm: GradientNorm,
+    /// 高斯模糊与梯度算子共用的边界处理
+    pub border: BorderMode,
+    /// 参与计算的梯度分量
+    pub axis: GradientAxis,
+}
+
+impl GradientParams {
+    /// 按 axis 选取分量后计算幅值
+    pub fn magnitude(self, gx: f32, gy: f32) -> f32 {
+        let (gx, gy) = self.axis.select(gx, gy);
+        self.norm.magnitude(gx, gy)
+    }
+}
+
+/// 使用指定算子计算两个方向的梯度分量
+/// 返回 (gx, gy)
+pub fn gradient(
+    src: &[f32],
+    width: usize,
+    height: usize,
+    op: GradientOperator,
+    border: BorderMode,
+) -> Result<(Vec<f32>, Vec<f32>)> {
+    check_len("gradient src", src.len(), width * height)?;
+    match op {
+        GradientOperator::Sobel => Ok(smoothed_diff(src, width, height, [1.0, 2.0, 1.0], border)),
+        GradientOperator::Scharr => Ok(smoothed_diff(src, width, height, [3.0, 10.0, 3.0], border)),
+        GradientOperator::Prewitt => Ok(smoothed_diff(src, width, height, [1.0, 1.0, 1.0], border)),
+        GradientOperator::Roberts => Ok(roberts(src, width, height, border)),
+        GradientOperator::Laplacian => {
+            let k = [1.0, -2.0, 1.0];
+            Ok((
+                convolve_xy(src, width, height, &k, &[1.0], border)?,
+                convolve_xy(src, width, height, &[1.0], &k, border)?,
+            ))
+        }
+        GradientOperator::Dog => {
+            let g1 = gaussian_kernel_1d(1.0)?;
+            let g2 = gaussian_kernel_1d(1.6)?;
+            // 将窄核补零到与宽核等长后相减
+            let pad = (g2.len() - g1.len()) / 2;
+            let mut dog = g2.iter().map(|v| -v).collect::<Vec<f32>>();
+            for (d, v) in dog[pad..].iter_mut().zip(&g1) {
+                *d += v;
+            }
+            Ok((
+                convolve_xy(src, width, height, &dog, &g1, border)?,
+                convolve_xy(src, width, height, &g1, &dog, border)?,
+            ))
+        }
+        GradientOperator::ForwardDiff => Ok(forward_diff(src, width, height, border)),
+    }
+}
+
+/// Sobel 边缘检测算子
+/// 返回 (gx, gy) 两个梯度图
+pub fn sobel(src: &[f32], width: usize, height: usize, border: BorderMode) -> Result<(Vec<f32>, Vec<f32>)> {
+    check_len("sobel src", src.len(), width * height)?;
+    Ok(smoothed_diff(src, width, height, [1.0, 2.0, 1.0], border))
+}
+
+/// 按边界模式读取像素；Constant 越界时为 0
+fn pixel_at(src: &[f32], width: usize, x: Option<usize>, y: Option<usize>) -> f32 {
+    match (x, y) {
+        (Some(x), Some(y)) => src[y * width + x],
+        _ => 0.0,
+    }
+}
+
+/// 3x3 中心差分 + 垂直方向平滑（Sobel / Scharr / Prewitt 的通用形式）
+/// Gx = [-w0 0 w0; -w1 0 w1; -w2 0 w2]，Gy 为其转置
+fn smoothed_diff(src: &[f32], width: usize, height: usize, w: [f32; 3], border: BorderMode) -> (Vec<f32>, Vec<f32>) {
+    let mut gx = vec![0.0f32; src.len()];
+    let mut gy = vec![0.0f32; src.len()];
+
+    for y in 0..height {
+        let ys = [border.resolve(y as i32 - 1, height), Some(y), border.resolve(y as i32 + 1, height)];
+
+        for x in 0..width {
+            let xs = [border.resolve(x as i32 - 1, width), Some(x), border.resolve(x as i32 + 1, width)];
+            let a = |r: usize, c: usize| pixel_at(src, width, xs[c], ys[r]);
+
+            let idx = y * width + x;
+            gx[idx] = w[0] * (a(0, 2) - a(0, 0)) + w[1] * (a(1, 2) - a(1, 0)) + w[2] * (a(2, 2) - a(2, 0));
+            gy[idx] = w[0] * (a(2, 0) - a(0, 0)) + w[1] * (a(2, 1) - a(0, 1)) + w[2] * (a(2, 2) - a(0, 2));
+        }
+    }
+
+    (gx, gy)
+}
+
+/// Roberts 交叉算子
+fn roberts(src: &[f32], width: usize, height: usize, border: BorderMode) -> (Vec<f32>, Vec<f32>) {
+    let mut gx = vec![0.0f32; src.len()];
+    let mut gy = vec![0.0f32; src.len()];
+    for y in 0..height {
+        let y1 = border.resolve(y as i32 + 1, height);
+        for x in 0..width {
+            let x1 = border.resolve(x as i32 + 1, width);
+            let idx = y * width + x;
+            gx[idx] = src[idx] - pixel_at(src, width, x1, y1);
+            gy[idx] = pixel_at(src, width, x1, Some(y)) - pixel_at(src, width, Some(x), y1);
+        }
+    }
+    (gx, gy)
+}
+
+/// 前向差分 a[x+1] - a[x]，最后一列 / 行的右侧邻居按边界模式取值
+fn forward_diff(src: &[f32], width: usize, height: usize, border: BorderMode) -> (Vec<f32>, Vec<f32>) {
+    let mut gx = vec![0.0f32; src.len()];
+    let mut gy = vec![0.0f32; src.len()];
+    for y in 0..height {
+        let y1 = border.resolve(y as i32 + 1, height);
+        for x in 0..width {
+            let idx = y * width + x;
+            gx[idx] = pixel_at(src, width, border.resolve(x as i32 + 1, width), Some(y)) - src[idx];
+            gy[idx] = pixel_at(src, width, Some(x), y1) - src[idx];
+        }
+    }
+    (gx, gy)
+}
+
+/// 保边去噪滤波器
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DenoiseFilter {
+    /// 不去噪
+    #[default]
+    None,
+    /// 双边滤波：空间高斯 × 值域高斯
+    Bilateral,
+    /// 中值滤波，窗口 (2r+1)x(2r+1)：r=1 为 3x3，r=2 为 5x5
+    Median,
+    /// 引导滤波（以自身为引导图）
+    Guided,
+    /// Kuwahara 滤波：取四个象限中方差最小者的均值
+    Kuwahara,
+}
+
+/// 去噪参数；数值均针对 0-1 范围的输入
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DenoiseParams {
+    pub filter: DenoiseFilter,
+    /// 窗口半径
+    pub radius: usize,
+    /// 双边滤波的空间标准差（像素）
+    pub sigma_space: f32,
+    /// 双边滤波的值域标准差
+    pub sigma_range: f32,
+    /// 引导滤波的正则项
+    pub eps: f32,
+}
+
+impl Default for DenoiseParams {
+    fn default() -> Self {
+        Self { filter: DenoiseFilter::None, radius: 2, sigma_space: 2.0, sigma_range: 0.1, eps: 0.01 }
+    }
+}
+
+/// 对单通道图像去噪
+pub fn denoise(src: &[f32], width: usize, height: usize, params: &DenoiseParams) -> Result<Vec<f32>> {
+    check_len("denoise src", src.len(), width * height)?;
+    let r = params.radius;
+    match params.filter {
+        DenoiseFilter::None => Ok(src.to_vec()),
+        _ if r == 0 => Ok(src.to_vec()),
+        DenoiseFilter::Bilateral => bilateral(src, width, height, r, params.sigma_space, params.sigma_range),
+        DenoiseFilter::Median => median(src, width, height, r),
+        DenoiseFilter::Guided => guided(src, width, height, r, params.eps),
+        DenoiseFilter::Kuwahara => kuwahara(src, width, height, r),
+    }
+}
+
+/// 对 RGBA 的三个颜色通道分别去噪，alpha 通道不变
+pub fn denoise_rgba(rgba: &[u8], width: usize, height: usize, params: &DenoiseParams) -> Result<Vec<u8>> {
+    check_len("denoise_rgba rgba", rgba.len(), width * height * 4)?;
+    let mut out = rgba.to_vec();
+    if params.filter == DenoiseFilter::None {
+        return Ok(out);
+    }
+    for c in 0..3 {
+        let plane: Vec<f32> = rgba.chunks_exact(4).map(|px| px[c] as f32 / 255.0).collect();
+        let plane = denoise(&plane, width, height, params)?;
+        for (px, v) in out.chunks_exact_mut(4).zip(plane) {
+            px[c] = (v * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    Ok(out)
+}
+
+/// 双边滤波，越界时复制边缘像素
+pub fn bilateral(
+    src: &[f32],
+    width: usize,
+    height: usize,
+    radius: usize,
+    sigma_space: f32,
+    sigma_range: f32,
+) -> Result<Vec<f32>> {
+    check_len("bilateral src", src.len(), width * height)?;
+    if sigma_space.is_nan() || sigma_range.is_nan() || sigma_space <= 0.0 || sigma_range <= 0.0 {
+        return Err(invalid(format!(
+            "bilateral sigmas must be positive, got space={} range={}",
+            sigma_space, sigma_range
+        )));
+    }
+    let r = radius as i32;
+    let size = 2 * radius + 1;
+    let mut spatial = vec![0.0f32; size * size];
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let d2 = (dx * dx + dy * dy) as f32;
+            spatial[((dy + r) as usize) * size + (dx + r) as usize] = (-d2 / (2.0 * sigma_space * sigma_space)).exp();
+        }
+    }
+    let range_k = -1.0 / (2.0 * sigma_range * sigma_range);
+
+    let mut dst = vec![0.0f32; src.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let center = src[y * width + x];
+            let (mut acc, mut wsum) = (0.0f32, 0.0f32);
+            for dy in -r..=r {
+                let yy = clamp_index(y as i32 + dy, height);
+                for dx in -r..=r {
+                    let xx = clamp_index(x as i32 + dx, width);
+                    let v = src[yy * width + xx];
+                    let ws = spatial[((dy + r) as usize) * size + (dx + r) as usize];
+                    let w = ws * ((v - center).powi(2) * range_k).exp();
+                    acc += w * v;
+                    wsum += w;
+                }
+            }
+            dst[y * width + x] = acc / wsum;
+        }
+    }
+    Ok(dst)
+}
+
+/// 中值滤波，窗口只取图像内部像素
+pub fn median(src: &[f32], width: usize, height: usize, radius: usize) -> Result<Vec<f32>> {
+    check_len("median src", src.len(), width * height)?;
+    let mut dst = vec![0.0f32; src.len()];
+    let mut window = Vec::with_capacity((2 * radius + 1).pow(2));
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius).min(height - 1));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius).min(width - 1));
+            window.clear();
+            for yy in y0..=y1 {
+                window.extend_from_slice(&src[yy * width + x0..=yy * width + x1]);
+            }
+            let mid = window.len() / 2;
+            let (_, m, _) = window.select_nth_unstable_by(mid, f32::total_cmp);
+            dst[y * width + x] = *m;
+        }
+    }
+    Ok(dst)
+}
+
+/// 引导滤波（He et al.），以输入自身为引导图
+/// eps 越大越接近均值滤波，越小越保边
+pub fn guided(src: &[f32], width: usize, height: usize, radius: usize, eps: f32) -> Result<Vec<f32>> {
+    check_len("guided src", src.len(), width * height)?;
+    if eps.is_nan() || eps <= 0.0 {
+        return Err(invalid(format!("guided eps must be positive, got {}", eps)));
+    }
+    let sq: Vec<f32> = src.iter().map(|v| v * v).collect();
+    let mean = box_mean(src, width, height, radius);
+    let mean_sq = box_mean(&sq, width, height, radius);
+
+    let mut a = vec![0.0f32; src.len()];
+    let mut b = vec![0.0f32; src.len()];
+    for i in 0..src.len() {
+        let var = (mean_sq[i] - mean[i] * mean[i]).max(0.0);
+        a[i] = var / (var + eps);
+        b[i] = mean[i] - a[i] * mean[i];
+    }
+    let mean_a = box_mean(&a, width, height, radius);
+    let mean_b = box_mean(&b, width, height, radius);
+    Ok(src.iter().zip(mean_a.iter().zip(&mean_b)).map(|(v, (a, b))| a * v + b).collect())
+}
+
+/// Kuwahara 滤波：以当前像素为角的四个 (r+1)x(r+1) 象限中取方差最小者的均值
+pub fn kuwahara(src: &[f32], width: usize, height: usize, radius: usize) -> Result<Vec<f32>> {
+    check_len("kuwahara src", src.len(), width * height)?;
+    let sq: Vec<f32> = src.iter().map(|v| v * v).collect();
+    let sum = Integral::new(src, width, height);
+    let sum_sq = Integral::new(&sq, width, height);
+
+    let mut dst = vec![0.0f32; src.len()];
+    for y in 0..height {
+        let ys = [(y.saturating_sub(radius), y), (y, (y + radius).min(height - 1))];
+        for x in 0..width {
+            let xs = [(x.saturating_sub(radius), x), (x, (x + radius).min(width - 1))];
+            let mut best = (f64::INFINITY, 0.0f64);
+            for &(y0, y1) in &ys {
+                for &(x0, x1) in &xs {
+                    let n = ((x1 - x0 + 1) * (y1 - y0 + 1)) as f64;
+                    let m = sum.sum(x0, y0, x1, y1) / n;
+                    let var = sum_sq.sum(x0, y0, x1, y1) / n - m * m;
+                    if var < best.0 {
+                        best = (var, m);
+                    }
+                }
+            }
+            dst[y * width + x] = best.1 as f32;
+        }
+    }
+    Ok(dst)
+}
+
+/// 越界时复制边缘像素
+fn clamp_index(x: i32, limit: usize) -> usize {
+    x.clamp(0, limit as i32 - 1) as usize
+}
+
+/// 积分图（f64 累加，避免大图精度损失）
+struct Integral {
+    width: usize,
+    data: Vec<f64>,
+}
+
+impl Integral {
+    fn new(src: &[f32], width: usize, height: usize) -> Self {
+        let stride = width + 1;
+        let mut data = vec![0.0f64; stride * (height + 1)];
+        for y in 0..height {
+            let mut row = 0.0f64;
+            for x in 0..width {
+                row += src[y * width + x] as f64;
+                data[(y + 1) * stride + x + 1] = data[y * stride + x + 1] + row;
+            }
+        }
+        Self { width, data }
+    }
+
+    /// 闭区间 [x0, x1] x [y0, y1] 内的和
+    fn sum(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> f64 {
+        let stride = self.width + 1;
+        self.data[(y1 + 1) * stride + x1 + 1] - self.data[y0 * stride + x1 + 1] - self.data[(y1 + 1) * stride + x0]
+            + self.data[y0 * stride + x0]
+    }
+}
+
+/// 均值滤波，窗口只取图像内部像素
+fn box_mean(src: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let integral = Integral::new(src, width, height);
+    let mut dst = vec![0.0f32; src.len()];
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius).min(height - 1));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius).min(width - 1));
+            let n = ((x1 - x0 + 1) * (y1 - y0 + 1)) as f64;
+            dst[y * width + x] = (integral.sum(x0, y0, x1, y1) / n) as f32;
+        }
+    }
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gaussian_kernel() {
+        let k = gaussian_kernel_1d(1.0).unwrap();
+        assert_eq!(k.len(), 7); // radius = ceil(3*1) = 3, size = 7
+        let sum: f32 = k.iter().sum();
+        assert!((sum - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_reflect101() {
+        assert_eq!(reflect101(0, 10), 0);
+        assert_eq!(reflect101(5, 10), 5);
+        assert_eq!(reflect101(9, 10), 9);
+        assert_eq!(reflect101(-1, 10), 1);
+        assert_eq!(reflect101(-2, 10), 2);
+        assert_eq!(reflect101(10, 10), 8);
+        assert_eq!(reflect101(11, 10), 7);
+    }
+
+    #[test]
+    fn test_border_modes() {
+        use BorderMode::*;
+        assert_eq!([-2, -1, 5, 6].map(|i| Reflect.resolve(i, 5)), [Some(1), Some(0), Some(4), Some(3)]);
+        assert_eq!([-2, -1, 5, 6].map(|i| Replicate.resolve(i, 5)), [Some(0), Some(0), Some(4), Some(4)]);
+        assert_eq!([-2, -1, 5, 6].map(|i| Wrap.resolve(i, 5)), [Some(3), Some(4), Some(0), Some(1)]);
+        assert_eq!(Constant.resolve(-1, 5), None);
+        assert_eq!(Reflect101.resolve(-7, 3), Some(1));
+        // 1 像素长度
+        for mode in [Reflect101, Reflect, Replicate, Wrap] {
+            assert_eq!([-3, -1, 1, 4].map(|i| mode.resolve(i, 1)), [Some(0); 4], "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_convolve_tiny_images() {
+        // 1 像素宽 / 高的图像与比图像更大的核都不越界
+        let k = gaussian_kernel_1d(2.0).unwrap();
+        use BorderMode::*;
+        for mode in [Reflect101, Reflect, Replicate, Constant, Wrap] {
+            for (w, h) in [(1, 1), (1, 5), (5, 1), (2, 3)] {
+                let src: Vec<f32> = (0..w * h).map(|i| i as f32).collect();
+                assert_eq!(convolve_separable(&src, w, h, &k, mode).unwrap().len(), w * h);
+                let (gx, gy) = sobel(&src, w, h, mode).unwrap();
+                assert_eq!((gx.len(), gy.len()), (w * h, w * h));
+            }
+        }
+        let one = convolve_separable(&[0.5], 1, 1, &k, Reflect101).unwrap();
+        assert!((one[0] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sobel_wrap_seam() {
+        // 可平铺纹理：wrap 模式下左右接缝处（第 0 列与最后一列）有梯度，reflect101 下没有
+        let (w, h) = (8, 4);
+        let src: Vec<f32> = (0..w * h).map(|i| if i % w >= 4 { 1.0 } else { 0.0 }).collect();
+        let (gx, _) = sobel(&src, w, h, BorderMode::Wrap).unwrap();
+        assert!(gx[w].abs() > 0.0 && gx[2 * w - 1].abs() > 0.0);
+        let (gx, _) = sobel(&src, w, h, BorderMode::Reflect101).unwrap();
+        assert_eq!((gx[w], gx[2 * w - 1]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_sobel_vertical_edge() {
+        // 左半黑、右半白：gx 在边界处非零，gy 全零
+        let (w, h) = (6, 4);
+        let src: Vec<f32> = (0..w * h).map(|i| if i % w >= 3 { 1.0 } else { 0.0 }).collect();
+        let (gx, gy) = sobel(&src, w, h, BorderMode::Reflect101).unwrap();
+        assert!(gx[w + 2] > 0.0);
+        assert!(gx[w + 3] > 0.0);
+        assert_eq!(gx[w], 0.0);
+        assert!(gy.iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn test_gradient_operators_vertical_edge() {
+        // 所有算子在竖直边缘处都只有水平分量
+        let (w, h) = (8, 6);
+        let src: Vec<f32> = (0..w * h).map(|i| if i % w >= 4 { 1.0 } else { 0.0 }).collect();
+        for op in [
+            GradientOperator::Sobel,
+            GradientOperator::Scharr,
+            GradientOperator::Prewitt,
+            GradientOperator::Laplacian,
+            GradientOperator::Dog,
+            GradientOperator::ForwardDiff,
+        ] {
+            let (gx, gy) = gradient(&src, w, h, op, BorderMode::Reflect101).unwrap();
+            assert!(gx[2 * w + 3].abs() > 0.0, "{:?}", op);
+            assert!(gy.iter().all(|v| v.abs() < 1e-6), "{:?}", op);
+        }
+
+        // 前向差分只在边缘左侧一列响应
+        let (gx, _) = gradient(&src, w, h, GradientOperator::ForwardDiff, BorderMode::Replicate).unwrap();
+        assert_eq!(&gx[..w], &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+
+        let (gx, gy) = gradient(&src, w, h, GradientOperator::Roberts, BorderMode::Replicate).unwrap();
+        assert_eq!((gx[3], gy[3]), (-1.0, 1.0));
+        let border = BorderMode::Reflect101;
+        assert_eq!(sobel(&src, w, h, border).unwrap(), gradient(&src, w, h, GradientOperator::Sobel, border).unwrap());
+    }
+
+    #[test]
+    fn test_gradient_norms() {
+        assert_eq!(GradientNorm::L1.magnitude(3.0, -4.0), 7.0);
+        assert_eq!(GradientNorm::L2.magnitude(3.0, -4.0), 5.0);
+        assert_eq!(GradientNorm::Max.magnitude(3.0, -4.0), 4.0);
+        let grad = GradientParams { norm: GradientNorm::L2, axis: GradientAxis::Y, ..Default::default() };
+        assert_eq!(grad.magnitude(3.0, -4.0), 4.0);
+    }
+
+    #[test]
+    fn test_denoise_keeps_step_edge() {
+        // 带噪点的阶跃边缘：各滤波器去掉孤立噪点，同时保持边缘陡峭
+        let (w, h) = (12, 12);
+        let mut src: Vec<f32> = (0..w * h).map(|i| if i % w >= 6 { 0.9 } else { 0.1 }).collect();
+        src[5 * w + 2] = 0.25;
+        let filters = [DenoiseFilter::Bilateral, DenoiseFilter::Median, DenoiseFilter::Guided, DenoiseFilter::Kuwahara];
+        for filter in filters {
+            let params = DenoiseParams { filter, radius: 1, ..DenoiseParams::default() };
+            let out = denoise(&src, w, h, &params).unwrap();
+            assert!(out[5 * w + 2] < 0.2, "{:?} {}", filter, out[5 * w + 2]);
+            let step = out[3 * w + 6] - out[3 * w + 5];
+            assert!(step > 0.6, "{:?} {}", filter, step);
+        }
+        let params = DenoiseParams { filter: DenoiseFilter::Median, ..DenoiseParams::default() };
+        assert_eq!(denoise_rgba(&[10, 20, 30, 40], 1, 1, &params).unwrap(), vec![10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn test_denoise_filters_bad_length() {
+        let short = [0.5f32; 5];
+        let mismatch = |r: Result<Vec<f32>>| matches!(r, Err(crate::Img2PicError::DimensionMismatch { .. }));
+        assert!(mismatch(bilateral(&short, 3, 3, 1, 2.0, 0.1)));
+        assert!(mismatch(median(&short, 3, 3, 1)));
+        assert!(mismatch(guided(&short, 3, 3, 1, 0.01)));
+        assert!(mismatch(kuwahara(&short, 3, 3, 1)));
+    }
+
+    #[test]
+    fn test_sobel_dimension_mismatch() {
+        assert!(matches!(
+            sobel(&[0.0; 5], 2, 2, BorderMode::Reflect101),
+            Err(crate::Img2PicError::DimensionMismatch { expected: 4, actual: 5, .. })
+        ));
+    }
+}
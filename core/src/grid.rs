@@ -0,0 +1,1562 @@
+use std::collections::{HashSet, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{check_len, invalid, Img2PicError, Result};
+use crate::filters::BorderMode;
+use crate::spectral::{spectral_combined, spectral_period, spectral_scores};
+use crate::types::{GridLines, PixelArt, PixelSize, SampleMode};
+
+/// 1D 去趋势（移除移动平均）
+fn detrend_1d(x: &[f32], win: usize, border: BorderMode) -> Vec<f32> {
+    let w = win.max(3) | 1; // 确保是奇数
+    let half = w / 2;
+
+    // 移动平均
+    let mut sm = vec![0.0f32; x.len()];
+    for (i, s) in sm.iter_mut().enumerate() {
+        let mut acc = 0.0f32;
+        for t in -(half as i32)..=(half as i32) {
+            acc += border.sample(x, i as i32 + t);
+        }
+        *s = acc / w as f32;
+    }
+
+    // 计算差异并去均值
+    let mut mean = 0.0f32;
+    let mut out = vec![0.0f32; x.len()];
+    for ((o, &v), &s) in out.iter_mut().zip(x).zip(&sm) {
+        *o = v - s;
+        mean += *o;
+    }
+    mean /= x.len() as f32;
+    for v in out.iter_mut() {
+        *v -= mean;
+    }
+
+    out
+}
+
+/// 自相关分数（归一化）；wrap 边界时按循环自相关计算
+fn autocorr_score(x: &[f32], lag: usize, border: BorderMode) -> f32 {
+    let circular = border == BorderMode::Wrap;
+    let n = if circular { x.len() } else { x.len().saturating_sub(lag) };
+    if n <= 10 {
+        return -1e9;
+    }
+
+    let mut dot = 0.0f32;
+    let mut na = 0.0f32;
+    let mut nb = 0.0f32;
+
+    for i in 0..n {
+        let a = x[i];
+        let b = x[(i + lag) % x.len()];
+        dot += a * b;
+        na += a * a;
+        nb += b * b;
+    }
+
+    dot / ((na.sqrt() * nb.sqrt()) + 1e-9)
+}
+
+/// 自相关峰的亚像素位置：对 lag-1..=lag+1 做抛物线插值，偏移限制在 ±0.5
+fn parabolic_peak(x: &[f32], lag: usize, border: BorderMode) -> f32 {
+    if lag < 2 {
+        return lag as f32;
+    }
+    let (a, b, c) = (
+        autocorr_score(x, lag - 1, border),
+        autocorr_score(x, lag, border),
+        autocorr_score(x, lag + 1, border),
+    );
+    let denom = a - 2.0 * b + c;
+    if denom >= 0.0 || a < -1e8 || c < -1e8 {
+        return lag as f32;
+    }
+    lag as f32 + (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+}
+
+/// 亚像素周期估计
+///
+/// 先在整数峰 s 附近做抛物线插值，再依次在 2、4、8… 次谐波附近重复（谐波处误差被除以阶数），
+/// 谐波滞后不超过序列长度的 1/3。
+fn refine_period(x: &[f32], s: usize, border: BorderMode) -> f32 {
+    let mut period = parabolic_peak(x, s, border);
+    let mut m = 2usize;
+    while m as f32 * period <= (x.len() / 3) as f32 {
+        let center = (m as f32 * period).round() as usize;
+        let lag = (center - 1..=center + 1)
+            .max_by(|&a, &b| autocorr_score(x, a, border).total_cmp(&autocorr_score(x, b, border)))
+            .unwrap_or(center);
+        period = parabolic_peak(x, lag, border) / m as f32;
+        m *= 2;
+    }
+    period
+}
+
+/// 能量图在 x / y 轴上的投影（列和 / 行和）
+fn project_xy<T: Copy + Into<f32>>(energy: &[T], width: usize, height: usize) -> (Vec<f32>, Vec<f32>) {
+    let mut px = vec![0.0f32; width];
+    let mut py = vec![0.0f32; height];
+
+    for (row, sum) in energy.chunks_exact(width.max(1)).take(height).zip(py.iter_mut()) {
+        for (acc, &v) in px.iter_mut().zip(row) {
+            let v: f32 = v.into();
+            *acc += v;
+            *sum += v;
+        }
+    }
+
+    (px, py)
+}
+
+/// energy_x 的列投影与 energy_y 的行投影
+fn project_split<T: Copy + Into<f32>>(
+    energy_x: &[T],
+    energy_y: &[T],
+    width: usize,
+    height: usize,
+) -> Result<(Vec<f32>, Vec<f32>)> {
+    check_len("energy_x", energy_x.len(), width * height)?;
+    check_len("energy_y", energy_y.len(), width * height)?;
+    let (px, _) = project_xy(energy_x, width, height);
+    let (_, py) = project_xy(energy_y, width, height);
+    Ok((px, py))
+}
+
+/// 像素大小（周期）检测方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeriodMethod {
+    /// 逐个整数滞后计算归一化自相关，O(n · (max_s - min_s))
+    #[default]
+    Autocorr,
+    /// 周期图 + 谐波抑制，O(n log n)，适合较大的 max_s 与大面积平坦区域
+    Spectral,
+}
+
+/// 检测像素大小（默认通过自相关分析）
+///
+/// 搜索范围为 min_s..=max_s；max_s 为 0 时由图像尺寸确定（见 auto_search_range）并使用粗到细搜索，
+/// 显式指定的范围逐个穷举，其余检测函数同此约定。
+pub fn detect_pixel_size(
+    energy_u8: &[u8],
+    width: usize,
+    height: usize,
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+) -> Result<PixelSize> {
+    // 验证输入数组长度
+    check_len("detect_pixel_size energy_u8", energy_u8.len(), width * height)?;
+    let (px, py) = project_xy(energy_u8, width, height);
+    pixel_size_from_profiles(&px, &py, min_s, max_s, border, method)
+}
+
+/// 检测像素大小（浮点能量图，不经过 8 位量化）
+pub fn detect_pixel_size_f32(
+    energy: &[f32],
+    width: usize,
+    height: usize,
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+) -> Result<PixelSize> {
+    check_len("detect_pixel_size_f32 energy", energy.len(), width * height)?;
+    let (px, py) = project_xy(energy, width, height);
+    pixel_size_from_profiles(&px, &py, min_s, max_s, border, method)
+}
+
+/// 检测像素大小：energy_x（垂直边缘）投影到列，energy_y（水平边缘）投影到行
+#[allow(clippy::too_many_arguments)]
+pub fn detect_pixel_size_xy<T: Copy + Into<f32>>(
+    energy_x: &[T],
+    energy_y: &[T],
+    width: usize,
+    height: usize,
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+) -> Result<PixelSize> {
+    let (px, py) = project_split(energy_x, energy_y, width, height)?;
+    pixel_size_from_profiles(&px, &py, min_s, max_s, border, method)
+}
+
+/// 自动搜索范围的上限为短边的 1 / AUTO_RANGE_DIVISOR（至少容纳 4 个单元格）
+const AUTO_RANGE_DIVISOR: usize = 4;
+/// 粗到细搜索：第 k 段滞后 [OCTAVE_LAGS·2^k, OCTAVE_LAGS·2^(k+1)) 在降采样 2^k 倍的投影上穷举，
+/// 每段的粗搜滞后数不超过 OCTAVE_LAGS
+const OCTAVE_LAGS: usize = 16;
+/// 粗到细搜索中每段细化的峰数
+const BAND_PEAKS: usize = 3;
+
+/// 由图像尺寸确定的像素大小搜索范围：2 ..= 短边 / 4
+pub fn auto_search_range(width: usize, height: usize) -> (usize, usize) {
+    (2, (width.min(height) / AUTO_RANGE_DIVISOR).max(2))
+}
+
+/// 校验搜索范围；max_s 为 0 时由图像尺寸自动确定（min_s 非 0 时保留为下限）
+///
+/// 返回 (min_s, max_s, coarse)，coarse 表示范围为自动确定、自相关使用粗到细搜索
+fn search_range(min_s: usize, max_s: usize, width: usize, height: usize) -> Result<(usize, usize, bool)> {
+    let (min_s, max_s, coarse) = if max_s == 0 {
+        let (lo, hi) = auto_search_range(width, height);
+        (if min_s == 0 { lo } else { min_s }, hi, true)
+    } else {
+        (min_s, max_s, false)
+    };
+    if min_s == 0 || min_s > max_s {
+        return Err(invalid(format!("pixel size search range {}..={} is invalid", min_s, max_s)));
+    }
+    Ok((min_s, max_s, coarse))
+}
+
+/// 对列 / 行投影去趋势
+fn detrend_profiles(px: &[f32], py: &[f32], border: BorderMode) -> (Vec<f32>, Vec<f32>) {
+    let (width, height) = (px.len(), py.len());
+
+    // 去趋势
+    let win_x = (401_usize).min((31_usize).max((width / 10) | 1));
+    let win_y = (401_usize).min((31_usize).max((height / 10) | 1));
+
+    (detrend_1d(px, win_x, border), detrend_1d(py, win_y, border))
+}
+
+/// 得分序列的局部峰下标（含两端）
+fn local_peaks(scores: &[f32]) -> Vec<usize> {
+    (0..scores.len())
+        .filter(|&i| {
+            let s = scores[i];
+            (i == 0 || s >= scores[i - 1]) && (i + 1 == scores.len() || s >= scores[i + 1])
+        })
+        .collect()
+}
+
+/// 自相关得分（各投影之和）的局部峰 (滞后, 得分)，按滞后升序
+///
+/// coarse 为 false 时逐个穷举 min_s..=max_s。否则滞后小于 2 * OCTAVE_LAGS 时逐个穷举；更大的滞后按倍频程分段，第 k 段先在相邻元素两两合并 k 次的投影上
+/// 穷举（粗搜滞后数不超过 OCTAVE_LAGS），再对最强的 BAND_PEAKS 个峰逐级回到上一层、在 2c ± 2 内细化。
+/// 总代价约为 O(n · (3 * OCTAVE_LAGS + BAND_PEAKS · 5 · log(max_s)))，与搜索范围宽度无关。
+fn autocorr_peaks(
+    profiles: &[&[f32]],
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    coarse: bool,
+) -> Vec<(usize, f32)> {
+    let score = |level: &[Vec<f32>], lag: usize| level.iter().map(|x| autocorr_score(x, lag, border)).sum::<f32>();
+    let mut pyramid: Vec<Vec<Vec<f32>>> = vec![profiles.iter().map(|x| x.to_vec()).collect()];
+    let mut peaks = Vec::new();
+
+    let mut k = 0;
+    while k == 0 || (coarse && OCTAVE_LAGS << k <= max_s) {
+        let band_lo = if k == 0 { min_s } else { (OCTAVE_LAGS << k).max(min_s) };
+        let band_hi = if coarse { ((OCTAVE_LAGS << (k + 1)) - 1).min(max_s) } else { max_s };
+        if k > 0 {
+            let next = pyramid[k - 1].iter().map(|x| x.chunks_exact(2).map(|p| p[0] + p[1]).collect()).collect();
+            pyramid.push(next);
+        }
+        if band_lo > band_hi {
+            k += 1;
+            continue;
+        }
+
+        // 第 j 层上与 [band_lo, band_hi] 对应的滞后范围
+        let lags = |j: usize| (band_lo >> j).max(1)..=band_hi.div_ceil(1 << j);
+        let coarse: Vec<usize> = lags(k).collect();
+        let curve: Vec<f32> = coarse.iter().map(|&c| score(&pyramid[k], c)).collect();
+        let mut band = local_peaks(&curve);
+        if k > 0 {
+            band.sort_by(|&a, &b| curve[b].total_cmp(&curve[a]));
+            band.truncate(BAND_PEAKS);
+        }
+        for i in band {
+            let mut c = coarse[i];
+            for j in (0..k).rev() {
+                let range = lags(j);
+                let window = (2 * c).saturating_sub(2).max(*range.start())..=(2 * c + 2).min(*range.end());
+                let at = |lag: usize| score(&pyramid[j], lag);
+                c = window.max_by(|&a, &b| at(a).total_cmp(&at(b)).then(b.cmp(&a))).unwrap_or(2 * c);
+            }
+            if (band_lo..=band_hi).contains(&c) {
+                peaks.push((c, score(&pyramid[0], c)));
+            }
+        }
+        k += 1;
+    }
+
+    peaks.sort_by_key(|p| p.0);
+    peaks.dedup_by_key(|p| p.0);
+    peaks
+}
+
+/// 由列 / 行投影检测像素大小
+fn pixel_size_from_profiles(
+    px: &[f32],
+    py: &[f32],
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+) -> Result<PixelSize> {
+    let (min_s, max_s, coarse) = search_range(min_s, max_s, px.len(), py.len())?;
+    let (px_dt, py_dt) = detrend_profiles(px, py, border);
+    Ok(pixel_size_from_detrended(&px_dt, &py_dt, min_s, max_s, border, method, coarse))
+}
+
+/// 由去趋势后的列 / 行投影检测像素大小
+fn pixel_size_from_detrended(
+    px_dt: &[f32],
+    py_dt: &[f32],
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+    coarse: bool,
+) -> PixelSize {
+    if method == PeriodMethod::Spectral {
+        let combined = spectral_combined(px_dt, py_dt, min_s, max_s).unwrap_or(min_s);
+        let axis = |x: &[f32]| match spectral_period(x, min_s, max_s) {
+            Some((period, score)) if score > 0.0 => (period.round() as usize, period),
+            _ => (combined, combined as f32),
+        };
+        let ((x, period_x), (y, period_y)) = (axis(px_dt), axis(py_dt));
+        return PixelSize { x, y, combined, period_x, period_y };
+    }
+
+    // 寻找最佳像素大小：两轴分别取最大得分，combined 取得分之和最大
+    let best = [&[px_dt][..], &[py_dt], &[px_dt, py_dt]].map(|profiles| {
+        autocorr_peaks(profiles, min_s, max_s, border, coarse)
+            .into_iter()
+            .fold((min_s, -1e9f32), |b, p| if p.1 > b.1 { p } else { b })
+    });
+
+    // 某一轴没有周期结构（过短或得分不为正）时退回 combined
+    let combined = best[2].0;
+    let axis = |x: &[f32], (s, score): (usize, f32)| {
+        if score > 0.0 {
+            (s, refine_period(x, s, border))
+        } else {
+            (combined, combined as f32)
+        }
+    };
+    let ((x, period_x), (y, period_y)) = (axis(px_dt, best[0]), axis(py_dt, best[1]));
+    PixelSize { x, y, combined, period_x, period_y }
+}
+
+/// 强候选的判定阈值：得分不低于胜者得分的该比例
+const STRONG_CANDIDATE_RATIO: f32 = 0.75;
+
+/// 像素大小候选（得分曲线上的局部峰）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PixelSizeCandidate {
+    pub size: usize,
+    /// x / y 轴的归一化得分（0~1）
+    pub score_x: f32,
+    pub score_y: f32,
+    /// 两轴得分的平均
+    pub score: f32,
+}
+
+/// 像素大小检测报告
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PixelSizeReport {
+    /// 与 detect_pixel_size 相同的检测结果
+    pub size: PixelSize,
+    /// 胜者的得分（0~1，与候选的 score 同一尺度）
+    pub score: f32,
+    /// 候选像素大小，按得分降序，最多 top_n 个
+    pub candidates: Vec<PixelSizeCandidate>,
+    /// 置信度（0~1）：胜者得分减去最强竞争者的得分；胜者的整数倍是预期的谐波，不算竞争者
+    pub confidence: f32,
+    /// 胜者是另一个强候选的整数倍，真实像素大小可能是该候选
+    pub multiple: bool,
+}
+
+impl PixelSizeReport {
+    /// 胜者的强约数候选中得分最高者（multiple 为 true 且该候选在 candidates 中时存在）
+    pub fn divisor(&self) -> Option<&PixelSizeCandidate> {
+        let winner = self.size.combined;
+        self.candidates.iter().find(|c| self.multiple && is_strong_divisor(c, winner, self.score))
+    }
+}
+
+/// c 是胜者的真约数，且得分不低于胜者得分的 STRONG_CANDIDATE_RATIO
+fn is_strong_divisor(c: &PixelSizeCandidate, winner: usize, best: f32) -> bool {
+    c.size < winner && winner.is_multiple_of(c.size) && c.score >= STRONG_CANDIDATE_RATIO * best
+}
+
+/// 由列 / 行投影生成检测报告
+fn report_from_profiles(
+    px: &[f32],
+    py: &[f32],
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+    top_n: usize,
+) -> Result<PixelSizeReport> {
+    let (min_s, max_s, coarse) = search_range(min_s, max_s, px.len(), py.len())?;
+    let (px_dt, py_dt) = detrend_profiles(px, py, border);
+    let size = pixel_size_from_detrended(&px_dt, &py_dt, min_s, max_s, border, method, coarse);
+
+    // 两轴的归一化得分（0~1）：自相关取正部；频谱法取谐波功率占比（扣除随机基线），
+    // 两者都对真实周期的整数倍给出相近的得分
+    let candidate = |size: usize, score_x: f32, score_y: f32| {
+        let (score_x, score_y) = (score_x.max(0.0), score_y.max(0.0));
+        PixelSizeCandidate { size, score_x, score_y, score: 0.5 * (score_x + score_y) }
+    };
+    let (mut peaks, best): (Vec<PixelSizeCandidate>, f32) = match method {
+        PeriodMethod::Autocorr => {
+            let at = |s: usize| candidate(s, autocorr_score(&px_dt, s, border), autocorr_score(&py_dt, s, border));
+            let peaks = autocorr_peaks(&[&px_dt, &py_dt], min_s, max_s, border, coarse).into_iter().map(|(s, _)| at(s));
+            (peaks.collect(), at(size.combined).score)
+        }
+        PeriodMethod::Spectral => {
+            let (sx, sy) = (spectral_scores(&px_dt, min_s, max_s), spectral_scores(&py_dt, min_s, max_s));
+            let all: Vec<PixelSizeCandidate> = (0..sx.len()).map(|i| candidate(min_s + i, sx[i], sy[i])).collect();
+            let scores: Vec<f32> = all.iter().map(|c| c.score).collect();
+            (local_peaks(&scores).into_iter().map(|i| all[i]).collect(), all[size.combined - min_s].score)
+        }
+    };
+    peaks.retain(|c| c.score > 0.0);
+    peaks.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.size.cmp(&b.size)));
+
+    let winner = size.combined;
+    let rival = peaks
+        .iter()
+        .filter(|c| !c.size.is_multiple_of(winner))
+        .map(|c| c.score)
+        .fold(0.0f32, f32::max);
+    let confidence = (best - rival).clamp(0.0, 1.0);
+    let multiple = peaks.iter().any(|c| is_strong_divisor(c, winner, best));
+
+    peaks.truncate(top_n);
+    Ok(PixelSizeReport { size, score: best, candidates: peaks, confidence, multiple })
+}
+
+/// 检测像素大小并给出候选与置信度（能量图可为 u8 或 f32）
+#[allow(clippy::too_many_arguments)]
+pub fn detect_pixel_size_report<T: Copy + Into<f32>>(
+    energy: &[T],
+    width: usize,
+    height: usize,
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+    top_n: usize,
+) -> Result<PixelSizeReport> {
+    check_len("detect_pixel_size_report energy", energy.len(), width * height)?;
+    let (px, py) = project_xy(energy, width, height);
+    report_from_profiles(&px, &py, min_s, max_s, border, method, top_n)
+}
+
+/// 检测像素大小并给出候选与置信度：energy_x 投影到列，energy_y 投影到行
+#[allow(clippy::too_many_arguments)]
+pub fn detect_pixel_size_report_xy<T: Copy + Into<f32>>(
+    energy_x: &[T],
+    energy_y: &[T],
+    width: usize,
+    height: usize,
+    min_s: usize,
+    max_s: usize,
+    border: BorderMode,
+    method: PeriodMethod,
+    top_n: usize,
+) -> Result<PixelSizeReport> {
+    let (px, py) = project_split(energy_x, energy_y, width, height)?;
+    report_from_profiles(&px, &py, min_s, max_s, border, method, top_n)
+}
+
+/// 1D 盒式平滑；border 为 None 时只对范围内的元素取平均
+fn smooth_1d_box(x: &[f32], win: usize, border: Option<BorderMode>) -> Vec<f32> {
+    let w = win.max(1);
+    if w <= 1 {
+        return x.to_vec();
+    }
+
+    let half = w / 2;
+    let mut out = vec![0.0f32; x.len()];
+
+    for (i, o) in out.iter_mut().enumerate() {
+        let (mut acc, mut cnt) = (0.0f32, 0_usize);
+        for t in -(half as i32)..=(half as i32) {
+            let j = i as i32 + t;
+            match border {
+                Some(b) => acc += b.sample(x, j),
+                None if j >= 0 && (j as usize) < x.len() => acc += x[j as usize],
+                None => continue,
+            }
+            cnt += 1;
+        }
+        *o = acc / cnt as f32;
+    }
+
+    out
+}
+
+/// 1D 峰值检测
+pub fn detect_peaks_1d(
+    profile: &[f32],
+    gap_size: usize,
+    gap_tolerance: usize,
+    min_threshold_ratio: f32,
+    window_size: usize,
+    border: BorderMode,
+) -> Result<Vec<usize>> {
+    if !min_threshold_ratio.is_finite() {
+        return Err(invalid("min_threshold_ratio must be finite"));
+    }
+
+    let max_v = profile.iter().copied().fold(0.0f32, f32::max);
+    if max_v <= 0.0 {
+        return Ok(Vec::new());
+    }
+
+    let threshold = min_threshold_ratio * max_v;
+    let w = 5_usize.max(window_size.max(gap_size));
+    let step = (gap_size / 2).max(1);
+
+    let mut detected = HashSet::new();
+
+    let mut start = 0_usize;
+    while start + w <= profile.len() {
+        let end = start + w;
+        let mut local_max = -1e18;
+        let mut local_idx = 0_usize;
+
+        for (i, &v) in profile[start..end].iter().enumerate() {
+            if v > local_max {
+                local_max = v;
+                local_idx = start + i;
+            }
+        }
+
+        if local_max >= threshold {
+            // 检查是否是局部峰值（两端按边界模式取邻居）
+            let i = local_idx as i32;
+            let left_ok = profile[local_idx] >= border.sample(profile, i - 1);
+            let right_ok = profile[local_idx] >= border.sample(profile, i + 1);
+
+            if left_ok && right_ok {
+                detected.insert(local_idx);
+            }
+        }
+
+        start += step;
+    }
+
+    if detected.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // 排序并精炼
+    let mut peaks: Vec<usize> = detected.into_iter().collect();
+    peaks.sort();
+
+    let mut refined = Vec::new();
+    let rad = (gap_size / 4).max(1);
+
+    for &p in &peaks {
+        let s = p.saturating_sub(rad);
+        let e = (p + rad + 1).min(profile.len());
+
+        let mut best = -1e18;
+        let mut best_idx = p;
+
+        for (i, &v) in profile[s..e].iter().enumerate() {
+            if v > best {
+                best = v;
+                best_idx = s + i;
+            }
+        }
+
+        refined.push(best_idx);
+    }
+    refined.sort();
+
+    // 间距过滤
+    let mut filtered = Vec::new();
+    if !refined.is_empty() {
+        filtered.push(refined[0]);
+    }
+
+    for &p in refined.iter().skip(1) {
+        let last = *filtered.last().unwrap();
+        let spacing = p - last;
+
+        if spacing.abs_diff(gap_size) <= gap_tolerance
+            || spacing > gap_size + gap_tolerance
+        {
+            filtered.push(p);
+        }
+    }
+
+    Ok(filtered)
+}
+
+/// 检测网格线
+///
+/// border 为 None 时投影平滑只在图像内取平均、峰值两端不与越界邻居比较；其余网格线检测函数同此约定。
+#[allow(clippy::too_many_arguments)]
+pub fn detect_grid_lines(
+    energy_u8: &[u8],
+    width: usize,
+    height: usize,
+    gap_size: usize,
+    gap_tolerance: usize,
+    min_energy: f32,
+    smooth_win: usize,
+    window_size: usize,
+    border: Option<BorderMode>,
+) -> Result<GridLines> {
+    // 验证输入数组长度
+    check_len("detect_grid_lines energy_u8", energy_u8.len(), width * height)?;
+
+    // 计算投影
+    let (x_prof, y_prof) = project_xy(energy_u8, width, height);
+    let gap = gap_size;
+    grid_lines_from_profiles(&x_prof, &y_prof, gap, gap, gap_tolerance, min_energy, smooth_win, window_size, border)
+}
+
+/// 检测网格线（浮点能量图，不经过 8 位量化）
+#[allow(clippy::too_many_arguments)]
+pub fn detect_grid_lines_f32(
+    energy: &[f32],
+    width: usize,
+    height: usize,
+    gap_size: usize,
+    gap_tolerance: usize,
+    min_energy: f32,
+    smooth_win: usize,
+    window_size: usize,
+    border: Option<BorderMode>,
+) -> Result<GridLines> {
+    check_len("detect_grid_lines_f32 energy", energy.len(), width * height)?;
+    let (x_prof, y_prof) = project_xy(energy, width, height);
+    let gap = gap_size;
+    grid_lines_from_profiles(&x_prof, &y_prof, gap, gap, gap_tolerance, min_energy, smooth_win, window_size, border)
+}
+
+/// 检测网格线：energy_x 只用于列投影（x 线，间距 gap_x），energy_y 只用于行投影（y 线，间距 gap_y）
+#[allow(clippy::too_many_arguments)]
+pub fn detect_grid_lines_xy<T: Copy + Into<f32>>(
+    energy_x: &[T],
+    energy_y: &[T],
+    width: usize,
+    height: usize,
+    gap_x: usize,
+    gap_y: usize,
+    gap_tolerance: usize,
+    min_energy: f32,
+    smooth_win: usize,
+    window_size: usize,
+    border: Option<BorderMode>,
+) -> Result<GridLines> {
+    let (x_prof, y_prof) = project_split(energy_x, energy_y, width, height)?;
+    grid_lines_from_profiles(&x_prof, &y_prof, gap_x, gap_y, gap_tolerance, min_energy, smooth_win, window_size, border)
+}
+
+/// 由列 / 行投影检测网格线
+#[allow(clippy::too_many_arguments)]
+fn grid_lines_from_profiles(
+    x_prof: &[f32],
+    y_prof: &[f32],
+    gap_x: usize,
+    gap_y: usize,
+    gap_tolerance: usize,
+    min_energy: f32,
+    smooth_win: usize,
+    window_size: usize,
+    border: Option<BorderMode>,
+) -> Result<GridLines> {
+    let x_sm = smooth_1d_box(x_prof, smooth_win, border);
+    let y_sm = smooth_1d_box(y_prof, smooth_win, border);
+
+    // Replicate 的越界邻居等于端点本身，即不参与比较
+    let peak_border = border.unwrap_or(BorderMode::Replicate);
+    let x_lines = detect_peaks_1d(&x_sm, gap_x, gap_tolerance, min_energy, window_size, peak_border)?;
+    let y_lines = detect_peaks_1d(&y_sm, gap_y, gap_tolerance, min_energy, window_size, peak_border)?;
+
+    Ok(GridLines { x_lines, y_lines })
+}
+
+/// 校验网格线升序且位于 [0, limit) 内
+fn check_lines(what: &str, lines: &[usize], limit: usize) -> Result<()> {
+    if lines.windows(2).any(|w| w[1] < w[0]) {
+        return Err(invalid(format!("{} must be sorted ascending", what)));
+    }
+    if let Some(&last) = lines.last() {
+        if last >= limit {
+            return Err(invalid(format!("{} contains {} outside 0..{}", what, last, limit)));
+        }
+    }
+    Ok(())
+}
+
+/// 计算中位数间距
+fn median_gap(lines: &[usize], fallback: usize) -> usize {
+    if lines.len() < 2 {
+        return fallback;
+    }
+
+    let mut gaps: Vec<usize> = lines.windows(2).map(|w| w[1] - w[0]).collect();
+    gaps.sort();
+    gaps[gaps.len() / 2]
+}
+
+/// 用等距格点 offset + k * period 对检测到的网格线做最小二乘拟合，返回 (offset, period)
+///
+/// period 为初始周期估计；序号 k 按相邻线间距累加分配（对初始周期误差不敏感），
+/// 拟合后用新周期重新分配一次。网格线不是严格升序或少于两个不同序号时返回 None。
+pub fn fit_lattice(lines: &[usize], period: f32) -> Option<(f32, f32)> {
+    if period.is_nan() || period <= 0.0 || lines.windows(2).any(|w| w[1] <= w[0]) {
+        return None;
+    }
+    let mut fit = None;
+    let mut p = period as f64;
+    for _ in 0..2 {
+        fit = fit_lattice_once(lines, p);
+        p = fit?.1;
+    }
+    fit.map(|(a, b)| (a as f32, b as f32))
+}
+
+fn fit_lattice_once(lines: &[usize], period: f64) -> Option<(f64, f64)> {
+    let mut k = 0.0;
+    let mut pts: Vec<(f64, f64)> = Vec::with_capacity(lines.len());
+    for (i, &l) in lines.iter().enumerate() {
+        if i > 0 {
+            k += ((l - lines[i - 1]) as f64 / period).round();
+        }
+        pts.push((k, l as f64));
+    }
+    let n = pts.len() as f64;
+    if n == 0.0 {
+        return None;
+    }
+    let (sk, sl) = pts.iter().fold((0.0, 0.0), |(a, b), &(k, l)| (a + k, b + l));
+    let (mk, ml) = (sk / n, sl / n);
+    let (mut cov, mut var) = (0.0, 0.0);
+    for &(k, l) in &pts {
+        cov += (k - mk) * (l - ml);
+        var += (k - mk) * (k - mk);
+    }
+    if var <= 0.0 {
+        return None;
+    }
+    let p = cov / var;
+    (p > 0.0).then_some((ml - p * mk, p))
+}
+
+/// 亚像素网格线：由检测到的线拟合等距格点，返回覆盖 [0, limit - 1] 的浮点位置
+///
+/// 拟合失败（线太少）时以第一条线（或 0）为原点、period 为间距；
+/// 与边缘距离小于 1 像素的格点并入边缘 0 / limit - 1。
+pub fn lattice_lines(lines: &[usize], limit: usize, period: f32) -> Result<Vec<f32>> {
+    if limit == 0 {
+        return Err(Img2PicError::EmptyGrid);
+    }
+    if !period.is_finite() || period < 1.0 {
+        return Err(invalid(format!("lattice period must be >= 1, got {}", period)));
+    }
+    check_lines("lattice_lines lines", lines, limit)?;
+
+    let (offset, period) = fit_lattice(lines, period)
+        .filter(|&(_, p)| p >= 1.0)
+        .unwrap_or((lines.first().map_or(0.0, |&l| l as f32), period));
+    let end = (limit - 1) as f32;
+
+    let mut out = vec![0.0f32];
+    let mut k = (-offset / period).ceil();
+    loop {
+        let pos = offset + k * period;
+        if pos >= end - 1.0 {
+            break;
+        }
+        if pos >= 1.0 {
+            out.push(pos);
+        }
+        k += 1.0;
+    }
+    if end > 0.0 {
+        out.push(end);
+    }
+    Ok(out)
+}
+
+/// 插值缺失的网格线
+pub fn interpolate_lines(lines: &[usize], limit: usize, fallback_gap: usize, interp_threshold: f32) -> Result<Vec<usize>> {
+    check_lines("interpolate_lines lines", lines, limit)?;
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let typical = median_gap(lines, fallback_gap);
+
+    // 避免除零错误
+    if typical == 0 {
+        return Err(Img2PicError::ZeroTypicalGap);
+    }
+
+    let mut all: BTreeSet<usize> = lines.iter().copied().collect();
+
+    // 在第一条线之前插值
+    let first = lines[0];
+    if first > typical {
+        let num_before = (first / typical).max(1) - 1;
+        for k in 1..=num_before {
+            all.insert(k * first / (num_before + 1));
+        }
+    }
+
+    // 在线之间插值
+    for i in 0..lines.len() - 1 {
+        let a = lines[i];
+        let b = lines[i + 1];
+        let gap = b - a;
+
+        // 使用 interp_threshold 参数控制插值阈值
+        if gap > (typical as f32 * interp_threshold) as usize {
+            let num_missing = (gap / typical).max(1) - 1;
+            for k in 1..=num_missing {
+                all.insert(a + k * gap / (num_missing + 1));
+            }
+        }
+    }
+
+    // 在最后一条线之后插值
+    let last = *lines.last().unwrap();
+    if last < limit.saturating_sub(typical) {
+        let remain = limit - last;
+        let num_after = (remain / typical).max(1) - 1;
+        for k in 1..=num_after {
+            all.insert(last + k * remain / (num_after + 1));
+        }
+    }
+
+    let mut result: Vec<usize> = all.into_iter().collect();
+    result.sort();
+    Ok(result)
+}
+
+/// 完善边缘（确保覆盖整个图像）
+pub fn complete_edges(
+    all_lines: &[usize],
+    limit: usize,
+    typical_gap: usize,
+    gap_tolerance: usize,
+) -> Result<Vec<usize>> {
+    if limit == 0 {
+        return Err(Img2PicError::EmptyGrid);
+    }
+    if typical_gap == 0 {
+        return Err(Img2PicError::ZeroTypicalGap);
+    }
+
+    let mut lines: Vec<usize> = all_lines.to_vec();
+    lines.sort();
+    check_lines("complete_edges all_lines", &lines, limit)?;
+
+    if lines.is_empty() {
+        return Ok(vec![0, limit - 1]);
+    }
+
+    // 向左扩展
+    let first = lines[0];
+    if first > 0 {
+        let mut edge = Vec::new();
+        let mut x = first;
+        while x > 0 {
+            if x < typical_gap {
+                break;
+            }
+            x -= typical_gap;
+            edge.push(x);
+        }
+        lines = {
+            let combined: BTreeSet<usize> = edge.into_iter().chain(lines).collect();
+            combined.into_iter().collect()
+        };
+        lines.sort();
+    }
+
+    // 向右扩展
+    let last = *lines.last().unwrap();
+    if last < limit - 1 {
+        let mut edge = Vec::new();
+        let mut x = last;
+        while x < limit - 1 {
+            x += typical_gap;
+            if x < limit {
+                edge.push(x);
+            }
+        }
+        lines = {
+            let combined: BTreeSet<usize> = lines.into_iter().chain(edge).collect();
+            combined.into_iter().collect()
+        };
+        lines.sort();
+    }
+
+    // 根据容差规则过滤
+    let mut filtered = Vec::new();
+    for &line in &lines {
+        if filtered.is_empty() {
+            filtered.push(line);
+        } else {
+            let last = *filtered.last().unwrap();
+            let spacing = line - last;
+            if spacing.abs_diff(typical_gap) <= gap_tolerance
+                || spacing > typical_gap + gap_tolerance
+            {
+                filtered.push(line);
+            }
+        }
+    }
+
+    // 确保边缘存在
+    filtered.sort();
+    filtered.dedup();
+    if filtered.first() != Some(&0) {
+        filtered.insert(0, 0);
+    }
+    if filtered.last() != Some(&(limit - 1)) {
+        filtered.push(limit - 1);
+    }
+
+    Ok(filtered)
+}
+
+/// 校验放大倍数（非原生分辨率时必须 >= 1）
+fn check_upscale(upscale_factor: usize, native_res: bool) -> Result<()> {
+    if !native_res && upscale_factor == 0 {
+        return Err(invalid("upscale_factor must be >= 1 unless native_res is set"));
+    }
+    Ok(())
+}
+
+/// 直接比例采样（无需网格检测）
+#[allow(clippy::too_many_arguments)]
+pub fn sample_pixel_art_direct(
+    rgb: &[u8],
+    width: usize,
+    height: usize,
+    target_width: usize,
+    target_height: usize,
+    mode: SampleMode,
+    weight_ratio: f32,
+    upscale_factor: usize,
+    native_res: bool,
+) -> Result<PixelArt> {
+    check_len("sample_pixel_art_direct rgba", rgb.len(), width * height * 4)?;
+    if width == 0 || height == 0 || target_width == 0 || target_height == 0 {
+        return Err(Img2PicError::EmptyGrid);
+    }
+    check_upscale(upscale_factor, native_res)?;
+
+    let cell_w = target_width;
+    let cell_h = target_height;
+
+    let out_w = if native_res { cell_w } else { cell_w * upscale_factor };
+    let out_h = if native_res { cell_h } else { cell_h * upscale_factor };
+
+    let mut out_rgb = vec![0u8; out_w * out_h * 3];
+    let mut out_rgba = vec![0u8; out_w * out_h * 4];
+
+    let scale_x = width as f32 / cell_w as f32;
+    let scale_y = height as f32 / cell_h as f32;
+
+    fn get_rgba(data: &[u8], width: usize, height: usize, x: usize, y: usize) -> (u8, u8, u8, u8) {
+        let clamped_x = x.min(width - 1);
+        let clamped_y = y.min(height - 1);
+        let i = (clamped_y * width + clamped_x) * 4;
+        (data[i], data[i + 1], data[i + 2], data[i + 3])
+    }
+
+    fn get_rgba_interpolated(data: &[u8], width: usize, height: usize, x: f32, y: f32) -> (u8, u8, u8, u8) {
+        let clamped_x = x.max(0.0).min(width as f32 - 1.0);
+        let clamped_y = y.max(0.0).min(height as f32 - 1.0);
+
+        let x1 = clamped_x.floor() as usize;
+        let y1 = clamped_y.floor() as usize;
+        let x2 = (x1 + 1).min(width - 1);
+        let y2 = (y1 + 1).min(height - 1);
+
+        let fx = clamped_x - x1 as f32;
+        let fy = clamped_y - y1 as f32;
+
+        let (r1, g1, b1, a1) = get_rgba(data, width, height, x1, y1);
+        let (r2, g2, b2, a2) = get_rgba(data, width, height, x2, y1);
+        let (r3, g3, b3, a3) = get_rgba(data, width, height, x1, y2);
+        let (r4, g4, b4, a4) = get_rgba(data, width, height, x2, y2);
+
+        let w1 = (1.0 - fx) * (1.0 - fy);
+        let w2 = fx * (1.0 - fy);
+        let w3 = (1.0 - fx) * fy;
+        let w4 = fx * fy;
+
+        // 预乘 alpha 插值
+        let a = ((a1 as f32 * w1 + a2 as f32 * w2 + a3 as f32 * w3 + a4 as f32 * w4).round()) as u8;
+        let a_f = a.max(1) as f32;
+
+        let r = (((r1 as f32 * a1 as f32 * w1 + r2 as f32 * a2 as f32 * w2
+            + r3 as f32 * a3 as f32 * w3 + r4 as f32 * a4 as f32 * w4) / a_f).round()) as u8;
+        let g = (((g1 as f32 * a1 as f32 * w1 + g2 as f32 * a2 as f32 * w2
+            + g3 as f32 * a3 as f32 * w3 + g4 as f32 * a4 as f32 * w4) / a_f).round()) as u8;
+        let b = (((b1 as f32 * a1 as f32 * w1 + b2 as f32 * a2 as f32 * w2
+            + b3 as f32 * a3 as f32 * w3 + b4 as f32 * a4 as f32 * w4) / a_f).round()) as u8;
+
+        (r, g, b, a)
+    }
+
+    for j in 0..cell_h {
+        for i in 0..cell_w {
+            let center_x = (i as f32 + 0.5) * scale_x;
+            let center_y = (j as f32 + 0.5) * scale_y;
+
+            let (r, g, b, a) = match mode {
+                SampleMode::Direct => get_rgba_interpolated(rgb, width, height, center_x, center_y),
+                SampleMode::Center => {
+                    let x = center_x.floor() as usize;
+                    let y = center_y.floor() as usize;
+                    get_rgba(rgb, width, height, x, y)
+                }
+                SampleMode::Average => {
+                    let x1 = (i as f32 * scale_x).floor() as usize;
+                    let y1 = (j as f32 * scale_y).floor() as usize;
+                    let x2 = (((i + 1) as f32 * scale_x).floor() as usize).min(width - 1);
+                    let y2 = (((j + 1) as f32 * scale_y).floor() as usize).min(height - 1);
+
+                    let mut sum_r = 0u32;
+                    let mut sum_g = 0u32;
+                    let mut sum_b = 0u32;
+                    let mut sum_a = 0u32;
+                    let mut cnt = 0usize;
+
+                    for y in y1..=y2 {
+                        for x in x1..=x2 {
+                            let (rr, gg, bb, aa) = get_rgba(rgb, width, height, x, y);
+                            sum_r += rr as u32 * aa as u32;
+                            sum_g += gg as u32 * aa as u32;
+                            sum_b += bb as u32 * aa as u32;
+                            sum_a += aa as u32;
+                            cnt += 1;
+                        }
+                    }
+
+                    if cnt > 0 {
+                        let avg_a = (sum_a / cnt as u32) as u8;
+                        let r = (sum_r / cnt as u32) as u8;
+                        let g = (sum_g / cnt as u32) as u8;
+                        let b = (sum_b / cnt as u32) as u8;
+                        (r, g, b, avg_a)
+                    } else {
+                        (0, 0, 0, 0)
+                    }
+                }
+                SampleMode::Weighted => {
+                    let region_size = (scale_x * weight_ratio).floor().max(1.0) as usize;
+                    let cx = center_x.floor() as usize;
+                    let cy = center_y.floor() as usize;
+                    let x1 = cx.saturating_sub(region_size / 2);
+                    let y1 = cy.saturating_sub(region_size / 2);
+                    let x2 = (cx + region_size / 2).min(width - 1);
+                    let y2 = (cy + region_size / 2).min(height - 1);
+
+                    let mut sum_r = 0u32;
+                    let mut sum_g = 0u32;
+                    let mut sum_b = 0u32;
+                    let mut sum_a = 0u32;
+                    let mut cnt = 0usize;
+
+                    for y in y1..=y2 {
+                        for x in x1..=x2 {
+                            let (rr, gg, bb, aa) = get_rgba(rgb, width, height, x, y);
+                            sum_r += rr as u32 * aa as u32;
+                            sum_g += gg as u32 * aa as u32;
+                            sum_b += bb as u32 * aa as u32;
+                            sum_a += aa as u32;
+                            cnt += 1;
+                        }
+                    }
+
+                    if cnt > 0 {
+                        let avg_a = (sum_a / cnt as u32) as u8;
+                        let r = (sum_r / cnt as u32) as u8;
+                        let g = (sum_g / cnt as u32) as u8;
+                        let b = (sum_b / cnt as u32) as u8;
+                        (r, g, b, avg_a)
+                    } else {
+                        (0, 0, 0, 0)
+                    }
+                }
+            };
+
+            // 写入输出
+            if native_res {
+                let o = (j * out_w + i) * 3;
+                let o4 = (j * out_w + i) * 4;
+                out_rgb[o] = r;
+                out_rgb[o + 1] = g;
+                out_rgb[o + 2] = b;
+                out_rgba[o4] = r;
+                out_rgba[o4 + 1] = g;
+                out_rgba[o4 + 2] = b;
+                out_rgba[o4 + 3] = a;
+            } else {
+                let ox = i * upscale_factor;
+                let oy = j * upscale_factor;
+                for yy in 0..upscale_factor {
+                    for xx in 0..upscale_factor {
+                        let o = ((oy + yy) * out_w + (ox + xx)) * 3;
+                        let o4 = ((oy + yy) * out_w + (ox + xx)) * 4;
+                        out_rgb[o] = r;
+                        out_rgb[o + 1] = g;
+                        out_rgb[o + 2] = b;
+                        out_rgba[o4] = r;
+                        out_rgba[o4 + 1] = g;
+                        out_rgba[o4 + 2] = b;
+                        out_rgba[o4 + 3] = a;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(PixelArt { width: out_w, height: out_h, rgb: out_rgb, rgba: out_rgba })
+}
+
+/// 基于网格的像素采样
+#[allow(clippy::too_many_arguments)]
+pub fn sample_pixel_art(
+    rgb: &[u8],
+    width: usize,
+    height: usize,
+    all_x: &[usize],
+    all_y: &[usize],
+    mode: SampleMode,
+    weight_ratio: f32,
+    upscale_factor: usize,
+    native_res: bool,
+) -> Result<PixelArt> {
+    check_len("sample_pixel_art rgba", rgb.len(), width * height * 4)?;
+    if all_x.len() < 2 || all_y.len() < 2 {
+        return Err(Img2PicError::EmptyGrid);
+    }
+    check_lines("sample_pixel_art all_x", all_x, width)?;
+    check_lines("sample_pixel_art all_y", all_y, height)?;
+    check_upscale(upscale_factor, native_res)?;
+
+    let cell_w = all_x.len() - 1;
+    let cell_h = all_y.len() - 1;
+
+    let out_w = if native_res { cell_w } else { cell_w * upscale_factor };
+    let out_h = if native_res { cell_h } else { cell_h * upscale_factor };
+
+    let mut out_rgb = vec![0u8; out_w * out_h * 3];
+    let mut out_rgba = vec![0u8; out_w * out_h * 4];
+
+    fn get_rgba(data: &[u8], width: usize, x: usize, y: usize) -> (u8, u8, u8, u8) {
+        let i = (y * width + x) * 4;
+        (data[i], data[i + 1], data[i + 2], data[i + 3])
+    }
+
+    for i in 0..cell_w {
+        let x1 = all_x[i];
+        let x2 = all_x[i + 1];
+        let cx = (x1 + x2) / 2;
+
+        for j in 0..cell_h {
+            let y1 = all_y[j];
+            let y2 = all_y[j + 1];
+            let cy = (y1 + y2) / 2;
+
+            let (r, g, b, a) = match mode {
+                SampleMode::Center => {
+                    get_rgba(rgb, width, cx, cy)
+                }
+                SampleMode::Average => {
+                    let mut sum_r = 0u32;
+                    let mut sum_g = 0u32;
+                    let mut sum_b = 0u32;
+                    let mut sum_a = 0u32;
+                    let mut cnt = 0usize;
+
+                    for y in y1..y2 {
+                        for x in x1..x2 {
+                            let (rr, gg, bb, aa) = get_rgba(rgb, width, x, y);
+                            sum_r += rr as u32;
+                            sum_g += gg as u32;
+                            sum_b += bb as u32;
+                            sum_a += aa as u32;
+                            cnt += 1;
+                        }
+                    }
+
+                    if cnt > 0 {
+                        ((sum_r / cnt as u32) as u8,
+                         (sum_g / cnt as u32) as u8,
+                         (sum_b / cnt as u32) as u8,
+                         (sum_a / cnt as u32) as u8)
+                    } else {
+                        (0, 0, 0, 255)
+                    }
+                }
+                // direct 模式在网格采样中按 weighted 处理
+                SampleMode::Weighted | SampleMode::Direct => {
+                    let cw = x2 - x1;
+                    let ch = y2 - y1;
+                    let ww = (cw as f32 * weight_ratio).max(1.0) as usize;
+                    let hh = (ch as f32 * weight_ratio).max(1.0) as usize;
+                    let wx1 = cx.saturating_sub(ww / 2);
+                    let wx2 = (cx + ww / 2).min(width);
+                    let wy1 = cy.saturating_sub(hh / 2);
+                    let wy2 = (cy + hh / 2).min(height);
+
+                    let mut sum_r = 0u32;
+                    let mut sum_g = 0u32;
+                    let mut sum_b = 0u32;
+                    let mut sum_a = 0u32;
+                    let mut cnt = 0usize;
+
+                    for y in wy1..wy2 {
+                        for x in wx1..wx2 {
+                            let (rr, gg, bb, aa) = get_rgba(rgb, width, x, y);
+                            sum_r += rr as u32;
+                            sum_g += gg as u32;
+                            sum_b += bb as u32;
+                            sum_a += aa as u32;
+                            cnt += 1;
+                        }
+                    }
+
+                    if cnt > 0 {
+                        ((sum_r / cnt as u32) as u8,
+                         (sum_g / cnt as u32) as u8,
+                         (sum_b / cnt as u32) as u8,
+                         (sum_a / cnt as u32) as u8)
+                    } else {
+                        (0, 0, 0, 255)
+                    }
+                }
+            };
+
+            // 写入输出
+            if native_res {
+                let o = (j * out_w + i) * 3;
+                let o4 = (j * out_w + i) * 4;
+                out_rgb[o] = r;
+                out_rgb[o + 1] = g;
+                out_rgb[o + 2] = b;
+                out_rgba[o4] = r;
+                out_rgba[o4 + 1] = g;
+                out_rgba[o4 + 2] = b;
+                out_rgba[o4 + 3] = a;
+            } else {
+                let ox = i * upscale_factor;
+                let oy = j * upscale_factor;
+                for yy in 0..upscale_factor {
+                    for xx in 0..upscale_factor {
+                        let o = ((oy + yy) * out_w + (ox + xx)) * 3;
+                        let o4 = ((oy + yy) * out_w + (ox + xx)) * 4;
+                        out_rgb[o] = r;
+                        out_rgb[o + 1] = g;
+                        out_rgb[o + 2] = b;
+                        out_rgba[o4] = r;
+                        out_rgba[o4 + 1] = g;
+                        out_rgba[o4 + 2] = b;
+                        out_rgba[o4 + 3] = a;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(PixelArt { width: out_w, height: out_h, rgb: out_rgb, rgba: out_rgba })
+}
+
+/// 最近邻放大像素画（每个像素放大为 factor x factor 的色块）
+pub fn upscale_pixel_art(art: &PixelArt, factor: usize) -> Result<PixelArt> {
+    check_len("upscale_pixel_art rgba", art.rgba.len(), art.width * art.height * 4)?;
+    check_upscale(factor, false)?;
+
+    let out_w = art.width * factor;
+    let out_h = art.height * factor;
+    let mut out = vec![0u8; out_w * out_h * 4];
+    for (oy, row) in out.chunks_exact_mut(out_w * 4).enumerate() {
+        let src = &art.rgba[(oy / factor) * art.width * 4..(oy / factor + 1) * art.width * 4];
+        for (ox, px) in row.chunks_exact_mut(4).enumerate() {
+            let i = (ox / factor) * 4;
+            px.copy_from_slice(&src[i..i + 4]);
+        }
+    }
+    Ok(PixelArt::from_rgba(out_w, out_h, out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 生成 cell x cell 的棋盘格能量图（网格边界处为 255）
+    fn grid_energy(width: usize, height: usize, cell: usize) -> Vec<u8> {
+        let mut e = vec![0u8; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                if x % cell == 0 || y % cell == 0 {
+                    e[y * width + x] = 255;
+                }
+            }
+        }
+        e
+    }
+
+    #[test]
+    fn test_detect_pixel_size() {
+        let e = grid_energy(96, 96, 8);
+        let size = detect_pixel_size(&e, 96, 96, 4, 12, BorderMode::Reflect101, PeriodMethod::Autocorr).unwrap();
+        assert_eq!((size.x, size.y, size.combined), (8, 8, 8));
+        assert!((size.period_x - 8.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_detect_pixel_size_per_axis() {
+        // 非正方形单元格：宽 6、高 12
+        let (w, h) = (96, 96);
+        let mut e = vec![0u8; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                if x % 6 == 0 || y % 12 == 0 {
+                    e[y * w + x] = 255;
+                }
+            }
+        }
+        let size = detect_pixel_size(&e, w, h, 4, 16, BorderMode::Reflect101, PeriodMethod::Autocorr).unwrap();
+        assert_eq!((size.x, size.y), (6, 12));
+        let spectral = detect_pixel_size(&e, w, h, 4, 16, BorderMode::Reflect101, PeriodMethod::Spectral).unwrap();
+        assert_eq!((spectral.x, spectral.y), (6, 12));
+        let lines = detect_grid_lines_xy(&e, &e, w, h, size.x, size.y, 1, 0.15, 1, 0, None).unwrap();
+        assert!(lines.x_lines.windows(2).all(|g| g[1] - g[0] == 6));
+        assert!(lines.y_lines.windows(2).all(|g| g[1] - g[0] == 12));
+    }
+
+    #[test]
+    fn test_pixel_size_report() {
+        let b = BorderMode::Reflect101;
+        for method in [PeriodMethod::Autocorr, PeriodMethod::Spectral] {
+            let e = grid_energy(96, 96, 8);
+            let report = detect_pixel_size_report(&e, 96, 96, 4, 20, b, method, 3).unwrap();
+            assert_eq!(report.size, detect_pixel_size(&e, 96, 96, 4, 20, b, method).unwrap());
+            assert_eq!(report.candidates[0].size, 8, "{:?}", method);
+            assert!(report.candidates.len() <= 3);
+            assert!(report.confidence > 0.3 && !report.multiple, "{:?} {:?}", method, report);
+        }
+
+        // 6px 网格线强弱交替：12px 周期的得分接近甚至超过 6px
+        let (w, h) = (96, 96);
+        let mut e = vec![0u8; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                if x % 6 == 0 || y % 6 == 0 {
+                    e[y * w + x] = if x % 12 == 0 || y % 12 == 0 { 255 } else { 150 };
+                }
+            }
+        }
+        let report = detect_pixel_size_report(&e, w, h, 4, 16, b, PeriodMethod::Autocorr, 5).unwrap();
+        assert_eq!(report.size.combined, 12);
+        assert!(report.multiple && report.confidence < 0.2, "{:?}", report);
+        assert_eq!(report.divisor().map(|c| c.size), Some(6));
+
+        // 弱约数候选（得分低于胜者的 STRONG_CANDIDATE_RATIO）不作为 divisor
+        let weak = PixelSizeCandidate { size: 4, score_x: 0.3, score_y: 0.3, score: 0.3 };
+        let winner = PixelSizeCandidate { size: 12, score_x: 0.8, score_y: 0.8, score: 0.8 };
+        let report = PixelSizeReport {
+            size: PixelSize::uniform(12),
+            score: 0.8,
+            candidates: vec![winner, weak],
+            confidence: 0.1,
+            multiple: true,
+        };
+        assert_eq!(report.divisor(), None);
+    }
+
+    #[test]
+    fn test_auto_search_range() {
+        assert_eq!(auto_search_range(256, 200), (2, 50));
+        assert_eq!(auto_search_range(4, 4), (2, 2));
+        let b = BorderMode::Reflect101;
+        // 默认范围 4..=24 以外的单元格；max_s 为 0 时自动确定范围
+        for (size, cell) in [(320, 40), (300, 37), (256, 8), (96, 3)] {
+            let e = grid_energy(size, size, cell);
+            for method in [PeriodMethod::Autocorr, PeriodMethod::Spectral] {
+                let detected = detect_pixel_size(&e, size, size, 0, 0, b, method).unwrap();
+                assert_eq!((detected.x, detected.y), (cell, cell), "{:?}", method);
+            }
+            let report = detect_pixel_size_report(&e, size, size, 0, 0, b, PeriodMethod::Autocorr, 3).unwrap();
+            assert_eq!(report.size.combined, cell);
+        }
+        assert!(detect_pixel_size(&grid_energy(64, 64, 8), 64, 64, 40, 0, b, PeriodMethod::Autocorr).is_err());
+    }
+
+    #[test]
+    fn test_coarse_to_fine_matches_exhaustive() {
+        // 在完整的自动搜索范围上与逐个滞后的穷举比较，覆盖各个降采样段
+        let b = BorderMode::Reflect101;
+        let (len, (min_s, max_s)) = (1200, auto_search_range(1200, 1200));
+        let first_max = |a: (usize, f32), p: (usize, f32)| if p.1 > a.1 { p } else { a };
+        for period in [5.0f32, 23.0, 41.5, 77.0, 130.0, 190.0, 270.0] {
+            let x: Vec<f32> = (0..len).map(|i| if (i as f32 % period) < 2.0 { 1.0 } else { 0.0 }).collect();
+            let (x, _) = detrend_profiles(&x, &x, b);
+            let exhaustive = (min_s..=max_s).map(|s| (s, autocorr_score(&x, s, b))).fold((0, -1e9), first_max);
+            let coarse = autocorr_peaks(&[&x], min_s, max_s, b, true).into_iter().fold((0, -1e9), first_max);
+            assert_eq!(coarse, exhaustive, "period {}", period);
+            // 显式范围逐个穷举：所有局部峰都保留
+            let peaks = autocorr_peaks(&[&x], min_s, max_s, b, false);
+            assert_eq!(peaks.iter().copied().fold((0, -1e9), first_max), exhaustive, "period {}", period);
+            let scores: Vec<f32> = (min_s..=max_s).map(|s| autocorr_score(&x, s, b)).collect();
+            assert_eq!(peaks.len(), local_peaks(&scores).len(), "period {}", period);
+        }
+    }
+
+    #[test]
+    fn test_detect_grid_lines() {
+        let e = grid_energy(64, 64, 8);
+        let lines = detect_grid_lines(&e, 64, 64, 8, 2, 0.15, 1, 0, None).unwrap();
+        assert!(lines.x_lines.windows(2).all(|w| w[1] - w[0] == 8));
+        assert!(lines.y_lines.len() >= 6);
+    }
+
+    #[test]
+    fn test_grid_1d_helpers_border() {
+        // 1 像素长度不越界
+        for mode in [BorderMode::Reflect101, BorderMode::Reflect, BorderMode::Constant, BorderMode::Wrap] {
+            assert_eq!(smooth_1d_box(&[2.0], 5, Some(mode)).len(), 1);
+            assert_eq!(detrend_1d(&[2.0], 31, mode).len(), 1);
+        }
+        assert_eq!(smooth_1d_box(&[3.0, 0.0, 0.0, 0.0], 3, Some(BorderMode::Wrap)), vec![1.0, 1.0, 0.0, 1.0]);
+        // 未指定边界：两端只对范围内的元素取平均
+        assert_eq!(smooth_1d_box(&[3.0, 0.0, 0.0, 0.0], 3, None), vec![1.5, 1.0, 0.0, 0.0]);
+        assert_eq!(smooth_1d_box(&[2.0], 5, None), vec![2.0]);
+
+        // wrap：第 0 列的接缝峰只有和最后一列比较才成立
+        let profile = [5.0, 0.0, 0.0, 0.0, 5.0, 0.0, 0.0, 6.0];
+        let wrap = detect_peaks_1d(&profile, 4, 1, 0.5, 0, BorderMode::Wrap).unwrap();
+        assert!(!wrap.contains(&0));
+        let replicate = detect_peaks_1d(&profile, 4, 1, 0.5, 0, BorderMode::Replicate).unwrap();
+        assert!(replicate.contains(&0));
+    }
+
+    #[test]
+    fn test_detect_grid_f32_matches_u8() {
+        let e = grid_energy(64, 64, 8);
+        let f: Vec<f32> = e.iter().map(|&v| v as f32 / 255.0).collect();
+        let b = BorderMode::Reflect101;
+        assert_eq!(
+            detect_grid_lines_f32(&f, 64, 64, 8, 2, 0.15, 1, 0, Some(b)).unwrap(),
+            detect_grid_lines(&e, 64, 64, 8, 2, 0.15, 1, 0, Some(b)).unwrap()
+        );
+        let m = PeriodMethod::Autocorr;
+        let size = detect_pixel_size(&e, 64, 64, 4, 12, b, m).unwrap();
+        assert_eq!(detect_pixel_size_f32(&f, 64, 64, 4, 12, b, m).unwrap(), size);
+    }
+
+    #[test]
+    fn test_detect_grid_xy_ignores_cross_axis() {
+        // energy_x 只有 8px 间距的竖线；energy_y 只有 6px 间距的横线
+        let (w, h) = (48, 48);
+        let mut ex = vec![0u8; w * h];
+        let mut ey = vec![0u8; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                if x % 8 == 0 {
+                    ex[y * w + x] = 255;
+                }
+                if y % 6 == 0 {
+                    ey[y * w + x] = 255;
+                }
+            }
+        }
+        let lines = detect_grid_lines_xy(&ex, &ey, w, h, 8, 6, 1, 0.15, 1, 0, None).unwrap();
+        assert!(lines.x_lines.iter().all(|x| x % 8 == 0));
+        assert!(lines.y_lines.iter().all(|y| y % 6 == 0));
+        assert!(detect_grid_lines_xy(&ex, &ey[1..], w, h, 8, 6, 1, 0.15, 1, 0, None).is_err());
+    }
+
+    #[test]
+    fn test_subpixel_period_and_lattice() {
+        // 1000px 上 64 个单元格：周期 15.625，线宽 2px
+        let (w, h) = (1000, 16);
+        let mut e = vec![0u8; w * h];
+        for k in 0..64 {
+            let x = (k as f32 * 15.625).round() as usize;
+            for y in 0..h {
+                e[y * w + x] = 255;
+                e[y * w + (x + 1).min(w - 1)] = 128;
+            }
+        }
+        let size = detect_pixel_size(&e, w, h, 8, 24, BorderMode::Reflect101, PeriodMethod::Autocorr).unwrap();
+        assert!((size.period_x - 15.625).abs() < 0.05, "period {}", size.period_x);
+
+        let lines: Vec<usize> = (1..64).map(|k| (k as f32 * 15.625).round() as usize).collect();
+        let pos = lattice_lines(&lines, w, 16.0).unwrap();
+        assert_eq!((pos[0], *pos.last().unwrap(), pos.len()), (0.0, 999.0, 65));
+        for (k, &p) in pos.iter().enumerate().take(64) {
+            assert!((p - k as f32 * 15.625).abs() < 0.6, "line {} at {}", k, p);
+        }
+        assert!(lattice_lines(&lines, w, 0.0).is_err());
+
+        // 未排序或重复的网格线不做拟合
+        assert!(fit_lattice(&lines, 16.0).is_some());
+        assert_eq!(fit_lattice(&[40, 8, 24], 16.0), None);
+        assert_eq!(fit_lattice(&[8, 24, 24, 40], 16.0), None);
+    }
+
+    #[test]
+    fn test_complete_edges_covers_image() {
+        let lines = complete_edges(&[8, 16, 24], 32, 8, 1).unwrap();
+        assert_eq!(lines, vec![0, 8, 16, 24, 31]);
+    }
+
+    #[test]
+    fn test_errors_instead_of_fallbacks() {
+        assert!(matches!(
+            detect_pixel_size(&[0u8; 3], 2, 2, 4, 8, BorderMode::Reflect101, PeriodMethod::Autocorr),
+            Err(Img2PicError::DimensionMismatch { .. })
+        ));
+        assert_eq!(complete_edges(&[0, 4], 8, 0, 1), Err(Img2PicError::ZeroTypicalGap));
+        assert_eq!(interpolate_lines(&[3, 3], 8, 0, 1.5), Err(Img2PicError::ZeroTypicalGap));
+        assert_eq!(
+            sample_pixel_art(&[0u8; 16], 2, 2, &[0], &[0, 1], SampleMode::Center, 0.6, 1, true),
+            Err(Img2PicError::EmptyGrid)
+        );
+    }
+
+    #[test]
+    fn test_sample_pixel_art_center() {
+        // 2x1 网格，左红右蓝
+        let (w, h) = (4, 2);
+        let mut rgba = vec![0u8; w * h * 4];
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) * 4;
+                rgba[i..i + 4].copy_from_slice(if x < 2 { &[255, 0, 0, 255] } else { &[0, 0, 255, 255] });
+            }
+        }
+        let art = sample_pixel_art(&rgba, w, h, &[0, 2, 3], &[0, 1], SampleMode::Center, 0.6, 1, true).unwrap();
+        assert_eq!((art.width, art.height), (2, 1));
+        assert_eq!(art.rgba, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(art.rgb, vec![255, 0, 0, 0, 0, 255]);
+
+        let big = upscale_pixel_art(&art, 2).unwrap();
+        assert_eq!((big.width, big.height), (4, 2));
+        assert_eq!(big.rgba[4..8], [255, 0, 0, 255]);
+        assert_eq!(big.rgba[(4 + 2) * 4..(4 + 3) * 4], [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,34 @@
+//! img2pic 核心算法库
+//!
+//! 纯 Rust 实现（无 wasm_bindgen / web_sys 依赖），
+//! 供 WASM 绑定层、命令行工具和后端服务共用。
+//!
+//! 启用 `codec` feature 后可直接解码图像文件字节并输出 PNG。
+
+mod error;
+mod types;
+pub mod color;
+pub mod filters;
+pub mod energy;
+pub mod spectral;
+pub mod grid;
+pub mod quantize;
+pub mod palette;
+pub mod dither;
+pub mod pipeline;
+#[cfg(feature = "codec")]
+pub mod codec;
+
+pub use error::{Img2PicError, Result};
+pub use types::*;
+pub use color::*;
+pub use filters::*;
+pub use energy::*;
+pub use spectral::*;
+pub use grid::*;
+pub use quantize::*;
+pub use palette::*;
+pub use dither::*;
+pub use pipeline::*;
+#[cfg(feature = "codec")]
+pub use codec::*;
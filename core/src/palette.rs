@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::{dist2, ColorSpace};
+use crate::error::{check_len, invalid, Img2PicError, Result};
+use crate::types::RgbaImage;
+
+/// 内置复古调色板
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltinPalette {
+    /// PICO-8（16 色）
+    Pico8,
+    /// NES / Famicom 2C02（去重后 55 色）
+    Nes,
+    /// Game Boy DMG（4 色绿）
+    Gameboy,
+    /// IBM CGA（16 色）
+    Cga,
+    /// DawnBringer 16
+    Db16,
+    /// DawnBringer 32
+    Db32,
+    /// Endesga 32
+    Endesga32,
+}
+
+const fn hex(v: u32) -> [u8; 3] {
+    [(v >> 16) as u8, (v >> 8) as u8, v as u8]
+}
+
+const PICO8: [[u8; 3]; 16] = [
+    hex(0x000000), hex(0x1D2B53), hex(0x7E2553), hex(0x008751),
+    hex(0xAB5236), hex(0x5F574F), hex(0xC2C3C7), hex(0xFFF1E8),
+    hex(0xFF004D), hex(0xFFA300), hex(0xFFEC27), hex(0x00E436),
+    hex(0x29ADFF), hex(0x83769C), hex(0xFF77A8), hex(0xFFCCAA),
+];
+
+const NES: [[u8; 3]; 55] = [
+    hex(0x7C7C7C), hex(0x0000FC), hex(0x0000BC), hex(0x4428BC), hex(0x940084), hex(0xA80020), hex(0xA81000),
+    hex(0x881400), hex(0x503000), hex(0x007800), hex(0x006800), hex(0x005800), hex(0x004058), hex(0x000000),
+    hex(0xBCBCBC), hex(0x0078F8), hex(0x0058F8), hex(0x6844FC), hex(0xD800CC), hex(0xE40058), hex(0xF83800),
+    hex(0xE45C10), hex(0xAC7C00), hex(0x00B800), hex(0x00A800), hex(0x00A844), hex(0x008888),
+    hex(0xF8F8F8), hex(0x3CBCFC), hex(0x6888FC), hex(0x9878F8), hex(0xF878F8), hex(0xF85898), hex(0xF87858),
+    hex(0xFCA044), hex(0xF8B800), hex(0xB8F818), hex(0x58D854), hex(0x58F898), hex(0x00E8D8), hex(0x787878),
+    hex(0xFCFCFC), hex(0xA4E4FC), hex(0xB8B8F8), hex(0xD8B8F8), hex(0xF8B8F8), hex(0xF8A4C0), hex(0xF0D0B0),
+    hex(0xFCE0A8), hex(0xF8D878), hex(0xD8F878), hex(0xB8F8B8), hex(0xB8F8D8), hex(0x00FCFC), hex(0xF8D8F8),
+];
+
+const GAMEBOY: [[u8; 3]; 4] = [hex(0x0F380F), hex(0x306230), hex(0x8BAC0F), hex(0x9BBC0F)];
+
+const CGA: [[u8; 3]; 16] = [
+    hex(0x000000), hex(0x0000AA), hex(0x00AA00), hex(0x00AAAA),
+    hex(0xAA0000), hex(0xAA00AA), hex(0xAA5500), hex(0xAAAAAA),
+    hex(0x555555), hex(0x5555FF), hex(0x55FF55), hex(0x55FFFF),
+    hex(0xFF5555), hex(0xFF55FF), hex(0xFFFF55), hex(0xFFFFFF),
+];
+
+const DB16: [[u8; 3]; 16] = [
+    hex(0x140C1C), hex(0x442434), hex(0x30346D), hex(0x4E4A4E),
+    hex(0x854C30), hex(0x346524), hex(0xD04648), hex(0x757161),
+    hex(0x597DCE), hex(0xD27D2C), hex(0x8595A1), hex(0x6DAA2C),
+    hex(0xD2AA99), hex(0x6DC2CA), hex(0xDAD45E), hex(0xDEEED6),
+];
+
+const DB32: [[u8; 3]; 32] = [
+    hex(0x000000), hex(0x222034), hex(0x45283C), hex(0x663931), hex(0x8F563B), hex(0xDF7126), hex(0xD9A066), hex(0xEEC39A),
+    hex(0xFBF236), hex(0x99E550), hex(0x6ABE30), hex(0x37946E), hex(0x4B692F), hex(0x524B24), hex(0x323C39), hex(0x3F3F74),
+    hex(0x306082), hex(0x5B6EE1), hex(0x639BFF), hex(0x5FCDE4), hex(0xCBDBFC), hex(0xFFFFFF), hex(0x9BADB7), hex(0x847E87),
+    hex(0x696A6A), hex(0x595652), hex(0x76428A), hex(0xAC3232), hex(0xD95763), hex(0xD77BBA), hex(0x8F974A), hex(0x8A6F30),
+];
+
+const ENDESGA32: [[u8; 3]; 32] = [
+    hex(0xBE4A2F), hex(0xD77643), hex(0xEAD4AA), hex(0xE4A672), hex(0xB86F50), hex(0x733E39), hex(0x3E2731), hex(0xA22633),
+    hex(0xE43B44), hex(0xF77622), hex(0xFEAE34), hex(0xFEE761), hex(0x63C74D), hex(0x3E8948), hex(0x265C42), hex(0x193C3E),
+    hex(0x124E89), hex(0x0099DB), hex(0x2CE8F5), hex(0xFFFFFF), hex(0xC0CBDC), hex(0x8B9BB4), hex(0x5A6988), hex(0x3A4466),
+    hex(0x262B44), hex(0x181425), hex(0xFF0044), hex(0x68386C), hex(0xB55088), hex(0xF6757A), hex(0xE8B796), hex(0xC28569),
+];
+
+impl BuiltinPalette {
+    pub const ALL: [BuiltinPalette; 7] = [
+        BuiltinPalette::Pico8,
+        BuiltinPalette::Nes,
+        BuiltinPalette::Gameboy,
+        BuiltinPalette::Cga,
+        BuiltinPalette::Db16,
+        BuiltinPalette::Db32,
+        BuiltinPalette::Endesga32,
+    ];
+
+    /// 名称（与 serde 一致）
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinPalette::Pico8 => "pico8",
+            BuiltinPalette::Nes => "nes",
+            BuiltinPalette::Gameboy => "gameboy",
+            BuiltinPalette::Cga => "cga",
+            BuiltinPalette::Db16 => "db16",
+            BuiltinPalette::Db32 => "db32",
+            BuiltinPalette::Endesga32 => "endesga32",
+        }
+    }
+
+    /// 按名称查找（不区分大小写，忽略 - 和 _）
+    pub fn from_name(name: &str) -> Option<Self> {
+        let key: String = name
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let key = match key.as_str() {
+            "gb" | "dmg" | "gameboydmg" => "gameboy",
+            "endesga" => "endesga32",
+            other => other,
+        };
+        Self::ALL.into_iter().find(|p| p.name() == key)
+    }
+
+    /// 调色板颜色
+    pub fn colors(self) -> &'static [[u8; 3]] {
+        match self {
+            BuiltinPalette::Pico8 => &PICO8,
+            BuiltinPalette::Nes => &NES,
+            BuiltinPalette::Gameboy => &GAMEBOY,
+            BuiltinPalette::Cga => &CGA,
+            BuiltinPalette::Db16 => &DB16,
+            BuiltinPalette::Db32 => &DB32,
+            BuiltinPalette::Endesga32 => &ENDESGA32,
+        }
+    }
+}
+
+/// 调色板文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP `.gpl`
+    Gpl,
+    /// 每行一个 RRGGBB（Lospec `.hex`）
+    Hex,
+    /// JASC-PAL `.pal`
+    JascPal,
+    /// Paint.NET `.txt`（每行 AARRGGBB，`;` 注释）
+    PaintNet,
+}
+
+impl PaletteFormat {
+    /// 根据文件内容识别格式
+    pub fn detect(text: &str) -> Self {
+        let first = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+        if first.starts_with("GIMP Palette") {
+            PaletteFormat::Gpl
+        } else if first.starts_with("JASC-PAL") {
+            PaletteFormat::JascPal
+        } else if text.lines().map(str::trim).any(|l| l.starts_with(';'))
+            || data_lines(text, ';').any(|l| l.trim_start_matches('#').len() == 8)
+        {
+            PaletteFormat::PaintNet
+        } else {
+            PaletteFormat::Hex
+        }
+    }
+}
+
+/// 非空、非注释行
+fn data_lines(text: &str, comment: char) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(move |l| !l.is_empty() && !l.starts_with(comment))
+}
+
+fn decode_err(format: PaletteFormat, line: &str) -> Img2PicError {
+    Img2PicError::Decode(format!("{:?} palette: invalid line {:?}", format, line))
+}
+
+fn parse_hex_color(s: &str) -> Option<[u8; 3]> {
+    let s = s.trim_start_matches('#');
+    let v = u32::from_str_radix(s, 16).ok()?;
+    match s.len() {
+        6 => Some(hex(v)),
+        // AARRGGBB
+        8 => Some(hex(v & 0x00FF_FFFF)),
+        _ => None,
+    }
+}
+
+fn parse_rgb_triplet(line: &str) -> Option<[u8; 3]> {
+    let mut it = line.split_whitespace().map(|t| t.parse::<u8>().ok());
+    Some([it.next()??, it.next()??, it.next()??])
+}
+
+/// 解析调色板文件（自动识别格式）
+pub fn parse_palette(text: &str) -> Result<Vec<[u8; 3]>> {
+    parse_palette_as(text, PaletteFormat::detect(text))
+}
+
+/// 按指定格式解析调色板文件
+pub fn parse_palette_as(text: &str, format: PaletteFormat) -> Result<Vec<[u8; 3]>> {
+    let colors = match format {
+        PaletteFormat::Gpl => data_lines(text, '#')
+            .skip(1)
+            .filter(|l| !l.starts_with("Name:") && !l.starts_with("Columns:"))
+            .map(|l| parse_rgb_triplet(l).ok_or_else(|| decode_err(format, l)))
+            .collect::<Result<Vec<_>>>()?,
+        PaletteFormat::Hex | PaletteFormat::PaintNet => data_lines(text, ';')
+            .map(|l| parse_hex_color(l).ok_or_else(|| decode_err(format, l)))
+            .collect::<Result<Vec<_>>>()?,
+        PaletteFormat::JascPal => {
+            let mut lines = data_lines(text, '#').skip(2);
+            let count: usize = lines
+                .next()
+                .and_then(|l| l.parse().ok())
+                .ok_or_else(|| Img2PicError::Decode("JASC palette: missing color count".into()))?;
+            let colors = lines
+                .map(|l| parse_rgb_triplet(l).ok_or_else(|| decode_err(format, l)))
+                .collect::<Result<Vec<_>>>()?;
+            if colors.len() != count {
+                return Err(Img2PicError::Decode(format!(
+                    "JASC palette: expected {} colors, got {}",
+                    count,
+                    colors.len()
+                )));
+            }
+            colors
+        }
+    };
+    if colors.is_empty() {
+        return Err(Img2PicError::Decode("palette contains no colors".into()));
+    }
+    Ok(colors)
+}
+
+/// 在指定色彩空间中查找调色板最近色
+#[derive(Debug, Clone)]
+pub struct PaletteMatcher {
+    colors: Vec<[u8; 3]>,
+    converted: Vec<[f32; 3]>,
+    space: ColorSpace,
+}
+
+impl PaletteMatcher {
+    pub fn new(colors: &[[u8; 3]], space: ColorSpace) -> Result<Self> {
+        if colors.is_empty() {
+            return Err(invalid("palette must not be empty"));
+        }
+        let converted = colors.iter().map(|c| space.convert(c.map(|v| v as f32))).collect();
+        Ok(Self { colors: colors.to_vec(), converted, space })
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// 最近色下标；rgb 为 0-255（可为小数，如误差扩散后的值）
+    pub fn nearest_index(&self, rgb: [f32; 3]) -> usize {
+        let c = self.space.convert(rgb);
+        self.converted
+            .iter()
+            .map(|p| dist2(c, *p))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    /// 最近色
+    pub fn nearest(&self, rgb: [f32; 3]) -> [u8; 3] {
+        self.colors[self.nearest_index(rgb)]
+    }
+}
+
+/// 将像素画映射到固定调色板；alpha 低于 alpha_threshold 的像素原样保留
+pub fn map_to_palette(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    palette: &[[u8; 3]],
+    space: ColorSpace,
+    alpha_threshold: u8,
+) -> Result<Vec<u8>> {
+    check_len("map_to_palette rgba", rgba.len(), width * height * 4)?;
+    let matcher = PaletteMatcher::new(palette, space)?;
+
+    let mut out = rgba.to_vec();
+    let mut cache: HashMap<[u8; 3], [u8; 3]> = HashMap::new();
+    for px in out.chunks_exact_mut(4) {
+        if px[3] < alpha_threshold {
+            continue;
+        }
+        let c = [px[0], px[1], px[2]];
+        let mapped = *cache.entry(c).or_insert_with(|| matcher.nearest(c.map(|v| v as f32)));
+        px[..3].copy_from_slice(&mapped);
+    }
+    Ok(out)
+}
+
+/// 像素画中实际使用的一种颜色
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteEntry {
+    pub color: [u8; 3],
+    /// 使用该颜色的（不透明）单元格数
+    pub count: usize,
+    /// 占不透明单元格的百分比（0~100）
+    pub coverage: f32,
+}
+
+/// 提取调色板时的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteSort {
+    /// 按使用次数从多到少
+    #[default]
+    Frequency,
+    /// 按色相（灰色在前，按亮度排列）
+    Hue,
+}
+
+/// 从单元格网格（原生分辨率像素画）中提取实际使用的颜色
+/// alpha 低于 alpha_threshold 的单元格不计入
+pub fn extract_palette(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    alpha_threshold: u8,
+    sort: PaletteSort,
+) -> Result<Vec<PaletteEntry>> {
+    check_len("extract_palette rgba", rgba.len(), width * height * 4)?;
+
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for px in rgba.chunks_exact(4).filter(|px| px[3] >= alpha_threshold) {
+        *counts.entry([px[0], px[1], px[2]]).or_insert(0) += 1;
+    }
+    let total = counts.values().sum::<usize>().max(1) as f32;
+
+    let mut entries: Vec<PaletteEntry> = counts
+        .into_iter()
+        .map(|(color, count)| PaletteEntry { color, count, coverage: count as f32 * 100.0 / total })
+        .collect();
+    match sort {
+        PaletteSort::Frequency => entries.sort_by(|a, b| b.count.cmp(&a.count).then(a.color.cmp(&b.color))),
+        PaletteSort::Hue => entries.sort_by(|a, b| {
+            hue_key(a.color).partial_cmp(&hue_key(b.color)).unwrap().then(a.color.cmp(&b.color))
+        }),
+    }
+    Ok(entries)
+}
+
+/// 色相排序键：(是否彩色, 色相, 亮度)
+fn hue_key(c: [u8; 3]) -> (bool, f32, f32) {
+    let [r, g, b] = c.map(|v| v as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    // 饱和度很低的颜色视为灰色
+    if max <= 0.0 || delta / max < 0.08 {
+        return (false, 0.0, max);
+    }
+    let hue = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    (true, hue * 60.0, max)
+}
+
+/// 导出 GIMP `.gpl` 调色板
+pub fn export_gpl(colors: &[[u8; 3]], name: &str) -> String {
+    let mut out = format!("GIMP Palette\nName: {}\nColumns: {}\n#\n", name, colors.len().clamp(1, 16));
+    for c in colors {
+        out.push_str(&format!("{:3} {:3} {:3}\t#{:02X}{:02X}{:02X}\n", c[0], c[1], c[2], c[0], c[1], c[2]));
+    }
+    out
+}
+
+/// 导出 `.hex` 调色板（每行一个 rrggbb）
+pub fn export_hex(colors: &[[u8; 3]]) -> String {
+    colors.iter().map(|c| format!("{:02x}{:02x}{:02x}\n", c[0], c[1], c[2])).collect()
+}
+
+/// 导出 Adobe Swatch Exchange（`.ase`）色板，颜色名为 #RRGGBB
+pub fn export_ase(colors: &[[u8; 3]]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(b"ASEF");
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&(colors.len() as u32).to_be_bytes());
+
+    for c in colors {
+        let name: Vec<u16> = format!("#{:02X}{:02X}{:02X}", c[0], c[1], c[2]).encode_utf16().chain([0]).collect();
+        let block_len = 2 + name.len() * 2 + 4 + 3 * 4 + 2;
+        // 颜色条目
+        out.extend_from_slice(&0x0001u16.to_be_bytes());
+        out.extend_from_slice(&(block_len as u32).to_be_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        for ch in name {
+            out.extend_from_slice(&ch.to_be_bytes());
+        }
+        out.extend_from_slice(b"RGB ");
+        for v in c {
+            out.extend_from_slice(&(*v as f32 / 255.0).to_be_bytes());
+        }
+        // 0=global, 1=spot, 2=normal
+        out.extend_from_slice(&2u16.to_be_bytes());
+    }
+    out
+}
+
+/// 生成横向色卡条：每种颜色一个 swatch_size x swatch_size 的色块
+pub fn swatch_strip(colors: &[[u8; 3]], swatch_size: usize) -> Result<RgbaImage> {
+    if colors.is_empty() || swatch_size == 0 {
+        return Err(invalid("swatch strip needs at least one color and swatch_size >= 1"));
+    }
+    let width = colors.len() * swatch_size;
+    let mut data = Vec::with_capacity(width * swatch_size * 4);
+    for _ in 0..swatch_size {
+        for c in colors {
+            for _ in 0..swatch_size {
+                data.extend_from_slice(&[c[0], c[1], c[2], 255]);
+            }
+        }
+    }
+    Ok(RgbaImage::new(width, swatch_size, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_palettes() {
+        assert_eq!(BuiltinPalette::Pico8.colors().len(), 16);
+        assert_eq!(BuiltinPalette::Gameboy.colors().len(), 4);
+        assert_eq!(BuiltinPalette::Db32.colors().len(), 32);
+        assert_eq!(BuiltinPalette::Endesga32.colors().len(), 32);
+        assert_eq!(BuiltinPalette::from_name("PICO-8"), Some(BuiltinPalette::Pico8));
+        assert_eq!(BuiltinPalette::from_name("gb"), Some(BuiltinPalette::Gameboy));
+        assert_eq!(BuiltinPalette::from_name("nope"), None);
+    }
+
+    #[test]
+    fn test_parse_palette_formats() {
+        let gpl = "GIMP Palette\nName: test\nColumns: 2\n# comment\n255   0   0\tRed\n  0  0 255 Blue\n";
+        let hex = "ff0000\n#0000FF\n";
+        let jasc = "JASC-PAL\n0100\n2\n255 0 0\n0 0 255\n";
+        let pdn = "; Paint.NET Palette File\n;comment\nFFFF0000\nFF0000FF\n";
+        for (text, format) in [
+            (gpl, PaletteFormat::Gpl),
+            (hex, PaletteFormat::Hex),
+            (jasc, PaletteFormat::JascPal),
+            (pdn, PaletteFormat::PaintNet),
+        ] {
+            assert_eq!(PaletteFormat::detect(text), format);
+            assert_eq!(parse_palette(text).unwrap(), vec![[255, 0, 0], [0, 0, 255]], "{:?}", format);
+        }
+        assert!(parse_palette("JASC-PAL\n0100\n3\n255 0 0\n").is_err());
+        assert!(parse_palette("zzzzzz\n").is_err());
+    }
+
+    #[test]
+    fn test_map_to_palette_spaces() {
+        let rgba = [240, 240, 70, 255, 0, 0, 0, 0];
+        for space in [ColorSpace::Rgb, ColorSpace::Lab, ColorSpace::Oklab] {
+            let out = map_to_palette(&rgba, 2, 1, BuiltinPalette::Cga.colors(), space, 128).unwrap();
+            assert_eq!(out[..4], [255, 255, 85, 255], "{:?}", space);
+            assert_eq!(out[4..], [0, 0, 0, 0]);
+        }
+        assert!(map_to_palette(&rgba, 2, 1, &[], ColorSpace::Rgb, 128).is_err());
+    }
+
+    #[test]
+    fn test_extract_palette() {
+        // 红 x2、蓝 x1、灰 x1、透明 x1
+        let rgba = [
+            255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255, 128, 128, 128, 255, 0, 255, 0, 0,
+        ];
+        let by_freq = extract_palette(&rgba, 5, 1, 128, PaletteSort::Frequency).unwrap();
+        assert_eq!(by_freq.len(), 3);
+        assert_eq!(by_freq[0].color, [255, 0, 0]);
+        assert_eq!(by_freq[0].count, 2);
+        assert!((by_freq[0].coverage - 50.0).abs() < 1e-4);
+
+        let by_hue = extract_palette(&rgba, 5, 1, 128, PaletteSort::Hue).unwrap();
+        let colors: Vec<[u8; 3]> = by_hue.iter().map(|e| e.color).collect();
+        assert_eq!(colors, vec![[128, 128, 128], [255, 0, 0], [0, 0, 255]]);
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let colors = vec![[255, 0, 0], [0, 128, 255]];
+        assert_eq!(parse_palette(&export_gpl(&colors, "test")).unwrap(), colors);
+        assert_eq!(parse_palette(&export_hex(&colors)).unwrap(), colors);
+
+        let ase = export_ase(&colors);
+        assert_eq!(&ase[..4], b"ASEF");
+        assert_eq!(u32::from_be_bytes(ase[8..12].try_into().unwrap()), 2);
+        // 每个条目：类型 2 + 长度 4 + 名称长度 2 + "#RRGGBB\0" 16 + "RGB " 4 + 3*f32 12 + 颜色类型 2
+        assert_eq!(ase.len(), 12 + 2 * 42);
+
+        let strip = swatch_strip(&colors, 2).unwrap();
+        assert_eq!((strip.width, strip.height), (4, 2));
+        assert_eq!(strip.data[8..12], [0, 128, 255, 255]);
+    }
+}
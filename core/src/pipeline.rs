@@ -0,0 +1,1022 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::energy::{
+    rgba_to_gray01, grad_energy_with, color_grad_energy, alpha_grad_energy, apply_alpha, enhance_energy_directional_with,
+    structure_tensor_energy, normalize_energy, deblock_jpeg, jpeg_blockiness, is_jpeg_aligned_grid, AlphaMode,
+    ChannelCombine, EnergyChannels, EnergyNorm, EnergyProjection, EnhanceMode, JpegDeblock, NormalizeParams,
+    JPEG_BLOCKINESS_THRESHOLD,
+};
+use crate::error::{check_len, Result};
+use crate::filters::{
+    denoise_rgba, BorderMode, DenoiseFilter, DenoiseParams, GradientAxis, GradientNorm, GradientOperator,
+    GradientParams,
+};
+use crate::grid::{
+    detect_pixel_size_report_xy, detect_grid_lines_xy, interpolate_lines, complete_edges, lattice_lines, PeriodMethod,
+    PixelSizeReport, sample_pixel_art_direct, sample_pixel_art, upscale_pixel_art,
+};
+use crate::color::ColorSpace;
+use crate::dither::{dither_to_palette, DitherMode, DitherParams};
+use crate::palette::{map_to_palette, BuiltinPalette, PaletteMatcher};
+use crate::quantize::{quantize_rgba, QuantizeMethod, QuantizeParams};
+use crate::types::{PixelArt, PixelSize, RgbaImage, SampleMode};
+
+/// 完整处理流程参数（字段与前端 `PipelineParams` 一一对应，camelCase）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PipelineParams {
+    pub sigma: f64,
+    /// 梯度能量通道：luma=灰度，rgb/oklab/lab=逐通道彩色梯度
+    pub energy_channels: EnergyChannels,
+    /// 彩色梯度的通道合并方式
+    pub energy_combine: ChannelCombine,
+    /// 计算能量前透明度对颜色的处理：ignore / premultiply / mask
+    pub alpha_mode: AlphaMode,
+    /// alpha 梯度能量的叠加权重；0=不使用
+    pub alpha_weight: f32,
+    /// JPEG 8x8 块效应预处理：off / on / auto
+    pub jpeg_deblock: JpegDeblock,
+
+    // denoise
+    /// 保边去噪滤波器；none=不去噪
+    pub denoise: DenoiseFilter,
+    pub denoise_radius: usize,
+    /// 双边滤波空间 / 值域标准差（值域为 0-1 范围）
+    pub denoise_sigma_space: f32,
+    pub denoise_sigma_range: f32,
+    /// 引导滤波正则项
+    pub denoise_eps: f32,
+    /// 去噪后的图像用于能量计算
+    pub denoise_energy: bool,
+    /// 去噪后的图像用于采样
+    pub denoise_sample: bool,
+    /// 梯度算子
+    pub gradient_operator: GradientOperator,
+    /// 梯度幅值范数
+    pub gradient_norm: GradientNorm,
+    /// 卷积、梯度与网格投影的边界处理；可平铺纹理用 wrap
+    /// None：卷积与梯度使用 reflect101，网格投影平滑只在图像内取平均
+    pub border_mode: Option<BorderMode>,
+
+    pub enhance_energy: bool,
+    /// 方向增强方式；structuretensor 抑制斜向边缘
+    pub enhance_mode: EnhanceMode,
+    /// 结构张量积分尺度（高斯标准差）
+    pub structure_sigma: f64,
+    pub enhance_directional: bool,
+    pub enhance_horizontal: f32,
+    pub enhance_vertical: f32,
+    /// 方向增强前的离群值裁剪分位数；1.0=不裁剪
+    pub enhance_clip_quantile: f64,
+
+    // energy normalization
+    /// 能量图归一化方式
+    pub energy_norm: EnergyNorm,
+    /// percentile 归一化的分位数
+    pub energy_quantile: f64,
+    /// CLAHE 每轴分块数
+    pub clahe_tiles: usize,
+    /// CLAHE 直方图裁剪倍数（相对平均 bin 高度）
+    pub clahe_clip: f32,
+    /// 网格检测直接使用归一化后的浮点能量图，不量化为 8 位
+    pub float_energy: bool,
+    /// split：列 / 行投影分别使用 |gx| / |gy| 能量图；combined：共用合并能量图
+    pub energy_projection: EnergyProjection,
+
+    // grid
+    pub gap_tolerance: usize,
+    /// 当间距大于 typical * interp_threshold 时插入插值线
+    pub interp_threshold: f32,
+    /// 0..1 * max(profile)
+    pub min_energy: f32,
+    /// 1D 平滑窗口
+    pub smooth: usize,
+    /// 0=auto
+    pub window_size: usize,
+
+    // pixel size detect
+    /// 0=auto
+    pub pixel_size: usize,
+    /// 手动指定时 y 轴的像素大小；0=与 pixel_size 相同
+    pub pixel_size_y: usize,
+    /// 两轴使用同一像素大小（combined），不分别检测
+    pub square_pixels: bool,
+    /// 亚像素网格：按拟合的非整数周期生成等距网格线，避免整数步长在整幅图上累积漂移
+    pub subpixel_grid: bool,
+    pub min_s: usize,
+    /// 0=由图像尺寸自动确定上限
+    pub max_s: usize,
+    /// 忽略 min_s / max_s，由图像尺寸确定搜索范围（2 到短边的 1/4，粗到细搜索）
+    pub auto_range: bool,
+    /// 像素大小检测方法：autocorr=自相关，spectral=周期图（谐波抑制）
+    pub period_method: PeriodMethod,
+
+    // sampling
+    pub sample: bool,
+    pub sample_mode: SampleMode,
+    /// 0.1..0.9
+    pub sample_weight_ratio: f32,
+    /// 0=use pixelSize; 1=native; >1 custom
+    pub upscale: usize,
+    /// true => 1 pixel per cell
+    pub native_res: bool,
+
+    // quantize (在单元格网格上进行，放大之前)
+    pub quantize: bool,
+    pub quantize_method: QuantizeMethod,
+    /// 0=auto
+    pub colors: usize,
+    /// smart 模式相似度阈值 0..1
+    pub similarity_threshold: f32,
+
+    // fixed palette (在量化之后、放大之前映射)
+    /// 内置调色板；None=不使用
+    pub palette: Option<BuiltinPalette>,
+    /// 自定义调色板，非空时优先于 palette
+    pub custom_palette: Vec<[u8; 3]>,
+    /// 调色板最近色匹配使用的色彩空间
+    pub palette_space: ColorSpace,
+
+    // dithering (仅在启用量化或调色板时生效)
+    pub dither: DitherMode,
+    /// 0..1
+    pub dither_strength: f32,
+    /// 完全透明（alpha = 0）的单元格不抖动
+    pub dither_skip_transparent: bool,
+}
+
+impl Default for PipelineParams {
+    fn default() -> Self {
+        Self {
+            sigma: 1.0,
+            energy_channels: EnergyChannels::Luma,
+            energy_combine: ChannelCombine::Max,
+            alpha_mode: AlphaMode::Ignore,
+            alpha_weight: 0.0,
+            jpeg_deblock: JpegDeblock::Off,
+            denoise: DenoiseFilter::None,
+            denoise_radius: 2,
+            denoise_sigma_space: 2.0,
+            denoise_sigma_range: 0.1,
+            denoise_eps: 0.01,
+            denoise_energy: true,
+            denoise_sample: false,
+            gradient_operator: GradientOperator::Sobel,
+            gradient_norm: GradientNorm::L1,
+            border_mode: None,
+            enhance_energy: false,
+            enhance_mode: EnhanceMode::Sobel,
+            structure_sigma: 1.5,
+            enhance_directional: false,
+            enhance_horizontal: 1.0,
+            enhance_vertical: 1.0,
+            enhance_clip_quantile: 0.999,
+            energy_norm: EnergyNorm::Percentile,
+            energy_quantile: 0.99,
+            clahe_tiles: 8,
+            clahe_clip: 2.0,
+            float_energy: false,
+            energy_projection: EnergyProjection::Split,
+            gap_tolerance: 2,
+            interp_threshold: 1.5,
+            min_energy: 0.15,
+            smooth: 3,
+            window_size: 0,
+            pixel_size: 0,
+            pixel_size_y: 0,
+            square_pixels: false,
+            subpixel_grid: false,
+            period_method: PeriodMethod::Autocorr,
+            min_s: 4,
+            max_s: 24,
+            auto_range: false,
+            sample: true,
+            sample_mode: SampleMode::Center,
+            sample_weight_ratio: 0.6,
+            upscale: 0,
+            native_res: false,
+            quantize: false,
+            quantize_method: QuantizeMethod::Smart,
+            colors: 0,
+            similarity_threshold: 0.8,
+            palette: None,
+            custom_palette: Vec::new(),
+            palette_space: ColorSpace::Rgb,
+            dither: DitherMode::None,
+            dither_strength: 1.0,
+            dither_skip_transparent: true,
+        }
+    }
+}
+
+/// 完整处理流程结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineResult {
+    pub width: usize,
+    pub height: usize,
+
+    /// 检测（或手动指定）的像素大小；direct 采样模式下为 0
+    pub detected_pixel_size: usize,
+    /// x / y 轴各自的像素大小（非正方形单元格时与 detected_pixel_size 不同）
+    pub detected_pixel_size_x: usize,
+    pub detected_pixel_size_y: usize,
+    /// x / y 轴的亚像素周期
+    pub detected_period_x: f32,
+    pub detected_period_y: f32,
+    /// 自动检测时的候选像素大小与置信度（手动指定像素大小或 direct 模式下为 None）
+    pub pixel_size_report: Option<PixelSizeReport>,
+
+    /// 8 位能量热力图，长度 = width * height；split 模式下为两个方向的逐像素最大值
+    pub energy_u8: Vec<u8>,
+    /// split 模式下用于列投影的 |gx| 能量热力图（combined 模式为空）
+    pub energy_x_u8: Vec<u8>,
+    /// split 模式下用于行投影的 |gy| 能量热力图（combined 模式为空）
+    pub energy_y_u8: Vec<u8>,
+
+    /// 检测到的网格线
+    pub x_lines: Vec<usize>,
+    pub y_lines: Vec<usize>,
+    /// 插值并补全边缘后的网格线
+    pub all_x_lines: Vec<usize>,
+    pub all_y_lines: Vec<usize>,
+    /// all_x_lines / all_y_lines 的浮点位置（subpixel_grid 时为未取整的亚像素位置）
+    pub all_x_positions: Vec<f32>,
+    pub all_y_positions: Vec<f32>,
+
+    /// 原生分辨率的单元格网格（每个单元格 1 像素，已量化 / 映射调色板）
+    pub cells: Option<PixelArt>,
+    /// 采样得到的像素画（按 upscale_factor 放大；params.sample 为 false 时为 None）
+    pub pixel_art: Option<PixelArt>,
+    /// 像素画的放大倍数
+    pub upscale_factor: usize,
+    /// 像素画实际使用的颜色（未启用量化 / 调色板时为空）
+    pub palette: Vec<[u8; 3]>,
+    /// 检测结果可能不可靠时的提示（例如与 JPEG 块网格对齐的 8px 网格）
+    pub warnings: Vec<String>,
+}
+
+/// 像素大小检测报告中保留的候选数
+const CANDIDATES: usize = 5;
+
+/// 端到端处理流程：
+/// gray → grad_energy → enhance_energy_directional → to_heatmap_u8 → detect_pixel_size
+/// → detect_grid_lines → interpolate_lines → complete_edges → sample_pixel_art
+pub struct Pipeline;
+
+impl Pipeline {
+    /// 运行完整流程
+    pub fn run(image: &RgbaImage, params: &PipelineParams) -> Result<PipelineResult> {
+        let (width, height) = (image.width, image.height);
+        check_len("pipeline rgba", image.data.len(), width * height * 4)?;
+
+        let mut energy_u8 = vec![0u8; width * height];
+        let mut energy_x_u8 = Vec::new();
+        let mut energy_y_u8 = Vec::new();
+        let mut size = PixelSize::default();
+        let mut report = None;
+        let mut x_lines = Vec::new();
+        let mut y_lines = Vec::new();
+        let mut all_x = Vec::new();
+        let mut all_y = Vec::new();
+        let mut all_x_pos = Vec::new();
+        let mut all_y_pos = Vec::new();
+        let mut warnings = Vec::new();
+
+        // 保边去噪（可分别作用于能量计算和采样）
+        let denoised = if params.denoise != DenoiseFilter::None && (params.denoise_energy || params.denoise_sample) {
+            Some(denoise_rgba(&image.data, width, height, &params.denoise_params())?)
+        } else {
+            None
+        };
+        let pick = |use_denoised: bool| match &denoised {
+            Some(d) if use_denoised => d.as_slice(),
+            _ => image.data.as_slice(),
+        };
+        let (energy_src, sample_src) = (pick(params.denoise_energy), pick(params.denoise_sample));
+
+        // 直接采样模式不需要能量图和网格检测
+        if params.sample_mode != SampleMode::Direct {
+            // 0) JPEG 去块（仅影响能量图，采样仍使用原图）
+            let deblock = match params.jpeg_deblock {
+                JpegDeblock::Off => false,
+                JpegDeblock::On => true,
+                JpegDeblock::Auto => jpeg_blockiness(energy_src, width, height)? > JPEG_BLOCKINESS_THRESHOLD,
+            };
+            let source = if deblock {
+                Cow::Owned(deblock_jpeg(energy_src, width, height)?)
+            } else {
+                Cow::Borrowed(energy_src)
+            };
+
+            // 1) energy（split 模式下列 / 行投影分别使用 |gx| / |gy| 能量图）
+            let rgba = apply_alpha(&source, params.alpha_mode);
+            let energy_for = |axis: GradientAxis| -> Result<Vec<f32>> {
+                let grad = GradientParams { axis, ..params.gradient_params() };
+                let mut energy = match params.energy_channels.color_space() {
+                    None => grad_energy_with(&rgba_to_gray01(&rgba, width, height)?, params.sigma, grad)?,
+                    Some(space) => {
+                        color_grad_energy(&rgba, width, height, params.sigma, space, params.energy_combine, grad)?
+                    }
+                };
+                if params.alpha_weight > 0.0 {
+                    let alpha = alpha_grad_energy(energy_src, width, height, params.sigma, grad)?;
+                    for (e, a) in energy.data.iter_mut().zip(&alpha.data) {
+                        *e += params.alpha_weight * a;
+                    }
+                }
+
+                if params.enhance_energy {
+                    let (h_factor, v_factor) = if params.enhance_directional {
+                        (params.enhance_horizontal, params.enhance_vertical)
+                    } else {
+                        (1.5, 1.5)
+                    };
+                    let (clip, border) = (params.enhance_clip_quantile, params.border_mode.unwrap_or_default());
+                    energy = match params.enhance_mode {
+                        EnhanceMode::Sobel => {
+                            enhance_energy_directional_with(&energy, h_factor, v_factor, clip, border)?
+                        }
+                        EnhanceMode::StructureTensor => {
+                            let rho = params.structure_sigma;
+                            structure_tensor_energy(&energy, h_factor, v_factor, rho, clip, border)?
+                        }
+                    };
+                }
+
+                normalize_energy(&energy, &params.normalize_params())
+            };
+            let to_u8 = |norm: &[f32]| -> Vec<u8> { norm.iter().map(|&v| (v * 255.0) as u8).collect() };
+
+            let split = params.energy_projection == EnergyProjection::Split;
+            let norm_x = energy_for(if split { GradientAxis::X } else { GradientAxis::Both })?;
+            let norm_y = if split { Some(energy_for(GradientAxis::Y)?) } else { None };
+            let norm_y = norm_y.as_deref().unwrap_or(&norm_x);
+            energy_u8 = to_u8(&norm_x);
+            if split {
+                energy_x_u8 = std::mem::take(&mut energy_u8);
+                energy_y_u8 = to_u8(norm_y);
+                energy_u8 = energy_x_u8.iter().zip(&energy_y_u8).map(|(&x, &y)| x.max(y)).collect();
+            }
+            let (ex_u8, ey_u8) = if split { (&energy_x_u8, &energy_y_u8) } else { (&energy_u8, &energy_u8) };
+
+            // 2) pixel size detect (if needed)
+            let (min_s, max_s) = if params.auto_range { (0, 0) } else { (params.min_s, params.max_s) };
+            let (border, method) = (params.border_mode.unwrap_or_default(), params.period_method);
+            size = if params.pixel_size > 0 {
+                let y = if params.pixel_size_y > 0 { params.pixel_size_y } else { params.pixel_size };
+                let (period_x, period_y) = (params.pixel_size as f32, y as f32);
+                PixelSize { x: params.pixel_size, y, combined: params.pixel_size, period_x, period_y }
+            } else {
+                let n = CANDIDATES;
+                let detected = if params.float_energy {
+                    detect_pixel_size_report_xy(&norm_x, norm_y, width, height, min_s, max_s, border, method, n)?
+                } else {
+                    detect_pixel_size_report_xy(ex_u8, ey_u8, width, height, min_s, max_s, border, method, n)?
+                };
+                if let Some(divisor) = detected.divisor() {
+                    warnings.push(format!(
+                        "pixel size {} may be a multiple of the true pixel size {} (confidence {:.2})",
+                        detected.size.combined, divisor.size, detected.confidence
+                    ));
+                }
+                report.insert(detected).size
+            };
+            if params.square_pixels {
+                size = size.square();
+            }
+
+            // 3) grid detect
+            let (tol, min_e, smooth, win) =
+                (params.gap_tolerance, params.min_energy, params.smooth, params.window_size);
+            let border = params.border_mode;
+            let lines = if params.float_energy {
+                detect_grid_lines_xy(&norm_x, norm_y, width, height, size.x, size.y, tol, min_e, smooth, win, border)?
+            } else {
+                detect_grid_lines_xy(ex_u8, ey_u8, width, height, size.x, size.y, tol, min_e, smooth, win, border)?
+            };
+            x_lines = lines.x_lines;
+            y_lines = lines.y_lines;
+            if size.x == size.y && is_jpeg_aligned_grid(size.x, &x_lines, &y_lines) {
+                warnings.push(format!(
+                    "pixel size {} is aligned to the JPEG 8x8 block grid and may be a compression artifact{}",
+                    size.x,
+                    if deblock { "" } else { "; try jpegDeblock" }
+                ));
+            }
+
+            // 4) interpolate + complete edges（亚像素模式下直接由拟合的等距格点生成）
+            if params.subpixel_grid {
+                all_x_pos = lattice_lines(&x_lines, width, size.period_x)?;
+                all_y_pos = lattice_lines(&y_lines, height, size.period_y)?;
+                all_x = round_lines(&all_x_pos);
+                all_y = round_lines(&all_y_pos);
+            } else {
+                let all_x0 = interpolate_lines(&x_lines, width, size.x, params.interp_threshold)?;
+                let all_y0 = interpolate_lines(&y_lines, height, size.y, params.interp_threshold)?;
+
+                let typical_x = mean_gap(&all_x0, size.x);
+                let typical_y = mean_gap(&all_y0, size.y);
+
+                all_x = complete_edges(&all_x0, width, typical_x, params.gap_tolerance)?;
+                all_y = complete_edges(&all_y0, height, typical_y, params.gap_tolerance)?;
+                all_x_pos = all_x.iter().map(|&v| v as f32).collect();
+                all_y_pos = all_y.iter().map(|&v| v as f32).collect();
+            }
+        }
+
+        // 5) pixel art (optional)
+        // 原生分辨率优先级最高，强制使用 1x
+        let upscale_factor = if params.native_res {
+            1
+        } else if params.upscale > 0 {
+            params.upscale
+        } else {
+            size.combined.max(1)
+        };
+        let fixed_palette = params.palette_colors();
+
+        // 始终先按原生分辨率采样得到单元格网格，量化 / 调色板映射在网格上进行，最后再放大
+        let cells = if !params.sample {
+            None
+        } else if params.sample_mode == SampleMode::Direct {
+            // 直接采样模式必须手动设置像素大小
+            let direct_size = if params.pixel_size > 0 { params.pixel_size } else { 8 };
+            Some(sample_pixel_art_direct(
+                sample_src,
+                width,
+                height,
+                width / direct_size,
+                height / direct_size,
+                params.sample_mode,
+                params.sample_weight_ratio,
+                1,
+                true,
+            )?)
+        } else {
+            Some(sample_pixel_art(
+                sample_src,
+                width,
+                height,
+                &all_x,
+                &all_y,
+                params.sample_mode,
+                params.sample_weight_ratio,
+                1,
+                true,
+            )?)
+        };
+
+        let mut palette = Vec::new();
+        let cells = match cells {
+            Some(art) if params.quantize || fixed_palette.is_some() => {
+                let (art, used) = reduce_colors(&art, params, fixed_palette)?;
+                palette = used;
+                Some(art)
+            }
+            other => other,
+        };
+        let pixel_art = match &cells {
+            Some(art) if upscale_factor > 1 => Some(upscale_pixel_art(art, upscale_factor)?),
+            other => other.clone(),
+        };
+
+        Ok(PipelineResult {
+            width,
+            height,
+            detected_pixel_size: size.combined,
+            detected_pixel_size_x: size.x,
+            detected_pixel_size_y: size.y,
+            detected_period_x: size.period_x,
+            detected_period_y: size.period_y,
+            pixel_size_report: report,
+            energy_u8,
+            energy_x_u8,
+            energy_y_u8,
+            x_lines,
+            y_lines,
+            all_x_lines: all_x,
+            all_y_lines: all_y,
+            all_x_positions: all_x_pos,
+            all_y_positions: all_y_pos,
+            cells,
+            pixel_art,
+            upscale_factor,
+            palette,
+            warnings,
+        })
+    }
+}
+
+impl PipelineParams {
+    /// 梯度算子与范数
+    pub fn gradient_params(&self) -> GradientParams {
+        GradientParams {
+            operator: self.gradient_operator,
+            norm: self.gradient_norm,
+            border: self.border_mode.unwrap_or_default(),
+            axis: GradientAxis::Both,
+        }
+    }
+
+    /// 能量图归一化参数
+    pub fn normalize_params(&self) -> NormalizeParams {
+        NormalizeParams {
+            method: self.energy_norm,
+            quantile: self.energy_quantile,
+            clahe_tiles: self.clahe_tiles,
+            clahe_clip: self.clahe_clip,
+        }
+    }
+
+    /// 去噪参数
+    pub fn denoise_params(&self) -> DenoiseParams {
+        DenoiseParams {
+            filter: self.denoise,
+            radius: self.denoise_radius,
+            sigma_space: self.denoise_sigma_space,
+            sigma_range: self.denoise_sigma_range,
+            eps: self.denoise_eps,
+        }
+    }
+
+    /// 抖动参数
+    pub fn dither_params(&self) -> DitherParams {
+        DitherParams {
+            mode: self.dither,
+            strength: self.dither_strength,
+            skip_transparent: self.dither_skip_transparent,
+            ..DitherParams::default()
+        }
+    }
+
+    /// 生效的固定调色板（自定义优先）
+    pub fn palette_colors(&self) -> Option<&[[u8; 3]]> {
+        if !self.custom_palette.is_empty() {
+            Some(&self.custom_palette)
+        } else {
+            self.palette.map(BuiltinPalette::colors)
+        }
+    }
+
+    /// 量化参数
+    pub fn quantize_params(&self) -> QuantizeParams {
+        QuantizeParams {
+            method: self.quantize_method,
+            colors: self.colors,
+            similarity_threshold: self.similarity_threshold,
+            ..QuantizeParams::default()
+        }
+    }
+}
+
+/// 在原生分辨率像素画上做量化 / 调色板映射 / 抖动，返回结果与实际使用的颜色
+fn reduce_colors(
+    art: &PixelArt,
+    params: &PipelineParams,
+    fixed_palette: Option<&[[u8; 3]]>,
+) -> Result<(PixelArt, Vec<[u8; 3]>)> {
+    let quantize = params.quantize_params();
+    let alpha_threshold = quantize.alpha_threshold;
+    let mut rgba = art.rgba.clone();
+    // 最终目标调色板
+    let mut target: Vec<[u8; 3]> = Vec::new();
+
+    if params.quantize {
+        let q = quantize_rgba(&rgba, art.width, art.height, &quantize)?;
+        rgba = q.rgba;
+        target = q.palette;
+    }
+    if let Some(colors) = fixed_palette {
+        rgba = map_to_palette(&rgba, art.width, art.height, colors, params.palette_space, alpha_threshold)?;
+        target = if params.quantize {
+            // 量化调色板中的每种颜色映射到固定调色板
+            let matcher = PaletteMatcher::new(colors, params.palette_space)?;
+            let mut mapped: Vec<[u8; 3]> = Vec::new();
+            for c in target {
+                let m = matcher.nearest(c.map(|v| v as f32));
+                if !mapped.contains(&m) {
+                    mapped.push(m);
+                }
+            }
+            mapped
+        } else {
+            colors.to_vec()
+        };
+    }
+
+    // 抖动：从原始采样颜色出发，重新映射到目标调色板
+    if params.dither != DitherMode::None && !target.is_empty() {
+        rgba = dither_to_palette(
+            &art.rgba,
+            art.width,
+            art.height,
+            &target,
+            params.palette_space,
+            &params.dither_params(),
+        )?;
+    }
+
+    let used = used_colors(&rgba, &target, alpha_threshold);
+    Ok((PixelArt::from_rgba(art.width, art.height, rgba), used))
+}
+
+/// 调色板中在不透明像素里实际出现的颜色（保持调色板顺序）
+fn used_colors(rgba: &[u8], palette: &[[u8; 3]], alpha_threshold: u8) -> Vec<[u8; 3]> {
+    let used: std::collections::HashSet<[u8; 3]> = rgba
+        .chunks_exact(4)
+        .filter(|px| px[3] >= alpha_threshold)
+        .map(|px| [px[0], px[1], px[2]])
+        .collect();
+    palette.iter().filter(|c| used.contains(*c)).copied().collect()
+}
+
+/// 亚像素位置取整为像素网格线（去重）
+fn round_lines(positions: &[f32]) -> Vec<usize> {
+    let mut lines: Vec<usize> = positions.iter().map(|&p| p.round() as usize).collect();
+    lines.dedup();
+    lines
+}
+
+/// 平均间距（四舍五入），少于两条线时返回 fallback
+fn mean_gap(lines: &[usize], fallback: usize) -> usize {
+    match (lines.first(), lines.last()) {
+        (Some(&first), Some(&last)) if lines.len() > 1 => {
+            ((last - first) as f64 / (lines.len() - 1) as f64).round() as usize
+        }
+        _ => fallback,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cell x cell 单元格的棋盘格 RGBA 图
+    fn checker(width: usize, height: usize, cell: usize) -> RgbaImage {
+        let mut data = vec![0u8; width * height * 4];
+        for y in 0..height {
+            for x in 0..width {
+                let v = if (x / cell + y / cell).is_multiple_of(2) { 230 } else { 20 };
+                let i = (y * width + x) * 4;
+                data[i..i + 4].copy_from_slice(&[v, v, v, 255]);
+            }
+        }
+        RgbaImage::new(width, height, data)
+    }
+
+    #[test]
+    fn test_pipeline_checkerboard() {
+        let img = checker(64, 64, 8);
+        let params = PipelineParams { native_res: true, ..Default::default() };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        let art = res.pixel_art.unwrap();
+        assert_eq!((art.width, art.height), (8, 8));
+        assert_eq!(res.upscale_factor, 1);
+    }
+
+    #[test]
+    fn test_pipeline_quantize_before_upscale() {
+        let img = checker(64, 64, 8);
+        let params = PipelineParams {
+            quantize: true,
+            quantize_method: QuantizeMethod::MedianCut,
+            colors: 2,
+            ..Default::default()
+        };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.palette.len(), 2);
+        let art = res.pixel_art.unwrap();
+        assert_eq!((art.width, art.height), (64, 64));
+        assert_eq!(art.rgb.len(), 64 * 64 * 3);
+    }
+
+    #[test]
+    fn test_pipeline_dither_to_fixed_palette() {
+        let img = checker(64, 64, 8);
+        let params = PipelineParams {
+            native_res: true,
+            custom_palette: vec![[0, 0, 0], [128, 128, 128], [255, 255, 255]],
+            dither: DitherMode::FloydSteinberg,
+            ..Default::default()
+        };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert!(!res.palette.is_empty());
+        let art = res.pixel_art.unwrap();
+        assert!(art.rgb.chunks_exact(3).all(|c| params.custom_palette.contains(&[c[0], c[1], c[2]])));
+    }
+
+    #[test]
+    fn test_pipeline_color_energy_equal_luma_checker() {
+        // 亮度相同的红绿棋盘格：灰度能量检测不到网格，彩色能量可以
+        let (w, h, cell) = (64usize, 64usize, 8usize);
+        let g = ((0.299 * 200.0) / 0.587) as u8;
+        let data: Vec<u8> = (0..w * h)
+            .flat_map(|i| {
+                if ((i % w) / cell + (i / w) / cell).is_multiple_of(2) { [200, 0, 0, 255] } else { [0, g, 0, 255] }
+            })
+            .collect();
+        let img = RgbaImage::new(w, h, data);
+        let params = PipelineParams {
+            energy_channels: EnergyChannels::Oklab,
+            energy_combine: ChannelCombine::DiZenzo,
+            native_res: true,
+            ..Default::default()
+        };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        assert_eq!(res.cells.unwrap().width, 8);
+    }
+
+    #[test]
+    fn test_pipeline_alpha_energy_transparent_sprite() {
+        // 透明背景上的 8px 方块精灵，透明像素中残留噪声 RGB
+        let (w, h, cell) = (64usize, 64usize, 8usize);
+        let data: Vec<u8> = (0..w * h)
+            .flat_map(|i| {
+                let (x, y) = (i % w, i / w);
+                if (x / cell + y / cell).is_multiple_of(2) {
+                    [40, 160, 220, 255]
+                } else {
+                    [(i * 37 % 256) as u8, (i * 91 % 256) as u8, (i * 13 % 256) as u8, 0]
+                }
+            })
+            .collect();
+        let img = RgbaImage::new(w, h, data);
+        let params = PipelineParams {
+            alpha_mode: AlphaMode::Mask,
+            alpha_weight: 1.0,
+            native_res: true,
+            ..Default::default()
+        };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+    }
+
+    #[test]
+    fn test_pipeline_jpeg_aligned_warning() {
+        let img = checker(64, 64, 8);
+        let res = Pipeline::run(&img, &PipelineParams::default()).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        assert_eq!(res.warnings.len(), 1);
+
+        // 偏移 3px 的 8px 网格不与块网格对齐
+        let shifted: Vec<u8> = (0..64 * 64usize)
+            .flat_map(|i| {
+                let (x, y) = (i % 64 + 3, i / 64 + 3);
+                let v = if (x / 8 + y / 8).is_multiple_of(2) { 230 } else { 20 };
+                [v, v, v, 255]
+            })
+            .collect();
+        let shifted = RgbaImage::new(64, 64, shifted);
+        let res = Pipeline::run(&shifted, &PipelineParams::default()).unwrap();
+        assert!(res.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_pipeline_median_denoise() {
+        // 棋盘格上撒椒盐噪点：中值去噪后能量与采样都不受影响
+        let mut img = checker(64, 64, 8);
+        for i in (0..64 * 64).step_by(37) {
+            img.data[i * 4..i * 4 + 3].fill(if i % 2 == 0 { 255 } else { 0 });
+        }
+        let params = PipelineParams {
+            denoise: DenoiseFilter::Median,
+            denoise_radius: 1,
+            denoise_sample: true,
+            sample: true,
+            native_res: true,
+            ..Default::default()
+        };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        let art = res.cells.unwrap();
+        assert!(art.rgb.iter().all(|&v| v == 230 || v == 20));
+    }
+
+    #[test]
+    fn test_pipeline_float_energy_norms() {
+        let img = checker(64, 64, 8);
+        for energy_norm in [EnergyNorm::Percentile, EnergyNorm::MinMax, EnergyNorm::Log] {
+            for float_energy in [false, true] {
+                let params = PipelineParams { energy_norm, float_energy, ..Default::default() };
+                let res = Pipeline::run(&img, &params).unwrap();
+                assert_eq!(res.detected_pixel_size, 8, "{:?} float={}", energy_norm, float_energy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pipeline_structure_tensor_enhance() {
+        let img = checker(64, 64, 8);
+        let params = PipelineParams {
+            enhance_energy: true,
+            enhance_mode: EnhanceMode::StructureTensor,
+            ..Default::default()
+        };
+        assert_eq!(Pipeline::run(&img, &params).unwrap().detected_pixel_size, 8);
+    }
+
+    #[test]
+    fn test_pipeline_energy_projection() {
+        let img = checker(64, 64, 8);
+        let res = Pipeline::run(&img, &PipelineParams::default()).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        assert_eq!((res.energy_x_u8.len(), res.energy_y_u8.len()), (64 * 64, 64 * 64));
+        // 列投影能量图只包含竖直边缘：横向边缘行（非交点处）为 0
+        assert_eq!(res.energy_x_u8[8 * 64 + 4], 0);
+        assert!(res.energy_y_u8[8 * 64 + 4] > 0);
+
+        let params = PipelineParams { energy_projection: EnergyProjection::Combined, ..Default::default() };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        assert!(res.energy_x_u8.is_empty() && res.energy_y_u8.is_empty());
+    }
+
+    #[test]
+    fn test_pipeline_non_square_cells() {
+        // 单元格宽 6、高 10：两轴分别检测，网格线按各轴周期排列
+        let (w, h) = (90, 90);
+        let mut data = vec![0u8; w * h * 4];
+        for y in 0..h {
+            for x in 0..w {
+                let v = if (x / 6 + y / 10).is_multiple_of(2) { 230 } else { 20 };
+                data[(y * w + x) * 4..(y * w + x) * 4 + 4].copy_from_slice(&[v, v, v, 255]);
+            }
+        }
+        let img = RgbaImage::new(w, h, data);
+        let params = PipelineParams { min_s: 4, max_s: 16, native_res: true, ..Default::default() };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!((res.detected_pixel_size_x, res.detected_pixel_size_y), (6, 10));
+        assert!(res.x_lines.windows(2).all(|g| g[1] - g[0] == 6), "{:?}", res.x_lines);
+        assert!(res.y_lines.windows(2).all(|g| g[1] - g[0] == 10), "{:?}", res.y_lines);
+        let art = res.cells.unwrap();
+        assert_eq!((art.width, art.height), (15, 9));
+
+        // square_pixels：两轴强制使用同一像素大小
+        let params = PipelineParams { square_pixels: true, ..params };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size_x, res.detected_pixel_size_y);
+    }
+
+    #[test]
+    fn test_pipeline_subpixel_grid() {
+        // 20 个单元格、每个 12.5px：整数步长会累积漂移
+        let (w, h) = (250, 250);
+        let mut data = vec![0u8; w * h * 4];
+        for y in 0..h {
+            for x in 0..w {
+                let (cx, cy) = ((x as f32 / 12.5) as usize, (y as f32 / 12.5) as usize);
+                let v = if (cx + cy).is_multiple_of(2) { 230 } else { 20 };
+                data[(y * w + x) * 4..(y * w + x) * 4 + 4].copy_from_slice(&[v, v, v, 255]);
+            }
+        }
+        let img = RgbaImage::new(w, h, data);
+        let params =
+            PipelineParams { min_s: 8, max_s: 16, subpixel_grid: true, native_res: true, ..Default::default() };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert!((res.detected_period_x - 12.5).abs() < 0.1, "period {}", res.detected_period_x);
+        // 拟合的浮点网格线：内部等距 12.5px（不取整），两端贴合图像边缘
+        let pos = &res.all_x_positions;
+        assert_eq!(pos.len(), 21);
+        assert_eq!((pos[0], pos[20]), (0.0, 249.0));
+        for (k, &p) in pos.iter().enumerate().take(20).skip(1) {
+            assert!((p - 12.5 * k as f32).abs() < 1.0, "position {} = {}", k, p);
+        }
+        assert!(pos[1..20].windows(2).all(|g| (g[1] - g[0] - 12.5).abs() < 0.05), "{:?}", pos);
+        assert!(pos[1..20].iter().any(|p| p.fract() != 0.0));
+        let rounded: Vec<usize> = pos.iter().map(|p| p.round() as usize).collect();
+        assert_eq!(res.all_x_lines, rounded);
+        let art = res.cells.unwrap();
+        assert_eq!((art.width, art.height), (20, 20));
+        // 每个单元格颜色与棋盘格一致
+        for cy in 0..20 {
+            for cx in 0..20 {
+                let expect = if (cx + cy) % 2 == 0 { 230 } else { 20 };
+                assert_eq!(art.rgb[(cy * 20 + cx) * 3], expect, "cell ({}, {})", cx, cy);
+            }
+        }
+
+        // square_pixels 与 subpixel_grid 同时开启：两轴共用非整数周期
+        let params = PipelineParams { square_pixels: true, ..params };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_period_x, res.detected_period_y);
+        assert!((res.detected_period_x - 12.5).abs() < 0.1, "period {}", res.detected_period_x);
+        assert_eq!(res.all_x_positions.len(), 21);
+        assert!(res.all_x_positions[1..20].windows(2).all(|g| (g[1] - g[0] - 12.5).abs() < 0.05));
+        let art = res.cells.unwrap();
+        assert_eq!((art.width, art.height), (20, 20));
+    }
+
+    #[test]
+    fn test_pipeline_spectral_period() {
+        // 8px 单元格的稀疏精灵：约 1/4 的单元格有颜色，其余为大面积平坦背景
+        let (cell, n) = (8, 20);
+        let mut state = 5u32.wrapping_mul(2654435761);
+        let mut rnd = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            state >> 8
+        };
+        let colors: Vec<u8> =
+            (0..n * n).map(|_| if rnd() % 100 < 25 { (60 + rnd() % 190) as u8 } else { 20 }).collect();
+        let w = cell * n;
+        let mut data = vec![0u8; w * w * 4];
+        for y in 0..w {
+            for x in 0..w {
+                let v = colors[(y / cell) * n + x / cell];
+                data[(y * w + x) * 4..(y * w + x) * 4 + 4].copy_from_slice(&[v, v, v, 255]);
+            }
+        }
+        let img = RgbaImage::new(w, w, data);
+        // 自相关在这类图像上可能落在 2 倍周期上；频谱法应取到基频
+        let params = PipelineParams { period_method: PeriodMethod::Spectral, ..Default::default() };
+        assert_eq!(Pipeline::run(&img, &params).unwrap().detected_pixel_size, 8);
+    }
+
+    #[test]
+    fn test_pipeline_pixel_size_report() {
+        // 4px 单元格，边缘强度交替（100 / 120）：8px 周期得分最高，4px 同样可信
+        let pattern = [20u32, 120, 240, 140];
+        let (w, h) = (96, 96);
+        let mut data = vec![0u8; w * h * 4];
+        for y in 0..h {
+            for x in 0..w {
+                let v = ((pattern[x / 4 % 4] + pattern[y / 4 % 4]) / 2) as u8;
+                data[(y * w + x) * 4..(y * w + x) * 4 + 4].copy_from_slice(&[v, v, v, 255]);
+            }
+        }
+        let img = RgbaImage::new(w, h, data);
+        let params = PipelineParams { max_s: 12, ..Default::default() };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!(res.detected_pixel_size, 8);
+        let report = res.pixel_size_report.unwrap();
+        let sizes: Vec<usize> = report.candidates.iter().map(|c| c.size).collect();
+        assert_eq!(sizes[..2], [8, 4]);
+        assert!(report.candidates.windows(2).all(|c| c[0].score >= c[1].score));
+        assert!(report.multiple && report.confidence < 0.3, "{:?}", report);
+        assert_eq!(report.divisor().map(|c| c.size), Some(4));
+        assert!(res.warnings.iter().any(|w| w.contains("true pixel size 4")), "{:?}", res.warnings);
+
+        // 手动指定像素大小时不检测
+        let params = PipelineParams { pixel_size: 8, ..Default::default() };
+        assert!(Pipeline::run(&img, &params).unwrap().pixel_size_report.is_none());
+    }
+
+    #[test]
+    fn test_pipeline_auto_range() {
+        // 48px 单元格超出固定范围 4..=24：固定范围下检测不到，自动范围（2..=96）可以
+        let img = checker(384, 384, 48);
+        let fixed = Pipeline::run(&img, &PipelineParams { sample: false, ..Default::default() }).unwrap();
+        assert_ne!(fixed.detected_pixel_size, 48);
+        let params = PipelineParams { auto_range: true, native_res: true, ..Default::default() };
+        let res = Pipeline::run(&img, &params).unwrap();
+        assert_eq!((res.detected_pixel_size_x, res.detected_pixel_size_y), (48, 48));
+        let art = res.pixel_art.unwrap();
+        assert_eq!((art.width, art.height), (8, 8));
+    }
+
+    #[test]
+    fn test_pipeline_enhance_wrap_border() {
+        // 可平铺图像循环平移后，wrap 边界下（含方向性增强）的能量图也应随之循环平移
+        let (w, shift) = (64, 3);
+        let img = checker(w, w, 8);
+        let mut rolled = img.data.clone();
+        for y in 0..w {
+            for x in 0..w {
+                let (dst, src) = ((y * w + (x + shift) % w) * 4, (y * w + x) * 4);
+                rolled[dst..dst + 4].copy_from_slice(&img.data[src..src + 4]);
+            }
+        }
+        let rolled = RgbaImage::new(w, w, rolled);
+        let params = PipelineParams {
+            border_mode: Some(BorderMode::Wrap),
+            enhance_energy: true,
+            enhance_directional: true,
+            enhance_vertical: 2.0,
+            sample: false,
+            ..Default::default()
+        };
+        let a = Pipeline::run(&img, &params).unwrap().energy_x_u8;
+        let b = Pipeline::run(&rolled, &params).unwrap().energy_x_u8;
+        for y in 0..w {
+            for x in 0..w {
+                assert_eq!(a[y * w + x], b[y * w + (x + shift) % w], "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_params_from_json() {
+        let params: PipelineParams =
+            serde_json::from_str(r#"{"wasmEnabled":true,"sigma":0.5,"sampleMode":"weighted","minS":2}"#).unwrap();
+        assert_eq!(params.sigma, 0.5);
+        assert_eq!(params.sample_mode, SampleMode::Weighted);
+        assert_eq!(params.min_s, 2);
+        assert_eq!(params.max_s, 24);
+        assert_eq!(params.border_mode, None);
+        let params: PipelineParams = serde_json::from_str(r#"{"borderMode":"wrap"}"#).unwrap();
+        assert_eq!(params.border_mode, Some(BorderMode::Wrap));
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::dist2;
+use crate::error::{check_len, invalid, Result};
+
+/// 颜色量化算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantizeMethod {
+    /// 相似度阈值合并：相似度不低于阈值的颜色合并为一种
+    #[default]
+    Smart,
+    /// 中位切分
+    #[serde(rename = "mediancut")]
+    MedianCut,
+    /// 八叉树
+    Octree,
+    /// k-means（以中位切分结果初始化）
+    #[serde(rename = "kmeans")]
+    KMeans,
+}
+
+/// 颜色量化参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuantizeParams {
+    pub method: QuantizeMethod,
+    /// 目标颜色数；0=自动（smart 模式不限制，其余为 clamp(log2(像素数), 2, 256)）
+    pub colors: usize,
+    /// smart 模式的相似度阈值（0~1，越大越不容易合并）
+    pub similarity_threshold: f32,
+    /// alpha 低于该值的像素视为透明：不参与调色板计算，原样保留
+    pub alpha_threshold: u8,
+}
+
+impl Default for QuantizeParams {
+    fn default() -> Self {
+        Self {
+            method: QuantizeMethod::Smart,
+            colors: 0,
+            similarity_threshold: 0.8,
+            alpha_threshold: 128,
+        }
+    }
+}
+
+/// 量化结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quantized {
+    /// 量化后的 RGBA 数据（alpha 保持不变）
+    pub rgba: Vec<u8>,
+    /// 实际使用的调色板
+    pub palette: Vec<[u8; 3]>,
+}
+
+/// RGB 空间中两种颜色的最大欧氏距离
+const MAX_RGB_DIST: f32 = 441.672_96;
+
+/// 自动颜色数：clamp(log2(像素数), 2, 256)
+pub fn auto_color_count(pixel_count: usize) -> usize {
+    ((pixel_count.max(1) as f64).log2() as usize).clamp(2, 256)
+}
+
+/// 对 RGBA 像素做颜色量化（应在原生分辨率的单元格网格上调用，而不是放大后的图像）
+pub fn quantize_rgba(rgba: &[u8], width: usize, height: usize, params: &QuantizeParams) -> Result<Quantized> {
+    check_len("quantize_rgba rgba", rgba.len(), width * height * 4)?;
+    if !(0.0..=1.0).contains(&params.similarity_threshold) {
+        return Err(invalid(format!(
+            "similarity_threshold must be in [0, 1], got {}",
+            params.similarity_threshold
+        )));
+    }
+
+    let hist = color_histogram(rgba, params.alpha_threshold);
+    let opaque: usize = hist.iter().map(|&(_, n)| n as usize).sum();
+    let target = if params.colors > 0 { params.colors } else { auto_color_count(opaque) };
+
+    let palette = match params.method {
+        QuantizeMethod::Smart => {
+            let cap = if params.colors > 0 { params.colors } else { usize::MAX };
+            similarity_merge(&hist, params.similarity_threshold, cap)
+        }
+        QuantizeMethod::MedianCut => median_cut(&hist, target),
+        QuantizeMethod::Octree => octree(&hist, target),
+        QuantizeMethod::KMeans => kmeans(&hist, target),
+    };
+    let palette = dedup(palette);
+
+    Ok(Quantized { rgba: remap(rgba, &palette, params.alpha_threshold), palette })
+}
+
+/// 统计不透明像素的颜色直方图（按颜色排序，保证结果确定）
+fn color_histogram(rgba: &[u8], alpha_threshold: u8) -> Vec<([u8; 3], u32)> {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for px in rgba.chunks_exact(4) {
+        if px[3] >= alpha_threshold {
+            *counts.entry([px[0], px[1], px[2]]).or_insert(0) += 1;
+        }
+    }
+    let mut hist: Vec<_> = counts.into_iter().collect();
+    hist.sort_unstable();
+    hist
+}
+
+fn to_f32(c: [u8; 3]) -> [f32; 3] {
+    [c[0] as f32, c[1] as f32, c[2] as f32]
+}
+
+fn to_u8(c: [f32; 3]) -> [u8; 3] {
+    c.map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+/// 加权平均色
+fn weighted_mean(colors: &[([u8; 3], u32)]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    let mut total = 0u64;
+    for &(c, n) in colors {
+        for (s, v) in sum.iter_mut().zip(c) {
+            *s += v as u64 * n as u64;
+        }
+        total += n as u64;
+    }
+    let total = total.max(1);
+    sum.map(|s| ((s + total / 2) / total) as u8)
+}
+
+fn dedup(mut palette: Vec<[u8; 3]>) -> Vec<[u8; 3]> {
+    let mut seen = std::collections::HashSet::new();
+    palette.retain(|c| seen.insert(*c));
+    palette
+}
+
+/// 将每个不透明像素替换为调色板中最近的颜色
+fn remap(rgba: &[u8], palette: &[[u8; 3]], alpha_threshold: u8) -> Vec<u8> {
+    let mut out = rgba.to_vec();
+    if palette.is_empty() {
+        return out;
+    }
+    let mut cache: HashMap<[u8; 3], [u8; 3]> = HashMap::new();
+    for px in out.chunks_exact_mut(4) {
+        if px[3] < alpha_threshold {
+            continue;
+        }
+        let c = [px[0], px[1], px[2]];
+        let mapped = *cache.entry(c).or_insert_with(|| nearest(to_f32(c), palette));
+        px[..3].copy_from_slice(&mapped);
+    }
+    out
+}
+
+fn nearest(c: [f32; 3], palette: &[[u8; 3]]) -> [u8; 3] {
+    palette[nearest_index(c, palette.iter().map(|&p| to_f32(p)))]
+}
+
+fn nearest_index(c: [f32; 3], centers: impl Iterator<Item = [f32; 3]>) -> usize {
+    centers
+        .map(|p| dist2(c, p))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// 相似度阈值合并：按出现频率从高到低，相似度 >= 阈值的颜色并入已有簇；
+/// 若指定了颜色上限，再反复合并最近的两簇直到满足上限
+fn similarity_merge(hist: &[([u8; 3], u32)], threshold: f32, max_colors: usize) -> Vec<[u8; 3]> {
+    let max_dist = (1.0 - threshold) * MAX_RGB_DIST;
+    let max_dist2 = max_dist * max_dist;
+
+    let mut sorted = hist.to_vec();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    // (代表色, 加权和, 权重)
+    let mut clusters: Vec<([f32; 3], [f64; 3], f64)> = Vec::new();
+    for (c, n) in sorted {
+        let cf = to_f32(c);
+        let hit = clusters
+            .iter()
+            .enumerate()
+            .map(|(i, cl)| (i, dist2(cf, cl.0)))
+            .filter(|&(_, d)| d <= max_dist2)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let w = n as f64;
+        match hit {
+            Some((i, _)) => {
+                let cl = &mut clusters[i];
+                for (acc, v) in cl.1.iter_mut().zip(cf) {
+                    *acc += v as f64 * w;
+                }
+                cl.2 += w;
+                cl.0 = cl.1.map(|s| (s / cl.2) as f32);
+            }
+            None => clusters.push((cf, cf.map(|v| v as f64 * w), w)),
+        }
+    }
+
+    while clusters.len() > max_colors.max(1) {
+        let mut best = (0, 1, f32::INFINITY);
+        for i in 0..clusters.len() {
+            for j in i + 1..clusters.len() {
+                let d = dist2(clusters[i].0, clusters[j].0);
+                if d < best.2 {
+                    best = (i, j, d);
+                }
+            }
+        }
+        let (i, j, _) = best;
+        let b = clusters.swap_remove(j);
+        let a = &mut clusters[i];
+        for (acc, v) in a.1.iter_mut().zip(b.1) {
+            *acc += v;
+        }
+        a.2 += b.2;
+        a.0 = a.1.map(|s| (s / a.2) as f32);
+    }
+
+    clusters.into_iter().map(|cl| to_u8(cl.0)).collect()
+}
+
+/// 中位切分：反复沿跨度最大的通道在加权中位数处切分
+fn median_cut(hist: &[([u8; 3], u32)], k: usize) -> Vec<[u8; 3]> {
+    if hist.is_empty() {
+        return Vec::new();
+    }
+
+    fn widest(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+        (0..3)
+            .map(|ch| {
+                let (lo, hi) = colors
+                    .iter()
+                    .fold((255u8, 0u8), |(lo, hi), (c, _)| (lo.min(c[ch]), hi.max(c[ch])));
+                (ch, hi - lo)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap()
+    }
+
+    let mut boxes: Vec<Vec<([u8; 3], u32)>> = vec![hist.to_vec()];
+    while boxes.len() < k {
+        // 选择跨度最大的可切分盒子
+        let pick = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, widest(b).1))
+            .max_by_key(|&(_, range)| range);
+        let Some((i, _)) = pick else { break };
+
+        let mut b = boxes.swap_remove(i);
+        let (ch, _) = widest(&b);
+        b.sort_by_key(|(c, _)| c[ch]);
+        let total: u64 = b.iter().map(|&(_, n)| n as u64).sum();
+        let mut acc = 0u64;
+        let mut split = 1;
+        for (idx, &(_, n)) in b.iter().enumerate() {
+            acc += n as u64;
+            if acc * 2 >= total {
+                split = idx + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, b.len() - 1);
+        let rest = b.split_off(split);
+        boxes.push(b);
+        boxes.push(rest);
+    }
+
+    boxes.iter().map(|b| weighted_mean(b)).collect()
+}
+
+/// 八叉树量化：插入全部颜色后，从最深层开始合并像素数最少的节点
+fn octree(hist: &[([u8; 3], u32)], k: usize) -> Vec<[u8; 3]> {
+    const MAX_DEPTH: usize = 8;
+
+    #[derive(Default)]
+    struct Node {
+        children: [Option<usize>; 8],
+        sum: [u64; 3],
+        count: u64,
+        leaf: bool,
+    }
+
+    let mut nodes = vec![Node::default()];
+    let mut reducible: Vec<Vec<usize>> = vec![Vec::new(); MAX_DEPTH];
+    reducible[0].push(0);
+    let mut leaves = 0usize;
+
+    for &(c, n) in hist {
+        let mut idx = 0;
+        for depth in 0..MAX_DEPTH {
+            let shift = 7 - depth;
+            let octant = (((c[0] >> shift) & 1) << 2 | ((c[1] >> shift) & 1) << 1 | ((c[2] >> shift) & 1)) as usize;
+            idx = match nodes[idx].children[octant] {
+                Some(child) => child,
+                None => {
+                    let child = nodes.len();
+                    nodes.push(Node { leaf: depth + 1 == MAX_DEPTH, ..Node::default() });
+                    nodes[idx].children[octant] = Some(child);
+                    if depth + 1 < MAX_DEPTH {
+                        reducible[depth + 1].push(child);
+                    } else {
+                        leaves += 1;
+                    }
+                    child
+                }
+            };
+        }
+        let leaf = &mut nodes[idx];
+        for (s, v) in leaf.sum.iter_mut().zip(c) {
+            *s += v as u64 * n as u64;
+        }
+        leaf.count += n as u64;
+    }
+
+    // 子树像素总数（用于挑选最小节点）
+    fn subtree_count(nodes: &[Node], idx: usize) -> u64 {
+        let node = &nodes[idx];
+        node.count + node.children.iter().flatten().map(|&c| subtree_count(nodes, c)).sum::<u64>()
+    }
+
+    while leaves > k.max(1) {
+        let Some(level) = reducible.iter().rposition(|l| !l.is_empty()) else { break };
+        let (pos, _) = reducible[level]
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &idx)| subtree_count(&nodes, idx))
+            .unwrap();
+        let idx = reducible[level].swap_remove(pos);
+
+        let children: Vec<usize> = nodes[idx].children.iter().flatten().copied().collect();
+        for &child in &children {
+            let (sum, count) = (nodes[child].sum, nodes[child].count);
+            let node = &mut nodes[idx];
+            for (s, v) in node.sum.iter_mut().zip(sum) {
+                *s += v;
+            }
+            node.count += count;
+        }
+        nodes[idx].children = [None; 8];
+        nodes[idx].leaf = true;
+        leaves = leaves + 1 - children.len();
+    }
+
+    let mut palette = Vec::new();
+    let mut stack = vec![0];
+    while let Some(idx) = stack.pop() {
+        let node = &nodes[idx];
+        if node.leaf {
+            if node.count > 0 {
+                palette.push(node.sum.map(|s| ((s + node.count / 2) / node.count) as u8));
+            }
+        } else {
+            stack.extend(node.children.iter().flatten().rev());
+        }
+    }
+    palette
+}
+
+/// k-means：以中位切分结果为初始中心，加权 Lloyd 迭代
+fn kmeans(hist: &[([u8; 3], u32)], k: usize) -> Vec<[u8; 3]> {
+    const MAX_ITERS: usize = 20;
+
+    let mut centers: Vec<[f32; 3]> = median_cut(hist, k).into_iter().map(to_f32).collect();
+    if centers.is_empty() {
+        return Vec::new();
+    }
+    let mut assign = vec![usize::MAX; hist.len()];
+
+    for _ in 0..MAX_ITERS {
+        let mut changed = false;
+        for (a, &(c, _)) in assign.iter_mut().zip(hist) {
+            let best = nearest_index(to_f32(c), centers.iter().copied());
+            if *a != best {
+                *a = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![([0.0f64; 3], 0.0f64); centers.len()];
+        for (&a, &(c, n)) in assign.iter().zip(hist) {
+            let s = &mut sums[a];
+            for (acc, v) in s.0.iter_mut().zip(c) {
+                *acc += v as f64 * n as f64;
+            }
+            s.1 += n as f64;
+        }
+        // 空簇保持原中心
+        for (center, (sum, w)) in centers.iter_mut().zip(sums) {
+            if w > 0.0 {
+                *center = sum.map(|s| (s / w) as f32);
+            }
+        }
+    }
+
+    centers.into_iter().map(to_u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 两组相近颜色 + 一个透明像素
+    fn sample_rgba() -> Vec<u8> {
+        vec![
+            250, 10, 10, 255, 245, 5, 12, 255, 252, 8, 0, 255, //
+            10, 10, 240, 255, 12, 4, 250, 255, 0, 255, 0, 0,
+        ]
+    }
+
+    #[test]
+    fn test_quantize_all_methods_two_colors() {
+        for method in [QuantizeMethod::Smart, QuantizeMethod::MedianCut, QuantizeMethod::Octree, QuantizeMethod::KMeans] {
+            let params = QuantizeParams { method, colors: 2, ..Default::default() };
+            let q = quantize_rgba(&sample_rgba(), 3, 2, &params).unwrap();
+            assert_eq!(q.palette.len(), 2, "{:?}", method);
+            // 前三个像素同色、接下来两个同色
+            assert_eq!(q.rgba[0..4], q.rgba[4..8], "{:?}", method);
+            assert_eq!(q.rgba[12..16], q.rgba[16..20], "{:?}", method);
+            assert_ne!(q.rgba[0..3], q.rgba[12..15], "{:?}", method);
+            // 透明像素保持不变
+            assert_eq!(q.rgba[20..24], [0, 255, 0, 0], "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn test_smart_threshold() {
+        // 阈值 1.0 不合并任何颜色
+        let params = QuantizeParams { similarity_threshold: 1.0, ..Default::default() };
+        assert_eq!(quantize_rgba(&sample_rgba(), 3, 2, &params).unwrap().palette.len(), 5);
+        let params = QuantizeParams { similarity_threshold: 0.9, ..Default::default() };
+        assert_eq!(quantize_rgba(&sample_rgba(), 3, 2, &params).unwrap().palette.len(), 2);
+    }
+
+    #[test]
+    fn test_quantize_invalid() {
+        assert!(quantize_rgba(&[0u8; 6], 1, 1, &QuantizeParams::default()).is_err());
+        let params = QuantizeParams { similarity_threshold: 1.5, ..Default::default() };
+        assert!(quantize_rgba(&[0u8; 4], 1, 1, &params).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::f32::consts::PI;
+
+/// 谐波得分使用的最大谐波数
+const MAX_HARMONICS: usize = 8;
+/// 与最佳得分之比不低于该值的峰中取频率最低者（即周期最大者），避免选中基频的倍频
+const FUNDAMENTAL_RATIO: f32 = 0.75;
+/// 候选基频自身的功率须不低于其 2 / 3 次谐波功率的该比例，否则视为真实周期的 2 倍 / 3 倍（次谐波）
+const SUBHARMONIC_RATIO: f32 = 0.25;
+
+/// 原地基 2 FFT（长度须为 2 的幂）
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // 位反转重排
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let ang = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wr, wi) = ((ang * k as f32).cos(), (ang * k as f32).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let (xr, xi) = (re[b] * wr - im[b] * wi, re[b] * wi + im[b] * wr);
+                re[b] = re[a] - xr;
+                im[b] = im[a] - xi;
+                re[a] += xr;
+                im[a] += xi;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// 周期图：加 Hann 窗并补零到不小于 pad * len 的 2 的幂长度 N
+/// 返回 (功率谱 0..=N/2, N)；bin k 对应周期 N / k
+pub fn periodogram(x: &[f32], pad: usize) -> (Vec<f32>, usize) {
+    let n = (x.len() * pad.max(1)).max(2).next_power_of_two();
+    let mut re = vec![0.0f32; n];
+    let mut im = vec![0.0f32; n];
+    let m = x.len();
+    for (i, (r, &v)) in re.iter_mut().zip(x).enumerate() {
+        let w = if m > 1 { 0.5 - 0.5 * (2.0 * PI * i as f32 / (m - 1) as f32).cos() } else { 1.0 };
+        *r = v * w;
+    }
+    fft(&mut re, &mut im);
+    let power = re.iter().zip(&im).take(n / 2 + 1).map(|(r, i)| r * r + i * i).collect();
+    (power, n)
+}
+
+/// 线性插值读取分数 bin 处的功率
+fn power_at(power: &[f32], k: f32) -> f32 {
+    let i = k.floor() as usize;
+    if i + 1 >= power.len() {
+        return power.last().copied().unwrap_or(0.0);
+    }
+    let t = k - i as f32;
+    power[i] * (1.0 - t) + power[i + 1] * t
+}
+
+/// 谐波均值得分：基频 bin k 及其 2..=H 次谐波的平均功率 / 全谱平均功率 - 1
+///
+/// 取均值而非求和：周期为真实周期 2 倍 / 3 倍的候选缺少奇数（或非 3 倍数）次谐波，得分被拉低。
+fn harmonic_score(power: &[f32], k: f32, mean: f32) -> f32 {
+    let nyquist = (power.len() - 1) as f32;
+    let h_max = ((nyquist / k).floor() as usize).clamp(1, MAX_HARMONICS);
+    let sum: f32 = (1..=h_max).map(|h| power_at(power, k * h as f32)).sum();
+    sum / h_max as f32 / (mean + 1e-12) - 1.0
+}
+
+/// 去趋势投影的谐波得分频谱
+struct Spectrum {
+    power: Vec<f32>,
+    n: usize,
+    /// 原序列长度
+    len: usize,
+    mean: f32,
+}
+
+impl Spectrum {
+    fn new(x: &[f32]) -> Option<Self> {
+        if x.len() < 4 {
+            return None;
+        }
+        let (power, n) = periodogram(x, 4);
+        let mean = power.iter().skip(1).sum::<f32>() / (power.len() - 1) as f32;
+        (mean > 0.0).then_some(Self { power, n, len: x.len(), mean })
+    }
+
+    /// 周期 period 处的谐波得分
+    fn score(&self, period: f32) -> f32 {
+        harmonic_score(&self.power, self.n as f32 / period, self.mean)
+    }
+
+    /// 周期 period 的谐波（含 Hann 主瓣宽度）所占功率比例，扣除同样数量的 bin 随机占有的比例后归一化到 ≤ 1
+    ///
+    /// 与谐波均值得分不同，真实周期的约数只覆盖部分谐波、得分较低，而整数倍周期与真实周期得分相近（同自相关）。
+    fn explained(&self, period: f32) -> f32 {
+        let nyquist = self.power.len() - 1;
+        let k = self.n as f32 / period;
+        let lobe = 2 * self.n / self.len.max(1);
+        let mut mask = vec![false; nyquist + 1];
+        let mut h = 1.0f32;
+        while h * k <= nyquist as f32 {
+            let c = (h * k).round() as usize;
+            for m in &mut mask[c.saturating_sub(lobe).max(1)..=(c + lobe).min(nyquist)] {
+                *m = true;
+            }
+            h += 1.0;
+        }
+        let total: f32 = self.power[1..].iter().sum();
+        let hit: f32 = self.power.iter().zip(&mask).filter(|(_, &m)| m).map(|(p, _)| p).sum();
+        let base = mask.iter().filter(|&&m| m).count() as f32 / nyquist as f32;
+        if base >= 1.0 || total <= 0.0 {
+            return 0.0;
+        }
+        (hit / total - base) / (1.0 - base)
+    }
+
+    /// 周期 period 处基频功率（相对全谱平均）与其 2 / 3 次谐波中较大者；
+    /// 前者低于后者的 SUBHARMONIC_RATIO 时该周期是真实周期的整数倍
+    fn fundamental(&self, period: f32) -> (f32, f32) {
+        let k = self.n as f32 / period;
+        let at = |h: f32| if k * h < (self.power.len() - 1) as f32 { power_at(&self.power, k * h) } else { 0.0 };
+        (at(1.0) / self.mean, at(2.0).max(at(3.0)) / self.mean)
+    }
+}
+
+/// 频谱周期检测（谐波抑制）
+///
+/// 在 [min_s, max_s] 对应的频率 bin 上计算谐波得分，取得分不低于最佳值 3/4、且基频功率存在的局部峰中
+/// 周期最大者，再做抛物线插值得到亚像素周期。返回 (周期, 得分)；序列过短或无周期结构时返回 None。
+pub fn spectral_period(x: &[f32], min_s: usize, max_s: usize) -> Option<(f32, f32)> {
+    let spec = Spectrum::new(x)?;
+    let n = spec.n as f32;
+    let k_lo = ((n / max_s as f32).floor() as usize).max(1);
+    let k_hi = ((n / min_s.max(2) as f32).ceil() as usize).min(spec.power.len() - 1);
+    if k_lo + 2 > k_hi {
+        return None;
+    }
+
+    let scores: Vec<f32> = (k_lo..=k_hi).map(|k| harmonic_score(&spec.power, k as f32, spec.mean)).collect();
+    let best = scores.iter().copied().fold(f32::MIN, f32::max);
+    if best <= 0.0 {
+        return None;
+    }
+
+    // 局部峰（含区间端点）中频率最低且足够强者
+    let is_peak = |i: usize| {
+        (i == 0 || scores[i] >= scores[i - 1]) && (i + 1 == scores.len() || scores[i] >= scores[i + 1])
+    };
+    let fundamental = |i: usize| {
+        let (f1, f2) = spec.fundamental(n / (k_lo + i) as f32);
+        f1 >= SUBHARMONIC_RATIO * f2
+    };
+    let i = (0..scores.len()).find(|&i| scores[i] >= best * FUNDAMENTAL_RATIO && is_peak(i) && fundamental(i))?;
+
+    let mut k = (k_lo + i) as f32;
+    if i > 0 && i + 1 < scores.len() {
+        let (a, b, c) = (scores[i - 1], scores[i], scores[i + 1]);
+        let denom = a - 2.0 * b + c;
+        if denom < 0.0 {
+            k += (0.5 * (a - c) / denom).clamp(-0.5, 0.5);
+        }
+    }
+    let period = (n / k).clamp(min_s as f32, max_s as f32);
+    Some((period, scores[i]))
+}
+
+/// 整数周期 min_s..=max_s 的谐波功率占比得分（≤ 1）；序列过短或无能量时全为 0
+pub(crate) fn spectral_scores(x: &[f32], min_s: usize, max_s: usize) -> Vec<f32> {
+    let spec = Spectrum::new(x);
+    (min_s..=max_s).map(|s| spec.as_ref().map_or(0.0, |sp| sp.explained(s as f32))).collect()
+}
+
+/// 两轴整数周期 min_s..=max_s 的谐波得分之和最大者
+pub(crate) fn spectral_combined(px: &[f32], py: &[f32], min_s: usize, max_s: usize) -> Option<usize> {
+    let (sx, sy) = (Spectrum::new(px), Spectrum::new(py));
+    if sx.is_none() && sy.is_none() {
+        return None;
+    }
+    let score = |spec: &Option<Spectrum>, s: usize| spec.as_ref().map_or(0.0, |sp| sp.score(s as f32));
+    let scores: Vec<f32> = (min_s..=max_s).map(|s| score(&sx, s) + score(&sy, s)).collect();
+    let best = scores.iter().copied().fold(f32::MIN, f32::max);
+    if best <= 0.0 {
+        return None;
+    }
+    // 与单轴相同的谐波抑制：足够强、基频功率存在的局部峰中取周期最大者
+    let is_peak = |i: usize| {
+        (i == 0 || scores[i] >= scores[i - 1]) && (i + 1 == scores.len() || scores[i] >= scores[i + 1])
+    };
+    let fundamental = |s: usize| {
+        let f = |spec: &Option<Spectrum>| spec.as_ref().map_or((0.0, 0.0), |sp| sp.fundamental(s as f32));
+        let ((x1, x2), (y1, y2)) = (f(&sx), f(&sy));
+        x1 + y1 >= SUBHARMONIC_RATIO * (x2 + y2)
+    };
+    (0..scores.len())
+        .rev()
+        .find(|&i| scores[i] >= best * FUNDAMENTAL_RATIO && is_peak(i) && fundamental(min_s + i))
+        .map(|i| min_s + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 周期 period 的脉冲序列（线宽 2）
+    fn pulses(len: usize, period: f32) -> Vec<f32> {
+        let mut x = vec![0.0f32; len];
+        let mut k = 0.0f32;
+        while (k * period) < len as f32 {
+            let i = (k * period).round() as usize;
+            x[i.min(len - 1)] = 1.0;
+            x[(i + 1).min(len - 1)] = 0.5;
+            k += 1.0;
+        }
+        let mean = x.iter().sum::<f32>() / len as f32;
+        x.iter().map(|v| v - mean).collect()
+    }
+
+    #[test]
+    fn test_fft_matches_dft() {
+        let x: Vec<f32> = (0..8).map(|i| (i as f32 * 0.7).sin() + 0.3 * i as f32).collect();
+        let (mut re, mut im) = (x.clone(), vec![0.0f32; 8]);
+        fft(&mut re, &mut im);
+        for k in 0..8 {
+            let (mut dr, mut di) = (0.0f32, 0.0f32);
+            for (t, &v) in x.iter().enumerate() {
+                let a = -2.0 * PI * (k * t) as f32 / 8.0;
+                dr += v * a.cos();
+                di += v * a.sin();
+            }
+            assert!((re[k] - dr).abs() < 1e-4 && (im[k] - di).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_spectral_period_fundamental() {
+        let (p, score) = spectral_period(&pulses(512, 8.0), 2, 64).unwrap();
+        assert!((p - 8.0).abs() < 0.1, "period {}", p);
+        assert!(score > 0.0);
+        // 非整数周期
+        let (p, _) = spectral_period(&pulses(1000, 15.625), 4, 200).unwrap();
+        assert!((p - 15.625).abs() < 0.1, "period {}", p);
+        // 无周期结构
+        assert!(spectral_period(&[0.0; 64], 2, 16).is_none());
+        // 大面积平坦区域：不应选中 2 倍 / 3 倍周期
+        let mut x = pulses(600, 6.0);
+        x[150..450].fill(0.0);
+        let (p, _) = spectral_period(&x, 2, 40).unwrap();
+        assert!((p - 6.0).abs() < 0.2, "period {}", p);
+    }
+}
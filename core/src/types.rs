@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+/// RGBA 8 位图像，长度 = width * height * 4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: usize, height: usize, data: Vec<u8>) -> Self {
+        Self { width, height, data }
+    }
+}
+
+/// 0-1 范围的单通道灰度图
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl GrayImage {
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        Self { width, height, data }
+    }
+}
+
+/// 梯度能量图
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyMap {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl EnergyMap {
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        Self { width, height, data }
+    }
+}
+
+/// 检测到的网格线（x 为竖线位置，y 为横线位置）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GridLines {
+    pub x_lines: Vec<usize>,
+    pub y_lines: Vec<usize>,
+}
+
+/// 检测到的像素大小：x / y 为各轴独立的周期，combined 为两轴得分之和最大的周期
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PixelSize {
+    pub x: usize,
+    pub y: usize,
+    pub combined: usize,
+    /// x / y 轴的亚像素周期（例如 1000px 上 64 个单元格为 15.625）
+    pub period_x: f32,
+    pub period_y: f32,
+}
+
+impl PixelSize {
+    /// 两轴相同的像素大小
+    pub fn uniform(size: usize) -> Self {
+        Self { x: size, y: size, combined: size, period_x: size as f32, period_y: size as f32 }
+    }
+
+    /// 两轴统一为 combined 像素大小，保留共同的亚像素周期（取与 combined 一致的轴周期的平均值）
+    pub fn square(&self) -> Self {
+        let periods: Vec<f32> = [(self.x, self.period_x), (self.y, self.period_y)]
+            .into_iter()
+            .filter(|&(s, _)| s == self.combined)
+            .map(|(_, p)| p)
+            .collect();
+        let period =
+            if periods.is_empty() { self.combined as f32 } else { periods.iter().sum::<f32>() / periods.len() as f32 };
+        Self { period_x: period, period_y: period, ..Self::uniform(self.combined) }
+    }
+}
+
+/// 采样得到的像素画
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelArt {
+    pub width: usize,
+    pub height: usize,
+    /// RGB 数据，长度 = width * height * 3
+    pub rgb: Vec<u8>,
+    /// RGBA 数据，长度 = width * height * 4
+    pub rgba: Vec<u8>,
+}
+
+impl PixelArt {
+    /// 由 RGBA 数据构造（RGB 数据由 RGBA 去掉 alpha 得到）
+    pub fn from_rgba(width: usize, height: usize, rgba: Vec<u8>) -> Self {
+        let rgb = rgba.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+        Self { width, height, rgb, rgba }
+    }
+}
+
+/// 像素采样模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleMode {
+    /// 按比例直接插值采样（不依赖网格）
+    Direct,
+    /// 单元格中心点
+    #[default]
+    Center,
+    /// 单元格平均
+    Average,
+    /// 中心区域加权平均
+    Weighted,
+}
+
+impl SampleMode {
+    /// 与 WASM 接口约定的数值编码: 0=direct, 1=center, 2=average, 3=weighted
+    pub fn from_u32(mode: u32) -> Self {
+        match mode {
+            0 => SampleMode::Direct,
+            1 => SampleMode::Center,
+            2 => SampleMode::Average,
+            _ => SampleMode::Weighted,
+        }
+    }
+}
@@ -17,6 +17,7 @@ import {
   completeEdges as completeEdgesWasm,
   samplePixelArt as samplePixelArtWasm,
   samplePixelArtDirect as samplePixelArtDirectWasm,
+  runPipeline as runPipelineWasm,
 } from "./wasmCompat";
 
 // JavaScript 回退实现
@@ -79,6 +80,18 @@ export async function runPipeline(
   // 转换为 Uint8Array（WASM 可能不兼容 Uint8ClampedArray）
   const rgba = new Uint8Array(img.rgba);
 
+  // WASM 提供完整流程时一次调用完成（与原生 img2pic-core 结果一致）
+  if (params.wasmEnabled) {
+    const wasmResult = await runPipelineWasm(rgba, width, height, params);
+    if (wasmResult) {
+      console.log(`${contextLabel}: pipeline finished in WASM`, {
+        detectedPixelSize: wasmResult.detectedPixelSize,
+        hasPixelArt: !!wasmResult.pixelArt
+      });
+      return wasmResult;
+    }
+  }
+
   let energyU8: Uint8Array;
   let pixelSize = 0;
   let xLines: number[] = [];
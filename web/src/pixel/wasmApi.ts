@@ -3,7 +3,7 @@
  * 可选使用 WASM 加速图像计算
  */
 
-import type { SampleMode } from "./types";
+import type { PipelineParams, PipelineResult, SampleMode } from "./types";
 
 // WASM 模块接口定义（匹配实际 WASM 生成的类型）
 export interface WasmModule {
@@ -66,6 +66,8 @@ export interface WasmModule {
     upscale_factor: number,
     native_res: boolean
   ): { outW: number; outH: number; outRgb: Uint8Array; outRgba?: Uint8Array };
+  // 完整流程（一次调用），旧版 WASM 构建中可能不存在
+  run_pipeline?(rgba: Uint8Array, width: number, height: number, params: PipelineParams): PipelineResult;
 }
 
 // WASM 加载状态
@@ -10,7 +10,7 @@ import * as jsImpl from "./filters";
 import * as jsEnergy from "./energy";
 import * as jsGrid from "./grid";
 import { ensureWasmLoaded, sampleModeToWasm, isWasmEnabled } from "./wasmApi";
-import type { SampleMode } from "./types";
+import type { PipelineParams, PipelineResult, SampleMode } from "./types";
 import type { WasmModule } from "./wasmApi";
 
 /**
@@ -318,3 +318,26 @@ export async function samplePixelArt(
   console.log('[Render] Using JS engine for samplePixelArt');
   return jsGrid.samplePixelArt(rgb, width, height, allX, allY, mode, weightRatio, upscaleFactor, nativeRes);
 }
+
+/**
+ * 完整处理流程 - WASM 版本（单次调用）
+ * WASM 未启用、不可用或调用失败时返回 null，由调用方回退到逐步流程
+ */
+export async function runPipeline(
+  rgba: Uint8Array,
+  width: number,
+  height: number,
+  params: PipelineParams
+): Promise<PipelineResult | null> {
+  const wasm = await getWasmIfEnabled();
+  if (!wasm || typeof wasm.run_pipeline !== 'function') {
+    return null;
+  }
+  try {
+    console.log('[Render] Using WASM engine for runPipeline');
+    return wasm.run_pipeline(rgba, width, height, params);
+  } catch (err) {
+    console.warn('[Render] WASM runPipeline failed, falling back to step-by-step pipeline:', err);
+    return null;
+  }
+}
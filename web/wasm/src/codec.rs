@@ -0,0 +1,19 @@
+use wasm_bindgen::prelude::*;
+
+/// 解码图像文件字节（PNG / JPEG / WebP / BMP / GIF）
+/// 返回 {width, height, rgba}
+#[wasm_bindgen]
+pub fn decode_image(bytes: &[u8]) -> Result<JsValue, JsError> {
+    let img = img2pic_core::decode_image(bytes)?;
+    let result = js_sys::Object::new();
+    js_sys::Reflect::set(&result, &"width".into(), &JsValue::from(img.width as u32)).unwrap();
+    js_sys::Reflect::set(&result, &"height".into(), &JsValue::from(img.height as u32)).unwrap();
+    js_sys::Reflect::set(&result, &"rgba".into(), &js_sys::Uint8Array::from(&img.data[..])).unwrap();
+    Ok(JsValue::from(result))
+}
+
+/// 将 RGBA 数据编码为 PNG（保留透明通道）
+#[wasm_bindgen]
+pub fn encode_png(rgba: &[u8], width: usize, height: usize) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::encode_png(rgba, width, height)?)
+}
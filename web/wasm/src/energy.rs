@@ -1,98 +1,126 @@
 use wasm_bindgen::prelude::*;
-use crate::filters::{gaussian_kernel_1d, convolve_separable, sobel};
+use img2pic_core::{
+    AlphaMode, ChannelCombine, ColorSpace, EnergyMap, GradientNorm, GradientOperator, GradientParams, GrayImage,
+    Img2PicError, NormalizeParams,
+};
+use crate::filters::parse_border;
 
 /// 近似分位数计算（采样避免全排序）
 #[wasm_bindgen]
-pub fn quantile_approx(x: &[f32], q: f64) -> f32 {
-    if x.is_empty() {
-        return 0.0;
-    }
-
-    let n = x.len();
-    let sample_max = 200_000_usize;
-    let step = (n / sample_max).max(1);
+pub fn quantile_approx(x: &[f32], q: f64) -> Result<f32, JsError> {
+    Ok(img2pic_core::quantile_approx(x, q)?)
+}
 
-    let mut sample: Vec<f32> = x.iter().step_by(step).copied().collect();
-    sample.sort_by(|a, b| a.partial_cmp(b).unwrap());
+/// 精确分位数（NaN 被忽略）
+#[wasm_bindgen]
+pub fn quantile_exact(x: &[f32], q: f64) -> Result<f32, JsError> {
+    Ok(img2pic_core::quantile_exact(x, q)?)
+}
 
-    let idx = ((q * (sample.len() - 1) as f64).floor() as usize)
-        .max(0)
-        .min(sample.len() - 1);
+/// 一次计算多个精确分位数
+#[wasm_bindgen]
+pub fn quantiles(x: &[f32], qs: &[f64]) -> Result<Vec<f32>, JsError> {
+    Ok(img2pic_core::quantiles(x, qs)?)
+}
 
-    sample[idx]
+/// 8 位数据的分位数（直方图）
+#[wasm_bindgen]
+pub fn quantiles_u8(x: &[u8], qs: &[f64]) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::quantiles_u8(x, qs)?)
 }
 
 /// RGBA 转 0-1 范围的灰度图
-/// 使用标准亮度系数: 0.299*R + 0.587*G + 0.114*B
-#[wasm_bindgen]
-pub fn rgba_to_gray01(rgba: &[u8], width: usize, height: usize) -> Vec<f32> {
-    // 调试：记录接收到的参数
-    web_sys::console::log_1(
-        &format!("rgba_to_gray01 received: rgba.len()={}, width={}, height={}", rgba.len(), width, height).into()
-    );
-
-    let pixel_count = width * height;
-    let expected_rgba_len = pixel_count * 4;
-
-    // 验证输入数组长度
-    if rgba.len() != expected_rgba_len {
-        web_sys::console::error_1(
-            &format!("rgba_to_gray01: expected rgba length {}, got {}", expected_rgba_len, rgba.len()).into()
-        );
-        // 返回空数组
-        return vec![0.0f32; pixel_count];
-    }
+#[wasm_bindgen]
+pub fn rgba_to_gray01(rgba: &[u8], width: usize, height: usize) -> Result<Vec<f32>, JsError> {
+    Ok(img2pic_core::rgba_to_gray01(rgba, width, height)?.data)
+}
 
-    let mut gray = vec![0.0f32; pixel_count];
+/// 计算梯度能量图
+#[wasm_bindgen]
+pub fn grad_energy(gray01: &[f32], width: usize, height: usize, sigma: f64) -> Result<Vec<f32>, JsError> {
+    let gray = GrayImage::new(width, height, gray01.to_vec());
+    Ok(img2pic_core::grad_energy(&gray, sigma)?.data)
+}
 
-    for (p, pixel) in gray.iter_mut().enumerate() {
-        let i = p * 4;
-        let r = rgba[i] as f32 / 255.0;
-        let g = rgba[i + 1] as f32 / 255.0;
-        let b = rgba[i + 2] as f32 / 255.0;
-        // 标准亮度系数 (Rec. 601)
-        *pixel = 0.299 * r + 0.587 * g + 0.114 * b;
-    }
+/// 解析梯度算子与范数；op 见 filters::gradient，norm 为 "l1" | "l2" | "max"
+fn parse_grad(op: &str, norm: &str) -> Result<GradientParams, Img2PicError> {
+    let operator: GradientOperator = serde_json::from_value(serde_json::Value::from(op))
+        .map_err(|e| Img2PicError::Decode(format!("gradient operator: {}", e)))?;
+    let norm: GradientNorm = serde_json::from_value(serde_json::Value::from(norm))
+        .map_err(|e| Img2PicError::Decode(format!("gradient norm: {}", e)))?;
+    Ok(GradientParams { operator, norm, ..GradientParams::default() })
+}
 
-    gray
+/// 使用指定梯度算子与范数计算梯度能量图
+#[wasm_bindgen]
+pub fn grad_energy_with(
+    gray01: &[f32],
+    width: usize,
+    height: usize,
+    sigma: f64,
+    op: &str,
+    norm: &str,
+) -> Result<Vec<f32>, JsError> {
+    let gray = GrayImage::new(width, height, gray01.to_vec());
+    Ok(img2pic_core::grad_energy_with(&gray, sigma, parse_grad(op, norm)?)?.data)
 }
 
-/// 计算梯度能量图
-/// 先用高斯模糊（可选），再用 Sobel 算子计算梯度
-#[wasm_bindgen]
-pub fn grad_energy(gray01: &[f32], width: usize, height: usize, sigma: f64) -> Vec<f32> {
-    let g = if sigma > 0.0 {
-        let k = gaussian_kernel_1d(sigma);
-        convolve_separable(gray01, width, height, &k)
-    } else {
-        gray01.to_vec()
-    };
-
-    // 从 JS 对象中获取 gx 和 gy
-    let sobel_result = sobel(&g, width, height);
-    let result_obj = js_sys::Object::from(sobel_result);
-
-    let gx_val = js_sys::Reflect::get(&result_obj, &"gx".into()).unwrap();
-    let gy_val = js_sys::Reflect::get(&result_obj, &"gy".into()).unwrap();
-
-    let gx_array = js_sys::Float32Array::from(gx_val);
-    let gy_array = js_sys::Float32Array::from(gy_val);
-
-    let mut gx_vec = vec![0.0f32; g.len()];
-    let mut gy_vec = vec![0.0f32; g.len()];
-    gx_array.copy_to(&mut gx_vec);
-    gy_array.copy_to(&mut gy_vec);
-
-    let mut energy = vec![0.0f32; g.len()];
-    for i in 0..energy.len() {
-        energy[i] = gx_vec[i].abs() + gy_vec[i].abs();
-    }
+/// 逐通道彩色梯度能量
+/// channels 为 "rgb" | "oklab" | "lab"，combine 为 "max" | "sum" | "dizenzo"
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn color_grad_energy(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    sigma: f64,
+    channels: &str,
+    combine: &str,
+    op: &str,
+    norm: &str,
+) -> Result<Vec<f32>, JsError> {
+    let space: ColorSpace = serde_json::from_value(serde_json::Value::from(channels))
+        .map_err(|e| Img2PicError::Decode(format!("energy channels: {}", e)))?;
+    let combine: ChannelCombine = serde_json::from_value(serde_json::Value::from(combine))
+        .map_err(|e| Img2PicError::Decode(format!("channel combine: {}", e)))?;
+    let grad = parse_grad(op, norm)?;
+    Ok(img2pic_core::color_grad_energy(rgba, width, height, sigma, space, combine, grad)?.data)
+}
+
+/// 按透明度处理 RGBA 颜色分量，mode 为 "ignore" | "premultiply" | "mask"
+#[wasm_bindgen]
+pub fn apply_alpha(rgba: &[u8], mode: &str) -> Result<Vec<u8>, JsError> {
+    let mode: AlphaMode = serde_json::from_value(serde_json::Value::from(mode))
+        .map_err(|e| Img2PicError::Decode(format!("alpha mode: {}", e)))?;
+    Ok(img2pic_core::apply_alpha(rgba, mode).into_owned())
+}
+
+/// alpha 通道的梯度能量
+#[wasm_bindgen]
+pub fn alpha_grad_energy(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    sigma: f64,
+    op: &str,
+    norm: &str,
+) -> Result<Vec<f32>, JsError> {
+    Ok(img2pic_core::alpha_grad_energy(rgba, width, height, sigma, parse_grad(op, norm)?)?.data)
+}
+
+/// JPEG 块效应强度（块边界与块内部平均亮度差之比）
+#[wasm_bindgen]
+pub fn jpeg_blockiness(rgba: &[u8], width: usize, height: usize) -> Result<f32, JsError> {
+    Ok(img2pic_core::jpeg_blockiness(rgba, width, height)?)
+}
 
-    energy
+/// 平滑 8x8 块边界处的小台阶
+#[wasm_bindgen]
+pub fn deblock_jpeg(rgba: &[u8], width: usize, height: usize) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::deblock_jpeg(rgba, width, height)?)
 }
 
 /// 方向性能量增强
-/// 增强/削弱水平或垂直边缘
 #[wasm_bindgen]
 pub fn enhance_energy_directional(
     energy: &[f32],
@@ -100,93 +128,53 @@ pub fn enhance_energy_directional(
     height: usize,
     horizontal_factor: f32,
     vertical_factor: f32,
-) -> Vec<f32> {
-    if (horizontal_factor - 1.0).abs() < 0.001 && (vertical_factor - 1.0).abs() < 0.001 {
-        return energy.to_vec();
-    }
-
-    let sobel_result = sobel(energy, width, height);
-    let result_obj = js_sys::Object::from(sobel_result);
-
-    let gx_val = js_sys::Reflect::get(&result_obj, &"gx".into()).unwrap();
-    let gy_val = js_sys::Reflect::get(&result_obj, &"gy".into()).unwrap();
-
-    let gx_array = js_sys::Float32Array::from(gx_val);
-    let gy_array = js_sys::Float32Array::from(gy_val);
-
-    let mut gx_vec = vec![0.0f32; energy.len()];
-    let mut gy_vec = vec![0.0f32; energy.len()];
-    gx_array.copy_to(&mut gx_vec);
-    gy_array.copy_to(&mut gy_vec);
-
-    let mut out = vec![0.0f32; energy.len()];
-
-    for i in 0..out.len() {
-        let mut v = energy[i];
-        if horizontal_factor > 1.0 {
-            v += gy_vec[i].abs() * (horizontal_factor - 1.0);
-        }
-        if vertical_factor > 1.0 {
-            v += gx_vec[i].abs() * (vertical_factor - 1.0);
-        }
-        out[i] = v;
-    }
-
-    // 裁剪到 p99.9
-    let clip_v = quantile_approx(&out, 0.999);
-    for v in out.iter_mut() {
-        *v = v.min(clip_v);
-    }
+) -> Result<Vec<f32>, JsError> {
+    let energy = EnergyMap::new(width, height, energy.to_vec());
+    Ok(img2pic_core::enhance_energy_directional(&energy, horizontal_factor, vertical_factor)?.data)
+}
 
-    out
+/// 结构张量方向性能量（抑制斜向边缘）
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn structure_tensor_energy(
+    energy: &[f32],
+    width: usize,
+    height: usize,
+    horizontal_factor: f32,
+    vertical_factor: f32,
+    rho: f64,
+    clip_quantile: f64,
+    border: Option<String>,
+) -> Result<Vec<f32>, JsError> {
+    let energy = EnergyMap::new(width, height, energy.to_vec());
+    let (h, v, border) = (horizontal_factor, vertical_factor, parse_border(border)?);
+    Ok(img2pic_core::structure_tensor_energy(&energy, h, v, rho, clip_quantile, border)?.data)
 }
 
 /// 将能量图转换为 8 位灰度图（热力图）
 #[wasm_bindgen]
-pub fn to_heatmap_u8(energy: &[f32]) -> Vec<u8> {
-    let p99 = quantile_approx(energy, 0.99);
-    let denom = p99 + 1e-6;
-
-    let mut u8 = vec![0u8; energy.len()];
-    for (i, &v) in energy.iter().enumerate() {
-        let mut normalized = v / denom;
-        if normalized < 0.0 {
-            normalized = 0.0;
-        } else if normalized > 1.0 {
-            normalized = 1.0;
-        }
-        u8[i] = (normalized * 255.0) as u8;
-    }
-
-    u8
+pub fn to_heatmap_u8(energy: &[f32]) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::to_heatmap_u8(energy)?)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_quantile_approx() {
-        let x = vec![1.0, 2.0, 3.0, 4.0, 5.0];
-        let q50 = quantile_approx(&x, 0.5);
-        assert!((q50 - 3.0).abs() < 0.1);
-
-        let q0 = quantile_approx(&x, 0.0);
-        assert_eq!(q0, 1.0);
+fn parse_normalize(params: JsValue) -> Result<NormalizeParams, Img2PicError> {
+    if params.is_undefined() || params.is_null() {
+        return Ok(NormalizeParams::default());
     }
+    serde_wasm_bindgen::from_value(params).map_err(|e| Img2PicError::Decode(format!("normalize params: {}", e)))
+}
 
-    #[test]
-    fn test_rgba_to_gray01() {
-        // 纯白 RGBA
-        let rgba = vec![255u8, 255, 255, 255];
-        let gray = rgba_to_gray01(&rgba, 1, 1);
-        assert_eq!(gray.len(), 1);
-        assert!((gray[0] - 1.0).abs() < 0.01);
-
-        // 纯黑 RGBA
-        let rgba = vec![0u8, 0, 0, 255];
-        let gray = rgba_to_gray01(&rgba, 1, 1);
-        assert_eq!(gray.len(), 1);
-        assert!((gray[0] - 0.0).abs() < 0.01);
-    }
+/// 能量图归一化到 [0, 1]
+/// params 为 { method: "percentile" | "minmax" | "log" | "histeq" | "clahe", quantile, claheTiles, claheClip }
+#[wasm_bindgen]
+pub fn normalize_energy(energy: &[f32], width: usize, height: usize, params: JsValue) -> Result<Vec<f32>, JsError> {
+    let energy = EnergyMap::new(width, height, energy.to_vec());
+    Ok(img2pic_core::normalize_energy(&energy, &parse_normalize(params)?)?)
+}
+
+/// 按指定归一化方式转换为 8 位热力图，参数同 normalize_energy
+#[wasm_bindgen]
+pub fn to_heatmap_u8_with(energy: &[f32], width: usize, height: usize, params: JsValue) -> Result<Vec<u8>, JsError> {
+    let energy = EnergyMap::new(width, height, energy.to_vec());
+    Ok(img2pic_core::to_heatmap_u8_with(&energy, &parse_normalize(params)?)?)
 }
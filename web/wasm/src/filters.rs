@@ -1,120 +1,71 @@
 use wasm_bindgen::prelude::*;
-
-/// 边界反射处理 (reflect101 模式)
-fn reflect101(x: i32, limit: usize) -> usize {
-    if x < 0 {
-        (-x) as usize
-    } else if x as usize >= limit {
-        let limit = limit as i32;
-        (2 * limit - 2 - x) as usize
-    } else {
-        x as usize
-    }
-}
+use img2pic_core::{BorderMode, DenoiseParams, GradientOperator, Img2PicError};
 
 /// 生成 1D 高斯核
 #[wasm_bindgen]
-pub fn gaussian_kernel_1d(sigma: f64) -> Vec<f32> {
-    if sigma <= 0.0 {
-        return vec![1.0];
-    }
-    let radius = (3.0 * sigma).ceil().max(1.0) as i32;
-    let size = (radius * 2 + 1) as usize;
-    let mut k = vec![0.0f32; size];
-    let s2 = sigma * sigma;
-    let mut sum = 0.0f32;
-
-    for i in -radius..=radius {
-        let v = (-(i * i) as f64 / (2.0 * s2)).exp() as f32;
-        k[(i + radius) as usize] = v;
-        sum += v;
-    }
+pub fn gaussian_kernel_1d(sigma: f64) -> Result<Vec<f32>, JsError> {
+    Ok(img2pic_core::gaussian_kernel_1d(sigma)?)
+}
 
-    for i in 0..size {
-        k[i] /= sum;
-    }
+/// 解析边界模式；省略时为 reflect101
+/// border 为 "reflect101" | "reflect" | "replicate" | "constant" | "wrap"
+pub(crate) fn parse_border(border: Option<String>) -> Result<BorderMode, Img2PicError> {
+    Ok(parse_border_opt(border)?.unwrap_or_default())
+}
 
-    k
+/// 解析边界模式；省略时为 None（网格线检测的投影平滑只在范围内取平均）
+pub(crate) fn parse_border_opt(border: Option<String>) -> Result<Option<BorderMode>, Img2PicError> {
+    border
+        .map(|b| {
+            serde_json::from_value(serde_json::Value::from(b))
+                .map_err(|e| Img2PicError::Decode(format!("border mode: {}", e)))
+        })
+        .transpose()
 }
 
 /// 可分离卷积 (先水平后垂直)
 #[wasm_bindgen]
-pub fn convolve_separable(src: &[f32], width: usize, height: usize, k: &[f32]) -> Vec<f32> {
-    let radius = (k.len() - 1) / 2;
-    let mut tmp = vec![0.0f32; src.len()];
-    let mut dst = vec![0.0f32; src.len()];
-
-    // 水平卷积
-    for y in 0..height {
-        let row = y * width;
-        for x in 0..width {
-            let mut acc = 0.0f32;
-            let radius_i = radius as i32;
-            for t in -radius_i..=radius_i {
-                let xx = reflect101(x as i32 + t, width);
-                unsafe {
-                    acc += src.get_unchecked(row + xx) * k.get_unchecked((t + radius_i) as usize);
-                }
-            }
-            tmp[row + x] = acc;
-        }
-    }
-
-    // 垂直卷积
-    for y in 0..height {
-        for x in 0..width {
-            let mut acc = 0.0f32;
-            let radius_i = radius as i32;
-            for t in -radius_i..=radius_i {
-                let yy = reflect101(y as i32 + t, height);
-                unsafe {
-                    acc += tmp.get_unchecked(yy * width + x) * k.get_unchecked((t + radius_i) as usize);
-                }
-            }
-            dst[y * width + x] = acc;
-        }
-    }
-
-    dst
+pub fn convolve_separable(
+    src: &[f32],
+    width: usize,
+    height: usize,
+    k: &[f32],
+    border: Option<String>,
+) -> Result<Vec<f32>, JsError> {
+    Ok(img2pic_core::convolve_separable(src, width, height, k, parse_border(border)?)?)
 }
 
 /// Sobel 边缘检测算子
-/// 返回 (gx, gy) 两个梯度图
+/// 返回 { gx: Float32Array, gy: Float32Array }
 #[wasm_bindgen]
-pub fn sobel(src: &[f32], width: usize, height: usize) -> JsValue {
-    let mut gx = vec![0.0f32; src.len()];
-    let mut gy = vec![0.0f32; src.len()];
+pub fn sobel(src: &[f32], width: usize, height: usize, border: Option<String>) -> Result<JsValue, JsError> {
+    let (gx, gy) = img2pic_core::sobel(src, width, height, parse_border(border)?)?;
 
-    // Sobel kernels
-    // Gx = [-1 0 1; -2 0 2; -1 0 1]
-    // Gy = [-1 -2 -1; 0 0 0; 1 2 1]
-
-    for y in 0..height {
-        let y0 = reflect101(y as i32 - 1, height);
-        let y2 = reflect101(y as i32 + 1, height);
+    let gx_array: js_sys::Float32Array = gx.as_slice().into();
+    let gy_array: js_sys::Float32Array = gy.as_slice().into();
 
-        for x in 0..width {
-            let x0 = reflect101(x as i32 - 1, width);
-            let x2 = reflect101(x as i32 + 1, width);
+    let result = js_sys::Object::new();
+    js_sys::Reflect::set(&result, &"gx".into(), &gx_array).unwrap();
+    js_sys::Reflect::set(&result, &"gy".into(), &gy_array).unwrap();
 
-            unsafe {
-                let a00 = src.get_unchecked(y0 * width + x0);
-                let a01 = src.get_unchecked(y0 * width + x);
-                let a02 = src.get_unchecked(y0 * width + x2);
-                let a10 = src.get_unchecked(y * width + x0);
-                let a12 = src.get_unchecked(y * width + x2);
-                let a20 = src.get_unchecked(y2 * width + x0);
-                let a21 = src.get_unchecked(y2 * width + x);
-                let a22 = src.get_unchecked(y2 * width + x2);
+    Ok(JsValue::from(result))
+}
 
-                let idx = y * width + x;
-                gx[idx] = (-a00 + a02) + (-2.0 * a10 + 2.0 * a12) + (-a20 + a22);
-                gy[idx] = (-a00 - 2.0 * a01 - a02) + (a20 + 2.0 * a21 + a22);
-            }
-        }
-    }
+/// 指定算子的梯度
+/// op 为 "sobel" | "scharr" | "prewitt" | "roberts" | "laplacian" | "dog" | "forwarddiff"
+/// 返回 { gx: Float32Array, gy: Float32Array }
+#[wasm_bindgen]
+pub fn gradient(
+    src: &[f32],
+    width: usize,
+    height: usize,
+    op: &str,
+    border: Option<String>,
+) -> Result<JsValue, JsError> {
+    let op: GradientOperator = serde_json::from_value(serde_json::Value::from(op))
+        .map_err(|e| Img2PicError::Decode(format!("gradient operator: {}", e)))?;
+    let (gx, gy) = img2pic_core::gradient(src, width, height, op, parse_border(border)?)?;
 
-    // 返回对象 { gx: Float32Array, gy: Float32Array }
     let gx_array: js_sys::Float32Array = gx.as_slice().into();
     let gy_array: js_sys::Float32Array = gy.as_slice().into();
 
@@ -122,29 +73,22 @@ pub fn sobel(src: &[f32], width: usize, height: usize) -> JsValue {
     js_sys::Reflect::set(&result, &"gx".into(), &gx_array).unwrap();
     js_sys::Reflect::set(&result, &"gy".into(), &gy_array).unwrap();
 
-    JsValue::from(result)
+    Ok(JsValue::from(result))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn parse_denoise(params: JsValue) -> Result<DenoiseParams, Img2PicError> {
+    serde_wasm_bindgen::from_value(params).map_err(|e| Img2PicError::Decode(format!("denoise params: {}", e)))
+}
 
-    #[test]
-    fn test_gaussian_kernel() {
-        let k = gaussian_kernel_1d(1.0);
-        assert_eq!(k.len(), 7); // radius = ceil(3*1) = 3, size = 7
-        let sum: f32 = k.iter().sum();
-        assert!((sum - 1.0).abs() < 0.0001);
-    }
+/// 单通道保边去噪
+/// params 为 { filter: "bilateral" | "median" | "guided" | "kuwahara", radius, sigmaSpace, sigmaRange, eps }
+#[wasm_bindgen]
+pub fn denoise(src: &[f32], width: usize, height: usize, params: JsValue) -> Result<Vec<f32>, JsError> {
+    Ok(img2pic_core::denoise(src, width, height, &parse_denoise(params)?)?)
+}
 
-    #[test]
-    fn test_reflect101() {
-        assert_eq!(reflect101(0, 10), 0);
-        assert_eq!(reflect101(5, 10), 5);
-        assert_eq!(reflect101(9, 10), 9);
-        assert_eq!(reflect101(-1, 10), 1);
-        assert_eq!(reflect101(-2, 10), 2);
-        assert_eq!(reflect101(10, 10), 8);
-        assert_eq!(reflect101(11, 10), 7);
-    }
+/// RGBA 保边去噪（alpha 通道不变），参数同 denoise
+#[wasm_bindgen]
+pub fn denoise_rgba(rgba: &[u8], width: usize, height: usize, params: JsValue) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::denoise_rgba(rgba, width, height, &parse_denoise(params)?)?)
 }
@@ -1,7 +1,11 @@
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use crate::energy::{rgba_to_gray01, grad_energy, enhance_energy_directional, to_heatmap_u8};
-use crate::grid::{detect_pixel_size, detect_grid_lines, interpolate_lines, complete_edges, sample_pixel_art_direct, sample_pixel_art};
+use img2pic_core::{
+    rgba_to_gray01, grad_energy_with, enhance_energy_directional, to_heatmap_u8,
+    detect_pixel_size_report, detect_grid_lines, interpolate_lines, complete_edges,
+    sample_pixel_art_direct, sample_pixel_art, BorderMode, EnergyMap, GradientParams, GrayImage, Img2PicError,
+    PeriodMethod, SampleMode,
+};
 
 /// RGBA 转灰度图的 JSON 参数
 #[derive(Deserialize)]
@@ -24,6 +28,9 @@ struct GradEnergyParams {
     width: usize,
     height: usize,
     sigma: f64,
+    /// 可选：{ operator, norm }，缺省为 Sobel + L1
+    #[serde(default)]
+    gradient: GradientParams,
 }
 
 /// 梯度能量计算的 JSON 返回值
@@ -68,12 +75,38 @@ struct DetectPixelSizeParams {
     height: usize,
     min_s: usize,
     max_s: usize,
+    #[serde(default)]
+    border: BorderMode,
+    #[serde(default)]
+    method: PeriodMethod,
+    #[serde(default = "default_top_n")]
+    top_n: usize,
+}
+
+fn default_top_n() -> usize {
+    5
+}
+
+/// 像素大小候选的 JSON 表示
+#[derive(Serialize)]
+struct PixelSizeCandidateResult {
+    size: usize,
+    score_x: f32,
+    score_y: f32,
+    score: f32,
 }
 
 /// 像素大小检测的 JSON 返回值
 #[derive(Serialize)]
 struct DetectPixelSizeResult {
     pixel_size: usize,
+    pixel_size_x: usize,
+    pixel_size_y: usize,
+    period_x: f32,
+    period_y: f32,
+    candidates: Vec<PixelSizeCandidateResult>,
+    confidence: f32,
+    multiple: bool,
 }
 
 /// 网格线检测的 JSON 参数
@@ -87,6 +120,8 @@ struct DetectGridLinesParams {
     min_energy: f32,
     smooth_win: usize,
     window_size: usize,
+    #[serde(default)]
+    border: Option<BorderMode>,
 }
 
 /// 网格线检测的 JSON 返回值
@@ -102,6 +137,12 @@ struct InterpolateLinesParams {
     lines: Vec<usize>,
     limit: usize,
     fallback_gap: usize,
+    #[serde(default = "default_interp_threshold")]
+    interp_threshold: f32,
+}
+
+fn default_interp_threshold() -> f32 {
+    1.5
 }
 
 /// 线条插值的 JSON 返回值
@@ -171,123 +212,93 @@ struct SamplePixelArtResult {
     out_rgba: Vec<u8>,
 }
 
+/// 解析 JSON 参数，失败时返回 Decode 错误
+fn parse_params<T: serde::de::DeserializeOwned>(params_json: &str) -> Result<T, Img2PicError> {
+    serde_json::from_str(params_json).map_err(|e| Img2PicError::Decode(format!("JSON parse error: {}", e)))
+}
+
 /// RGBA 转灰度图 - JSON 版本
 #[wasm_bindgen]
-pub fn rgba_to_gray01_json(params_json: String) -> String {
-    web_sys::console::log_1(&"rgba_to_gray01_json called".into());
-    web_sys::console::log_1(&format!("params_json length: {}", params_json.len()).into());
-
-    let params: RgbaToGray01Params = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&RgbaToGray01Result { gray: vec![] }).unwrap();
-        }
-    };
-
-    web_sys::console::log_1(&format!("Parsed params: rgba.len()={}, width={}, height={}",
-        params.rgba.len(), params.width, params.height).into());
+pub fn rgba_to_gray01_json(params_json: String) -> Result<String, JsError> {
+    let params: RgbaToGray01Params = parse_params(&params_json)?;
 
-    let gray = rgba_to_gray01(&params.rgba, params.width, params.height);
+    let gray = rgba_to_gray01(&params.rgba, params.width, params.height)?.data;
     let result = RgbaToGray01Result { gray };
-    serde_json::to_string(&result).unwrap()
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 梯度能量计算 - JSON 版本
 #[wasm_bindgen]
-pub fn grad_energy_json(params_json: String) -> String {
-    web_sys::console::log_1(&"grad_energy_json called".into());
-
-    let params: GradEnergyParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&GradEnergyResult { energy: vec![] }).unwrap();
-        }
-    };
+pub fn grad_energy_json(params_json: String) -> Result<String, JsError> {
+    let params: GradEnergyParams = parse_params(&params_json)?;
 
-    let energy = grad_energy(&params.gray01, params.width, params.height, params.sigma);
+    let gray = GrayImage::new(params.width, params.height, params.gray01);
+    let energy = grad_energy_with(&gray, params.sigma, params.gradient)?.data;
     let result = GradEnergyResult { energy };
-    serde_json::to_string(&result).unwrap()
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 方向性能量增强 - JSON 版本
 #[wasm_bindgen]
-pub fn enhance_energy_directional_json(params_json: String) -> String {
-    web_sys::console::log_1(&"enhance_energy_directional_json called".into());
-
-    let params: EnhanceEnergyDirectionalParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&EnhanceEnergyDirectionalResult { energy: vec![] }).unwrap();
-        }
-    };
+pub fn enhance_energy_directional_json(params_json: String) -> Result<String, JsError> {
+    let params: EnhanceEnergyDirectionalParams = parse_params(&params_json)?;
 
-    let energy = enhance_energy_directional(
-        &params.energy,
-        params.width,
-        params.height,
-        params.horizontal_factor,
-        params.vertical_factor,
-    );
+    let energy = EnergyMap::new(params.width, params.height, params.energy);
+    let energy = enhance_energy_directional(&energy, params.horizontal_factor, params.vertical_factor)?.data;
     let result = EnhanceEnergyDirectionalResult { energy };
-    serde_json::to_string(&result).unwrap()
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 能量图转热力图 - JSON 版本
 #[wasm_bindgen]
-pub fn to_heatmap_u8_json(params_json: String) -> String {
-    web_sys::console::log_1(&"to_heatmap_u8_json called".into());
-
-    let params: ToHeatmapU8Params = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&ToHeatmapU8Result { heatmap: vec![] }).unwrap();
-        }
-    };
+pub fn to_heatmap_u8_json(params_json: String) -> Result<String, JsError> {
+    let params: ToHeatmapU8Params = parse_params(&params_json)?;
 
-    let heatmap = to_heatmap_u8(&params.energy);
+    let heatmap = to_heatmap_u8(&params.energy)?;
     let result = ToHeatmapU8Result { heatmap };
-    serde_json::to_string(&result).unwrap()
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 像素大小检测 - JSON 版本
 #[wasm_bindgen]
-pub fn detect_pixel_size_json(params_json: String) -> String {
-    web_sys::console::log_1(&"detect_pixel_size_json called".into());
-
-    let params: DetectPixelSizeParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&DetectPixelSizeResult { pixel_size: 8 }).unwrap();
-        }
-    };
-
-    web_sys::console::log_1(&format!("Parsed params: energy_u8.len()={}, width={}, height={}",
-        params.energy_u8.len(), params.width, params.height).into());
+pub fn detect_pixel_size_json(params_json: String) -> Result<String, JsError> {
+    let params: DetectPixelSizeParams = parse_params(&params_json)?;
 
-    let pixel_size = detect_pixel_size(&params.energy_u8, params.width, params.height, params.min_s, params.max_s);
-    let result = DetectPixelSizeResult { pixel_size };
-    serde_json::to_string(&result).unwrap()
+    let report = detect_pixel_size_report(
+        &params.energy_u8,
+        params.width,
+        params.height,
+        params.min_s,
+        params.max_s,
+        params.border,
+        params.method,
+        params.top_n,
+    )?;
+    let size = report.size;
+    let candidates = report
+        .candidates
+        .iter()
+        .map(|c| PixelSizeCandidateResult { size: c.size, score_x: c.score_x, score_y: c.score_y, score: c.score })
+        .collect();
+    let result = DetectPixelSizeResult {
+        pixel_size: size.combined,
+        pixel_size_x: size.x,
+        pixel_size_y: size.y,
+        period_x: size.period_x,
+        period_y: size.period_y,
+        candidates,
+        confidence: report.confidence,
+        multiple: report.multiple,
+    };
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 网格线检测 - JSON 版本
 #[wasm_bindgen]
-pub fn detect_grid_lines_json(params_json: String) -> String {
-    web_sys::console::log_1(&"detect_grid_lines_json called".into());
-
-    let params: DetectGridLinesParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&DetectGridLinesResult { x_lines: vec![], y_lines: vec![] }).unwrap();
-        }
-    };
+pub fn detect_grid_lines_json(params_json: String) -> Result<String, JsError> {
+    let params: DetectGridLinesParams = parse_params(&params_json)?;
 
-    let js_result = detect_grid_lines(
+    let lines = detect_grid_lines(
         &params.energy_u8,
         params.width,
         params.height,
@@ -296,168 +307,81 @@ pub fn detect_grid_lines_json(params_json: String) -> String {
         params.min_energy,
         params.smooth_win,
         params.window_size,
-    );
-
-    // 解析 JS 结果对象
-    let result_obj = js_sys::Object::from(js_result);
-    let x_lines_val = js_sys::Reflect::get(&result_obj, &"xLines".into()).unwrap();
-    let y_lines_val = js_sys::Reflect::get(&result_obj, &"yLines".into()).unwrap();
-
-    let x_lines_array = js_sys::Uint32Array::from(x_lines_val);
-    let y_lines_array = js_sys::Uint32Array::from(y_lines_val);
-
-    let mut x_lines_u32 = vec![0u32; x_lines_array.length() as usize];
-    let mut y_lines_u32 = vec![0u32; y_lines_array.length() as usize];
-    x_lines_array.copy_to(&mut x_lines_u32);
-    y_lines_array.copy_to(&mut y_lines_u32);
-
-    // 转换为 usize
-    let x_lines: Vec<usize> = x_lines_u32.into_iter().map(|x| x as usize).collect();
-    let y_lines: Vec<usize> = y_lines_u32.into_iter().map(|x| x as usize).collect();
+        params.border,
+    )?;
 
-    let result = DetectGridLinesResult { x_lines, y_lines };
-    serde_json::to_string(&result).unwrap()
+    let result = DetectGridLinesResult { x_lines: lines.x_lines, y_lines: lines.y_lines };
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 线条插值 - JSON 版本
 #[wasm_bindgen]
-pub fn interpolate_lines_json(params_json: String) -> String {
-    web_sys::console::log_1(&"interpolate_lines_json called".into());
-
-    let params: InterpolateLinesParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&InterpolateLinesResult { lines: vec![] }).unwrap();
-        }
-    };
+pub fn interpolate_lines_json(params_json: String) -> Result<String, JsError> {
+    let params: InterpolateLinesParams = parse_params(&params_json)?;
 
-    let lines = interpolate_lines(&params.lines, params.limit, params.fallback_gap);
+    let lines = interpolate_lines(&params.lines, params.limit, params.fallback_gap, params.interp_threshold)?;
     let result = InterpolateLinesResult { lines };
-    serde_json::to_string(&result).unwrap()
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 完善边缘 - JSON 版本
 #[wasm_bindgen]
-pub fn complete_edges_json(params_json: String) -> String {
-    web_sys::console::log_1(&"complete_edges_json called".into());
-
-    let params: CompleteEdgesParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&CompleteEdgesResult { lines: vec![] }).unwrap();
-        }
-    };
+pub fn complete_edges_json(params_json: String) -> Result<String, JsError> {
+    let params: CompleteEdgesParams = parse_params(&params_json)?;
 
-    let lines = complete_edges(&params.all_lines, params.limit, params.typical_gap, params.gap_tolerance);
+    let lines = complete_edges(&params.all_lines, params.limit, params.typical_gap, params.gap_tolerance)?;
     let result = CompleteEdgesResult { lines };
-    serde_json::to_string(&result).unwrap()
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 直接像素采样 - JSON 版本
 #[wasm_bindgen]
-pub fn sample_pixel_art_direct_json(params_json: String) -> String {
-    web_sys::console::log_1(&"sample_pixel_art_direct_json called".into());
-
-    let params: SamplePixelArtDirectParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&SamplePixelArtDirectResult {
-                out_w: 0,
-                out_h: 0,
-                out_rgb: vec![],
-                out_rgba: vec![],
-            }).unwrap();
-        }
-    };
+pub fn sample_pixel_art_direct_json(params_json: String) -> Result<String, JsError> {
+    let params: SamplePixelArtDirectParams = parse_params(&params_json)?;
 
-    web_sys::console::log_1(&format!("Parsed params: rgb.len()={}, width={}, height={}, target_width={}, target_height={}",
-        params.rgb.len(), params.width, params.height, params.target_width, params.target_height).into());
-
-    let js_result = sample_pixel_art_direct(
+    let art = sample_pixel_art_direct(
         &params.rgb,
         params.width,
         params.height,
         params.target_width,
         params.target_height,
-        params.mode,
+        SampleMode::from_u32(params.mode),
         params.weight_ratio,
         params.upscale_factor,
         params.native_res,
-    );
-
-    // 解析 JS 结果对象
-    let result_obj = js_sys::Object::from(js_result);
-    let out_w_val = js_sys::Reflect::get(&result_obj, &"outW".into()).unwrap();
-    let out_h_val = js_sys::Reflect::get(&result_obj, &"outH".into()).unwrap();
-    let out_rgb_val = js_sys::Reflect::get(&result_obj, &"outRgb".into()).unwrap();
-    let out_rgba_val = js_sys::Reflect::get(&result_obj, &"outRgba".into()).unwrap();
+    )?;
 
-    let out_w = out_w_val.as_f64().unwrap() as usize;
-    let out_h = out_h_val.as_f64().unwrap() as usize;
-
-    let out_rgb_array = js_sys::Uint8Array::from(out_rgb_val);
-    let out_rgba_array = js_sys::Uint8Array::from(out_rgba_val);
-
-    let mut out_rgb = vec![0u8; out_rgb_array.length() as usize];
-    let mut out_rgba = vec![0u8; out_rgba_array.length() as usize];
-    out_rgb_array.copy_to(&mut out_rgb);
-    out_rgba_array.copy_to(&mut out_rgba);
-
-    let result = SamplePixelArtDirectResult { out_w, out_h, out_rgb, out_rgba };
-    serde_json::to_string(&result).unwrap()
+    let result = SamplePixelArtDirectResult {
+        out_w: art.width,
+        out_h: art.height,
+        out_rgb: art.rgb,
+        out_rgba: art.rgba,
+    };
+    Ok(serde_json::to_string(&result)?)
 }
 
 /// 基于网格的像素采样 - JSON 版本
 #[wasm_bindgen]
-pub fn sample_pixel_art_json(params_json: String) -> String {
-    web_sys::console::log_1(&"sample_pixel_art_json called".into());
-
-    let params: SamplePixelArtParams = match serde_json::from_str(&params_json) {
-        Ok(p) => p,
-        Err(e) => {
-            web_sys::console::error_1(&format!("JSON parse error: {}", e).into());
-            return serde_json::to_string(&SamplePixelArtResult {
-                out_w: 0,
-                out_h: 0,
-                out_rgb: vec![],
-                out_rgba: vec![],
-            }).unwrap();
-        }
-    };
+pub fn sample_pixel_art_json(params_json: String) -> Result<String, JsError> {
+    let params: SamplePixelArtParams = parse_params(&params_json)?;
 
-    let js_result = sample_pixel_art(
+    let art = sample_pixel_art(
         &params.rgb,
         params.width,
         params.height,
         &params.all_x,
         &params.all_y,
-        params.mode,
+        SampleMode::from_u32(params.mode),
         params.weight_ratio,
         params.upscale_factor,
         params.native_res,
-    );
+    )?;
 
-    // 解析 JS 结果对象
-    let result_obj = js_sys::Object::from(js_result);
-    let out_w_val = js_sys::Reflect::get(&result_obj, &"outW".into()).unwrap();
-    let out_h_val = js_sys::Reflect::get(&result_obj, &"outH".into()).unwrap();
-    let out_rgb_val = js_sys::Reflect::get(&result_obj, &"outRgb".into()).unwrap();
-    let out_rgba_val = js_sys::Reflect::get(&result_obj, &"outRgba".into()).unwrap();
-
-    let out_w = out_w_val.as_f64().unwrap() as usize;
-    let out_h = out_h_val.as_f64().unwrap() as usize;
-
-    let out_rgb_array = js_sys::Uint8Array::from(out_rgb_val);
-    let out_rgba_array = js_sys::Uint8Array::from(out_rgba_val);
-
-    let mut out_rgb = vec![0u8; out_rgb_array.length() as usize];
-    let mut out_rgba = vec![0u8; out_rgba_array.length() as usize];
-    out_rgb_array.copy_to(&mut out_rgb);
-    out_rgba_array.copy_to(&mut out_rgba);
-
-    let result = SamplePixelArtResult { out_w, out_h, out_rgb, out_rgba };
-    serde_json::to_string(&result).unwrap()
+    let result = SamplePixelArtResult {
+        out_w: art.width,
+        out_h: art.height,
+        out_rgb: art.rgb,
+        out_rgba: art.rgba,
+    };
+    Ok(serde_json::to_string(&result)?)
 }
@@ -1,12 +1,28 @@
+//! img2pic WASM 绑定层
+//!
+//! 算法实现位于 `img2pic-core`，这里只负责 JS 类型转换。
+
 use wasm_bindgen::prelude::*;
 
 mod filters;
 mod energy;
 mod grid;
+mod json_api;
+mod quantize;
+mod palette;
+mod pipeline;
+#[cfg(feature = "codec")]
+mod codec;
 
 pub use filters::*;
 pub use energy::*;
 pub use grid::*;
+pub use json_api::*;
+pub use quantize::*;
+pub use palette::*;
+pub use pipeline::*;
+#[cfg(feature = "codec")]
+pub use codec::*;
 
 // 手动初始化函数（替代 #[wasm_bindgen(start)]）
 #[wasm_bindgen]
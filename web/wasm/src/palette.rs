@@ -0,0 +1,109 @@
+use wasm_bindgen::prelude::*;
+use img2pic_core::{BuiltinPalette, ColorSpace, DitherParams, Img2PicError, PaletteSort};
+
+/// 扁平 RGB 数组转颜色列表
+fn unflatten(palette: &[u8]) -> Result<Vec<[u8; 3]>, Img2PicError> {
+    if !palette.len().is_multiple_of(3) {
+        return Err(Img2PicError::InvalidParams(format!(
+            "palette length must be a multiple of 3, got {}",
+            palette.len()
+        )));
+    }
+    Ok(palette.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect())
+}
+
+fn flatten(colors: &[[u8; 3]]) -> Vec<u8> {
+    colors.iter().flatten().copied().collect()
+}
+
+/// 内置调色板（扁平 RGB），名称如 pico8 / nes / gameboy / cga / db16 / db32 / endesga32
+#[wasm_bindgen]
+pub fn builtin_palette(name: &str) -> Result<Vec<u8>, JsError> {
+    let palette = BuiltinPalette::from_name(name)
+        .ok_or_else(|| Img2PicError::InvalidParams(format!("unknown palette: {}", name)))?;
+    Ok(flatten(palette.colors()))
+}
+
+/// 解析调色板文件文本（.gpl / .hex / .pal / Paint.NET .txt），返回扁平 RGB
+#[wasm_bindgen]
+pub fn parse_palette(text: &str) -> Result<Vec<u8>, JsError> {
+    Ok(flatten(&img2pic_core::parse_palette(text)?))
+}
+
+fn parse_space(space: &str) -> Result<ColorSpace, Img2PicError> {
+    serde_json::from_value(serde_json::Value::from(space))
+        .map_err(|e| Img2PicError::Decode(format!("color space: {}", e)))
+}
+
+/// 映射到固定调色板；space 为 "rgb" | "lab" | "oklab"
+#[wasm_bindgen]
+pub fn map_to_palette(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    palette: &[u8],
+    space: &str,
+    alpha_threshold: u8,
+) -> Result<Vec<u8>, JsError> {
+    let space = parse_space(space)?;
+    let palette = unflatten(palette)?;
+    Ok(img2pic_core::map_to_palette(rgba, width, height, &palette, space, alpha_threshold)?)
+}
+
+/// 带抖动地映射到调色板
+/// params 为 {mode, strength, skipTransparent, alphaThreshold}，mode 如 "bayer4" / "floydsteinberg" / "bluenoise"
+#[wasm_bindgen]
+pub fn dither_to_palette(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    palette: &[u8],
+    space: &str,
+    params: JsValue,
+) -> Result<Vec<u8>, JsError> {
+    let params: DitherParams = serde_wasm_bindgen::from_value(params)
+        .map_err(|e| Img2PicError::Decode(format!("dither params: {}", e)))?;
+    let space = parse_space(space)?;
+    let palette = unflatten(palette)?;
+    Ok(img2pic_core::dither_to_palette(rgba, width, height, &palette, space, &params)?)
+}
+
+/// 提取像素画实际使用的颜色；sort 为 "frequency" | "hue"
+/// 返回 [{color: [r, g, b], count, coverage}]，coverage 为百分比
+#[wasm_bindgen]
+pub fn extract_palette(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    alpha_threshold: u8,
+    sort: &str,
+) -> Result<JsValue, JsError> {
+    let sort: PaletteSort = serde_json::from_value(serde_json::Value::from(sort))
+        .map_err(|e| Img2PicError::Decode(format!("palette sort: {}", e)))?;
+    let entries = img2pic_core::extract_palette(rgba, width, height, alpha_threshold, sort)?;
+    Ok(serde_wasm_bindgen::to_value(&entries)?)
+}
+
+/// 导出 GIMP .gpl 文本（palette 为扁平 RGB）
+#[wasm_bindgen]
+pub fn export_palette_gpl(palette: &[u8], name: &str) -> Result<String, JsError> {
+    Ok(img2pic_core::export_gpl(&unflatten(palette)?, name))
+}
+
+/// 导出 .hex 文本
+#[wasm_bindgen]
+pub fn export_palette_hex(palette: &[u8]) -> Result<String, JsError> {
+    Ok(img2pic_core::export_hex(&unflatten(palette)?))
+}
+
+/// 导出 .ase 色板字节
+#[wasm_bindgen]
+pub fn export_palette_ase(palette: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::export_ase(&unflatten(palette)?))
+}
+
+/// 色卡条 RGBA（宽 = 颜色数 * swatch_size，高 = swatch_size）
+#[wasm_bindgen]
+pub fn palette_swatch_strip(palette: &[u8], swatch_size: usize) -> Result<Vec<u8>, JsError> {
+    Ok(img2pic_core::swatch_strip(&unflatten(palette)?, swatch_size)?.data)
+}
@@ -0,0 +1,90 @@
+use wasm_bindgen::prelude::*;
+use img2pic_core::{Img2PicError, Pipeline, PipelineParams, PipelineResult, RgbaImage};
+
+/// 复制为独立的 ArrayBuffer（便于 Worker 中 transfer）
+fn to_array_buffer(data: &[u8]) -> js_sys::ArrayBuffer {
+    js_sys::Uint8Array::from(data).buffer()
+}
+
+/// 网格线转为 number[]
+fn lines_to_js(lines: &[usize]) -> js_sys::Array {
+    lines.iter().map(|&v| JsValue::from(v as u32)).collect()
+}
+
+fn set(obj: &js_sys::Object, key: &str, value: &JsValue) {
+    js_sys::Reflect::set(obj, &key.into(), value).unwrap();
+}
+
+/// 结果转为与前端 `PipelineResult` 相同结构的 JS 对象
+fn pipeline_result_to_js(res: &PipelineResult) -> JsValue {
+    let result = js_sys::Object::new();
+    set(&result, "width", &JsValue::from(res.width as u32));
+    set(&result, "height", &JsValue::from(res.height as u32));
+    set(&result, "detectedPixelSize", &JsValue::from(res.detected_pixel_size as u32));
+    set(&result, "detectedPixelSizeX", &JsValue::from(res.detected_pixel_size_x as u32));
+    set(&result, "detectedPixelSizeY", &JsValue::from(res.detected_pixel_size_y as u32));
+    set(&result, "detectedPeriodX", &JsValue::from(res.detected_period_x));
+    set(&result, "detectedPeriodY", &JsValue::from(res.detected_period_y));
+    if let Some(report) = &res.pixel_size_report {
+        // { size, candidates: [{ size, scoreX, scoreY, score }], confidence, multiple }
+        set(&result, "pixelSizeReport", &serde_wasm_bindgen::to_value(report).unwrap_or(JsValue::UNDEFINED));
+    }
+    set(&result, "energyU8", &to_array_buffer(&res.energy_u8));
+    if !res.energy_x_u8.is_empty() {
+        set(&result, "energyXU8", &to_array_buffer(&res.energy_x_u8));
+        set(&result, "energyYU8", &to_array_buffer(&res.energy_y_u8));
+    }
+    set(&result, "xLines", &lines_to_js(&res.x_lines));
+    set(&result, "yLines", &lines_to_js(&res.y_lines));
+    set(&result, "allXLines", &lines_to_js(&res.all_x_lines));
+    set(&result, "allYLines", &lines_to_js(&res.all_y_lines));
+    set(&result, "allXPositions", &js_sys::Float32Array::from(res.all_x_positions.as_slice()));
+    set(&result, "allYPositions", &js_sys::Float32Array::from(res.all_y_positions.as_slice()));
+
+    if let Some(art) = &res.pixel_art {
+        let pixel_art = js_sys::Object::new();
+        set(&pixel_art, "width", &JsValue::from(art.width as u32));
+        set(&pixel_art, "height", &JsValue::from(art.height as u32));
+        set(&pixel_art, "rgb", &to_array_buffer(&art.rgb));
+        set(&pixel_art, "rgba", &to_array_buffer(&art.rgba));
+        set(&pixel_art, "upscaleFactor", &JsValue::from(res.upscale_factor as u32));
+        set(&result, "pixelArt", &pixel_art);
+    }
+
+    if !res.palette.is_empty() {
+        let palette: Vec<u8> = res.palette.iter().flatten().copied().collect();
+        set(&result, "palette", &to_array_buffer(&palette));
+    }
+
+    let warnings: js_sys::Array = res.warnings.iter().map(|w| JsValue::from(w.as_str())).collect();
+    set(&result, "warnings", &warnings);
+
+    JsValue::from(result)
+}
+
+/// 完整处理流程 - 一次调用完成 能量图 → 网格检测 → 像素采样
+/// params 为前端 `PipelineParams` 对象（多余字段如 wasmEnabled 会被忽略）
+#[wasm_bindgen]
+pub fn run_pipeline(rgba: &[u8], width: usize, height: usize, params: JsValue) -> Result<JsValue, JsError> {
+    let params: PipelineParams = serde_wasm_bindgen::from_value(params)
+        .map_err(|e| Img2PicError::InvalidParams(format!("pipeline params: {}", e)))?;
+    let image = RgbaImage::new(width, height, rgba.to_vec());
+    let res = Pipeline::run(&image, &params)?;
+    Ok(pipeline_result_to_js(&res))
+}
+
+/// 直接从图像文件字节（PNG / JPEG / WebP / BMP / GIF）运行完整流程
+/// 结果额外包含 pixelArt.png（带透明通道的 PNG 字节）
+#[cfg(feature = "codec")]
+#[wasm_bindgen]
+pub fn run_pipeline_bytes(bytes: &[u8], params: JsValue) -> Result<JsValue, JsError> {
+    let params: PipelineParams = serde_wasm_bindgen::from_value(params)
+        .map_err(|e| Img2PicError::InvalidParams(format!("pipeline params: {}", e)))?;
+    let res = Pipeline::run_bytes(bytes, &params)?;
+    let result = pipeline_result_to_js(&res);
+    if let Some(png) = res.pixel_art_png()? {
+        let pixel_art = js_sys::Reflect::get(&result, &"pixelArt".into()).unwrap();
+        set(pixel_art.unchecked_ref(), "png", &to_array_buffer(&png));
+    }
+    Ok(result)
+}
@@ -0,0 +1,22 @@
+use wasm_bindgen::prelude::*;
+use img2pic_core::{Img2PicError, QuantizeParams};
+
+/// 颜色量化（应在原生分辨率像素画上调用）
+/// params 为 {method, colors, similarityThreshold, alphaThreshold}，返回 {rgba, palette}
+/// palette 为扁平 RGB 数组
+#[wasm_bindgen]
+pub fn quantize_colors(rgba: &[u8], width: usize, height: usize, params: JsValue) -> Result<JsValue, JsError> {
+    let params: QuantizeParams = if params.is_undefined() || params.is_null() {
+        QuantizeParams::default()
+    } else {
+        serde_wasm_bindgen::from_value(params)
+            .map_err(|e| Img2PicError::Decode(format!("quantize params: {}", e)))?
+    };
+    let q = img2pic_core::quantize_rgba(rgba, width, height, &params)?;
+    let palette: Vec<u8> = q.palette.iter().flatten().copied().collect();
+
+    let result = js_sys::Object::new();
+    js_sys::Reflect::set(&result, &"rgba".into(), &js_sys::Uint8Array::from(&q.rgba[..])).unwrap();
+    js_sys::Reflect::set(&result, &"palette".into(), &js_sys::Uint8Array::from(&palette[..])).unwrap();
+    Ok(JsValue::from(result))
+}